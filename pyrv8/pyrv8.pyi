@@ -1,13 +1,182 @@
-from typing import Any
+import asyncio
+import builtins
+import re
+from typing import Any, Callable, Coroutine, Iterable, TypeVar
+
+T = TypeVar("T")
 
 class Context:
     """Synchronous Javascript Runtime Powered by rustyscript and pyo3 written in Rust"""
-    def __init__(self, timeout: float | None = ..., max_heap_size: int | None = ...) -> None: ...
+    def __init__(
+        self,
+        timeout: float | None = ...,
+        max_heap_size: int | None = ...,
+        symbol_keys: bool | None = ...,
+        stack_trace_limit: int | None = ...,
+        convert_objects: bool | None = ...,
+        timeout_ms: int | None = ...,
+        max_source_bytes: int | None = ...,
+        cache_results: bool | None = ...,
+        memoryview: bool | None = ...,
+        shared_pool: bool | None = ...,
+        numeric_strings_as_decimal: bool | None = ...,
+        shared_module_cache: bool | None = ...,
+        regex_as_pattern: bool | None = ...,
+        minimal: bool | None = ...,
+        random_seed: int | None = ...,
+        preserve_surrogates: bool | None = ...,
+        default_locale: str | None = ...,
+        max_result_bytes: int | None = ...,
+        lock_timeout: float | None = ...,
+        import_map: dict[str, Any] | None = ...,
+        console_error_raises: bool | None = ...,
+        mark_circular: bool | None = ...,
+        module_dir: str | None = ...,
+        decimal_args_as_strings: bool | None = ...,
+        name: str | None = ...,
+        verbose_errors: bool | None = ...,
+        conditions: list[str] | None = ...,
+        web_apis: list[str] | None = ...,
+        integer_keys: bool | None = ...,
+        max_concurrent_ops: int | None = ...,
+        env: dict[str, str] | None = ...,
+        env_writable: bool | None = ...,
+        log_errors: bool | None = ...,
+        pin_thread: bool | None = ...,
+    ) -> None:
+        """
+        :param symbol_keys: when enabled, symbol-keyed properties of returned
+            objects are placed under a `__symbols__` key
+        :param stack_trace_limit: sets `Error.stackTraceLimit` so deeper
+            stack traces are kept (v8 defaults to 10 frames)
+        :param convert_objects: pass dataclasses, pydantic models and other
+            objects to javascript as objects made from their fields
+        :param timeout_ms: the same as timeout but in whole milliseconds,
+            can't be combined with timeout
+        :param max_source_bytes: sources for `eval` and modules longer than
+            this many bytes raise `ValueError` before being compiled, larger
+            files they import fail to load with `RuntimeError`
+        :param cache_results: hand back the same python object for objects
+            that hash the same as an earlier result instead of rebuilding it,
+            mutating a returned object affects later identical results
+        :param memoryview: return `ArrayBuffer`s and typed arrays as read only
+            memoryviews over the javascript memory instead of copies, buffers
+            that can resize or are shared between threads come back as bytes
+        :param shared_pool: run async ops on a tokio runtime shared by every
+            context that asks for it instead of one per context, this caps the
+            threads used but a blocking op can hold up the other contexts
+        :param numeric_strings_as_decimal: return strings written like a
+            decimal number such as `"1.10"` as `decimal.Decimal` so no
+            precision is lost, any other string is left alone
+        :param shared_module_cache: share loaded modules and their v8 code
+            caches between every context that enables this, modules loaded by
+            one context can be imported by the others and are compiled from
+            the cache instead of from scratch
+        :param regex_as_pattern: return `RegExp`s compiled into `re.Pattern`
+            instead of as `JsRegExp`, raising `ValueError` for patterns python
+            can't compile
+        :param minimal: leave only the ECMAScript builtins and `WebAssembly`
+            in the global scope, `console`, `fetch`, timers, `Deno` and the
+            rest are removed so referencing them raises a `ReferenceError`.
+            Features built on `console` or `Deno` don't work in this mode
+        :param random_seed: makes `Math.random` and `crypto.getRandomValues`
+            deterministic, the same seed gives the same sequence every run.
+            Not suitable for anything that needs real randomness
+        :param preserve_surrogates: keeps lone surrogates in returned strings
+            the way the `surrogatepass` error handler does, so
+            `s.encode("utf-16-le", "surrogatepass")` gives back the exact
+            code units javascript had. Otherwise they're replaced with U+FFFD.
+            Arguments still have to be valid unicode
+        :param default_locale: BCP 47 tag such as `"de-DE"` that `Intl` and the
+            `toLocaleString` methods use when no locale is passed, instead of
+            whatever the host is set to. Raises `ValueError` if malformed
+        :param max_result_bytes: results that come to more than about this
+            many bytes as json raise `ValueError`, the conversion stops as
+            soon as it gets that far
+        :param lock_timeout: seconds a method waits for another thread using
+            the runtime before raising `RuntimeError("runtime busy")`, the
+            default waits as long as it takes
+        :param import_map: a web style import map, either the contents of an
+            `importmap.json` or just its `imports`, mapping bare specifiers
+            such as `"react"` or prefixes such as `"lib/"` to urls or paths.
+            Relative paths are taken from the current directory and the files
+            mapped to can be imported without loading them first. `scopes`
+            aren't supported, malformed entries raise `ValueError`
+        :param console_error_raises: any `console.error` call raises a
+            `JsConsoleError` carrying its arguments once `eval`, `advance` or
+            `run_until_complete` returns. Only the first call since the last
+            of those is raised
+        :param mark_circular: results holding an object that contains itself
+            raise `ValueError("circular reference")`, with this the inner
+            reference becomes the string `"[Circular]"` instead. Objects that
+            are merely shared are converted everywhere they appear
+        :param module_dir: loads every `.js` and `.ts` module in the directory
+            in filename order as the context is made, raising the error of the
+            first that fails. Their handles are kept in `modules`
+        :param decimal_args_as_strings: `Decimal` and `Fraction` arguments are
+            passed as javascript numbers, warning with a `RuntimeWarning` when
+            that loses precision. With this they're passed as their exact text
+            instead, such as `"1.10"` or `"1/3"`
+        :param name: put in square brackets at the start of the message of
+            every `RuntimeError` and `TimeoutError` the context raises, an id
+            such as `context-3` is made up when it's left out
+        :param verbose_errors: `True` puts the source line, the stack and the
+            `cause` chain of thrown errors in the message, `False` only the
+            exception message. Left out errors read the way rustyscript formats them
+        :param conditions: resolves bare specifiers naming packages in the
+            `node_modules` directories above a module through the `exports` of
+            their `package.json`, picking the first entry whose condition is in
+            this list or is `default`, such as `["import", "node"]`. Without it
+            packages aren't looked up at all
+        :param web_apis: keeps only the web globals of the apis named, out of
+            `console`, `url`, `encoding`, `crypto`, `timers`, `blob`, `fetch`,
+            `streams`, `events`, `websocket` and `webstorage`. The globals of
+            the rest are removed, apis the build doesn't include stay missing.
+            Unknown names raise a `ValueError`, as does using it with `minimal`
+        :param integer_keys: keys of returned objects written like an integer
+            such as `"0"` or `"-3"` become `int` keys, ones like `"01"` that
+            wouldn't read back the same stay strings
+        :param max_concurrent_ops: at most this many `fetch` calls, async
+            python functions and `setTimeout` timers are in flight at once,
+            the rest wait for one of them to finish before they start.
+            `setInterval` isn't limited
+        :param env: string variables scripts read from `process.env`, and from
+            `Deno.env.get` when the runtime has no env of its own
+        :param env_writable: lets scripts change and delete `env` variables,
+            otherwise it's frozen and `Deno.env.set` throws
+        :param log_errors: errors thrown by timers and microtasks and promise
+            rejections nobody handled are written to `sys.stderr` with their
+            stack rather than raised, a handler from
+            `set_unhandled_rejection_handler` still takes the rejections
+        :param pin_thread: raises `RuntimeError` when the context is used from
+            any thread but the one that made it, instead of the threads taking
+            turns on its lock
+        """
+    def __enter__(self) -> "Context":...
+    def __exit__(self, *exc_info: object) -> bool:
+        """Calls `close`"""
+    def set_exit_handler(self, code: str, timeout: float | None = ...) -> None:
+        """Registers javascript for `close` to run so scripts can flush and
+        release what they hold, it's terminated after `timeout` seconds
+        (1 by default). Replaces any earlier handler"""
+    def close(self) -> None:
+        """Runs the exit handler then gives up on pending promises and timers
+        like `abort_all` and ends every `console_stream`. Errors in the handler
+        are reported as a `RuntimeWarning` instead of raised. Only the first
+        call does anything, every other method raises `RuntimeError("context
+        closed")` afterwards while `name` stays readable"""
+    @property
+    def name(self) -> str:...
+    @property
+    def modules(self) -> list["JsHandle"]:
+        """Handles of the modules loaded from `module_dir`, in load order"""
     @property
     def timeout(self) -> float:...
     @property
     def current_dir(self) -> str:...
-    def set_current_dir(self, path: str) -> None:...
+    def set_current_dir(self, path: str, create: bool | None = ...) -> None:
+        """Raises `NotADirectoryError` for missing paths unless `create` is
+        set, in which case the directory and its parents are made first"""
     def advance(self, wait_for_inspector: bool | None = ..., pump_v8_message_loop: bool | None = ...) -> bool:
         """
         Advances eventloop by a single tick this best used
@@ -15,15 +184,239 @@ class Context:
         This is meant to be used with Javascript Promise Values since
         an asyncio eventloop can call this if it's waiting on a Promise value
         """
-    def eval(self, code:str) -> Any:...
-    def call(self, name:str, *args) -> Any:...
+    def drain_microtasks(self) -> None:
+        """Runs every pending microtask such as the continuations of resolved
+        promises, without firing timers or polling async ops like `advance`"""
+    def eval(
+        self,
+        code:str,
+        retries: int | None = ...,
+        cancel_token: "CancelToken | None" = ...,
+        return_timing: bool | None = ...,
+        allow_top_level_await: bool | None = ...,
+    ) -> Any:
+        """`retries` re-runs the code when it throws an `Error`, `TypeError` or
+        `ReferenceError`, never on syntax errors, timeouts or exceptions python
+        functions raised, backing off exponentially from 10ms between attempts.
+        Tripping `cancel_token` from another thread stops the code and raises
+        `asyncio.CancelledError`, the GIL is released while it runs.
+        `return_timing` returns `(result, timing)` where timing has `wall_ms`
+        for the run itself, `lock_ms` for the wait on the runtime lock and
+        `cpu_ms` on platforms that can measure it.
+        `allow_top_level_await` runs the code in an async function so it can
+        `await` directly and returns what it resolves to, a single expression
+        is returned as is while statements have to `return` their result. The
+        context's timeout still applies"""
+    def profile(self, code: str) -> tuple[Any, str]:
+        """Evaluates code under the v8 CPU profiler and returns the result with
+        a `.cpuprofile` json string for Chrome DevTools, only available when
+        built with the `profiler` feature"""
+    def eval_display(self, code: str) -> str:
+        """Evaluates code and returns the result formatted the way the node
+        REPL shows it, such as `{ a: 1, b: [ 1, 2 ] }`, instead of converting
+        it into a python object"""
+    def eval_into(self, name: str, code: str) -> None:
+        """Evaluates code and binds the result to `globalThis[name]` without
+        converting it, so functions and class instances stay as they are"""
+    def last_error(self) -> BaseException | None:
+        """Takes the most recent error the event loop ran into, such as a timer
+        callback throwing with nothing to catch it, so polling code can check
+        for one without a rejection handler. `advance`, `run_until_complete`,
+        stepping a promise, the rejection handler and `log_errors` all keep
+        theirs here. Reading it clears it"""
+    def eval_assert(self, code: str, predicate: Callable[[Any], object]) -> Any:
+        """Evaluates code and returns the result once `predicate(result)` is
+        truthy, otherwise raises `AssertionError` with the result's repr"""
+    def eval_await(self, code: str, timeout: float | None = ..., timeout_ms: int | None = ...) -> Any:
+        """Evaluates code and blocks until the promise it evaluates to settles,
+        returning the resolved value or raising the rejection. With `timeout`
+        in seconds or `timeout_ms` in whole milliseconds a `TimeoutError` is
+        raised once it passes, in place of the context's own timeout"""
+    def eval_stream(self, code: str, on_chunk: Callable[[Any], object]) -> Any:
+        """Evaluates code that can call `__emit(chunk)` to hand `on_chunk` each
+        chunk as it's produced instead of returning one big value. `__emit` is
+        only defined while the code and the event loop after it run"""
+    def eval_lazy(self, code: str) -> "JsObjectProxy":
+        """Evaluates code to an object that stays in javascript, its properties
+        are only converted as they're read. Raises `TypeError` when the code
+        doesn't evaluate to an object"""
+    def eval_result(self, code: str) -> "JsResult":
+        """Like `eval` but errors are returned in the `JsResult` instead of
+        raised, only a busy or closed runtime still raises"""
+    def eval_bytes(self, code: str) -> bytes:
+        """Evaluates code resulting in an `ArrayBuffer` or typed array and
+        returns its bytes
+
+        :raises TypeError: if the result isn't binary data
+        """
+    def eval_as(self, code: str, py_type: type[T]) -> T:
+        """Evaluates code and builds `py_type` from the result, objects are
+        passed as keyword arguments (`py_type(**result)`), raises `TypeError`
+        when the type can't be built from it"""
+    def eval_capturing(self, code: str) -> tuple[Any, list[str]]:
+        """Evaluates code while capturing console output for just this call,
+        returning the result together with the captured lines"""
+    def call(
+        self, name:str, *args, retries: int | None = ..., return_timing: bool | None = ...
+    ) -> Any:
+        """`return_timing` returns `(result, timing)` the same as `eval`"""
+    def call_value(self, expr: str, *args: Any) -> Any:
+        """Evaluates `expr` to a function and calls it with `args`, for
+        functions that aren't globals such as `"obj.handlers.onClick"`. It's
+        called without a `this`. Raises `TypeError` when `expr` isn't callable"""
+    def warmup(self, name: str, iterations: int, *sample_args: Any) -> None:
+        """Calls `name` `iterations` times with `sample_args` and discards the
+        results, so v8 has optimized the function before it's measured"""
+    def call_full(
+        self, name: str, args: Iterable[Any], kwargs: dict[str, Any] | None = ...
+    ) -> Any:
+        """Calls `name` with `args` followed by `kwargs` as one final options
+        object, like `fn(...args, kwargs)` in javascript"""
+    def call_blob(self, name: str, blob: bytes, format: str = "msgpack") -> Any:
+        """Calls `name` with arguments decoded straight from a msgpack, cbor or
+        json blob, an array is spread into the arguments and anything else is
+        passed as the only argument. Binary strings arrive as `Uint8Array`s and
+        `NaN` or the infinities as themselves. Msgpack extensions, cbor tags
+        that change what a value means and integers outside of 64 bits raise
+        `ValueError`"""
+    def call_from_file(self, name: str, json_file: str) -> Any:
+        """Calls `name` with the parsed contents of `json_file` as its only
+        argument, arrays included unlike `call_blob`. The file is parsed as it's read without loading it into
+        python. Invalid json raises `ValueError`"""
     def call_module(self, module: "JsHandle", name: str, *args) -> Any:...
 
     def get_value(self, name:str) -> Any:...
-    def load_module(self, module: "JsModule" ) -> "JsHandle":...
+    def try_get_value(self, name: str) -> Any | None:
+        """Like `get_value` but returns `None` when the name doesn't exist"""
+    def put_buffer(self, name: str, data: bytes) -> None:
+        """Binds `globalThis[name]` to an `ArrayBuffer` with a copy of `data`,
+        much faster than passing the bytes as a value. `get_value` hands it
+        back as `bytes`"""
+    def create_shared_buffer(self, name: str, size: int) -> None:
+        """Binds `globalThis[name]` to a zeroed `SharedArrayBuffer` of `size`
+        bytes that javascript mutates in place across calls, it survives
+        `recycle`. Only this context's thread touches it, so there's no need
+        for `Atomics` and nothing else sees writes halfway through a call"""
+    def read_shared_buffer(self, name: str) -> bytes:
+        """A copy of what the buffer made by `create_shared_buffer` holds now,
+        raising `KeyError` for names it didn't make"""
+    def define_constant(self, name: str, value: Any) -> None:
+        """Defines a global that can't be reassigned, objects are frozen so
+        their contents can't change either"""
+    def create_realm(self) -> "Realm":
+        """Creates a realm with its own globals inside of this runtime's isolate"""
+    def eval_sandbox(self, code: str) -> Any:
+        """Evaluates code in a fresh realm that's discarded afterwards, so
+        whatever it defines never reaches the context or the next call. Like
+        a `Realm` only the javascript builtins are available"""
+    def check(self, code: str, module: bool | None = ...) -> str | None:
+        """Compiles code without running it returning `None` when it's valid
+        or the compile error, with `module` set imports are resolved too
+        but never loaded"""
+    def resolve_graph(self, entry: "JsModule") -> dict[str, dict[str, Any]]:
+        """Maps the resolved specifier of `entry` and everything it imports to
+        `{"sha256": str | None, "imports": list[str]}` without evaluating
+        anything, modules that aren't local files have no hash"""
+    def instantiate_wasm(self, name: str, bytes: bytes) -> None:
+        """Compiles WebAssembly and exposes its exports as `globalThis[name]`"""
+    def load_module(self, module: "JsModule", force: bool | None = ...) -> "JsHandle":
+        """Loads a module, a `.json` file becomes a module exporting the parsed
+        json as its default. Modules can import json files from disk with
+        `import data from './data.json' with { type: 'json' }`, the older
+        `assert { type: 'json' }` works too.
+
+        Loading a module whose resolved path was already loaded successfully
+        from the same contents returns the same handle without evaluating it
+        again, `force` loads and evaluates it anyway"""
+    def reload_module(self, handle: "JsHandle", new_contents: str) -> None:
+        """Evaluates a module loaded by this context again from `new_contents`,
+        keeping `handle` so code holding it sees the new exports.
+
+        Imports are live bindings to the module they were linked to, so any
+        module loaded with `load_module` that imports the reloaded one, even
+        through other modules, is evaluated again as well and its top level
+        side effects happen again. Modules only reached through imports and
+        values already copied out of the old exports keep pointing at the
+        old module"""
+    def register_function(self, name: str, func: Callable[..., Any]) -> None:
+        """Exposes a python callable to javascript as a global function. If
+        javascript doesn't catch an exception it raises, `eval` and `call`
+        raise that same exception rather than a `RuntimeError`"""
+    def register_async_function(
+        self,
+        name: str,
+        func: Callable[..., Coroutine[Any, Any, Any]],
+        event_loop: asyncio.AbstractEventLoop | None = ...,
+    ) -> None:
+        """Exposes an async python callable to javascript as a global function
+        returning a promise that settles with the coroutine's result.
+        Coroutines run on `event_loop`, which must be running in another
+        thread, or on a loop pyrv8 runs in a background thread by default"""
+    def register_functions(self, funcs: dict[str, Callable[..., Any]]) -> None:
+        """Exposes several python callables as global functions in one call"""
+    def set_module_load_hook(self, hook: Callable[[str], Any] | None) -> None:
+        """Calls `hook` with the resolved specifier of every module as it's
+        loaded, before it's evaluated. The hook only observes, anything it
+        raises goes to `sys.unraisablehook`. `None` removes the hook"""
+    def set_arg_encoder(self, encoder: Callable[[Any], Any] | None) -> None:
+        """Calls `encoder` with any argument that can't be serialized, even
+        inside of lists and dicts, and passes what it returns instead.
+        `None` removes the encoder"""
+    def supported_features(self) -> list[str]:
+        """Names of the rustyscript extensions available in this runtime such
+        as `console`, `url` and `crypto`"""
+    def dump_globals(self) -> dict[str, Any]:
+        """The enumerable properties of `globalThis` that json can hold, for
+        inspecting global state. Functions and values such as bigints or
+        cycles are skipped, as are `let` and `const` declarations which aren't
+        properties of `globalThis`"""
+    def registered_functions(self) -> list[str]:
+        """Sorted names of the python functions exposed to javascript"""
+    def unregister_function(self, name: str) -> None:
+        """Removes a registered function, calling it afterwards throws a
+        `ReferenceError` inside of javascript
+
+        :raises KeyError: if no function was registered under `name`
+        """
+    def recycle(self) -> None:
+        """Replaces the runtime with a fresh one made from the same options,
+        registered functions are restored but loaded modules and promises are not"""
+    def set_mock_time(self, epoch_ms: float | None) -> None:
+        """Freezes `Date.now()` and `new Date()` at `epoch_ms` milliseconds
+        since the epoch, `None` goes back to the real clock. Timers still run
+        on real time and the mock survives `recycle`"""
+    def advance_mock_time(self, ms: float) -> None:
+        """Moves the mocked time forward, raising `RuntimeError` when none is set"""
+    def abort_all(self) -> None:
+        """Gives up on in-flight async work while keeping globals. Promises
+        `call_async` handed out before it raise `CancelledError` from then on
+        and every timer set so far is cancelled. Nothing is rejected inside of
+        javascript, code waiting on a cancelled timer never resumes while async
+        ops that already started still finish and run their callbacks"""
+    def console_stream(self) -> "ConsoleStream":
+        """Async iterator of console lines as they are written, something
+        else has to keep advancing the event loop for lines to show up"""
+    def mount(self, js_path: str, host_path: str, readonly: bool = True) -> None:
+        """Exposes `host_path` to scripts under `js_path` through a node style
+        `fs` global, paths outside of every mount raise inside of javascript"""
+    def set_unhandled_rejection_handler(self, func: Callable[[Any], Any]) -> None:
+        """Calls `func` with the reason of promises that reject without a handler,
+        errors are passed as their string form"""
 
     def call_async(self, name:str, *args) -> "JsPromise":...
     def call_module_async(self, module: "JsHandle", name: str, *args) -> "JsPromise":...
+    def run_until_complete(
+        self,
+        promise: "JsPromise",
+        on_tick: Callable[[float], bool | None] | None = ...,
+        total_timeout: float | None = ...,
+    ) -> Any:
+        """Runs the event loop until `promise` settles and returns its result,
+        `on_tick` gets the seconds elapsed after each tick and can return
+        `False` to give up with `asyncio.CancelledError`. `total_timeout` in
+        seconds is a deadline for the whole run no matter how many ticks it
+        takes, once it passes the javascript is terminated and `TimeoutError`
+        raised"""
 
 
 
@@ -47,11 +440,28 @@ class JsPromise:
         :raises InavlidStateError: if state is invalid
         :raises RuntimeError: if JsPromise thrown an error
         """  
+    def result_json(self) -> str:
+        """Obtains the result as a json string without building a python object,
+        `undefined` comes back as `null`
+
+        :raises InvalidStateError: if the promise isn't done yet
+        """
+    def context_alive(self) -> bool:
+        """false once the context (or recycled runtime) that made this
+        promise is gone, meaning it can never resolve"""
+
+    def poll_exception(self, ctx: Context) -> RuntimeError | None:
+        """Steps the promise if it is pending and returns the exception it
+        rejected with, `None` while still pending or after resolving"""
     def exception(self) -> RuntimeError | None:
         """Obtains an exception if one was given
         Otherwise this function results with nothing.
         :raises InvalidStateError: if promise did not 
         complete yet"""
+    def debug_state(self) -> dict[str, Any]:
+        """`done`, `has_result` and `has_exception` for logging, along with a
+        `preview` of the value cut to 80 characters once resolved. Not
+        something to pickle"""
 
 class JsModule:
     def __init__(self, filename:str, contents:str) -> None:
@@ -64,9 +474,12 @@ class JsModule:
         """
     
     @staticmethod
-    def load_dir(directory: str) -> list[JsModule]:
+    def load_dir(directory: str, extensions: list[str] | None = ...) -> list[JsModule]:
         """loads a directory of javascript and typescript modules.
         raises an exception if a file was not found or an unexpected failure occurs
+
+        :param extensions: file extensions to load such as `["mjs", "jsx"]`,
+            defaults to `["js", "ts"]`
         """
     
     @property
@@ -75,6 +488,15 @@ class JsModule:
     @property
     def contents(self) -> str:...
 
+    def contents_hash(self) -> str:
+        """Hex sha256 of the contents, compare it with a fresh
+        `JsModule.load` to tell whether the file changed on disk"""
+    @staticmethod
+    def transpile(source: str, filename: str) -> str:
+        """Transpiles typescript or JSX to javascript without making a
+        `Context`, the extension of `filename` picks the syntax. Javascript
+        is returned as is and syntax errors raise `ValueError`"""
+
 
 class JsHandle:
     """Not meant to be initalized in Python but rather in rust 
@@ -86,9 +508,139 @@ class JsHandle:
     @property
     def contents(self) -> str:...
 
+    def resolved_path(self) -> str:
+        """The absolute path of the file the module was loaded from, or its
+        url when it didn't come from one, as opposed to the filename it was
+        given"""
+
+    def describe_export(self, ctx: Context, name: str) -> str:
+        """The javascript `typeof` of the export `name` such as `"function"` or
+        `"object"`, read without converting the value. Raises `KeyError` when
+        the module has no such export"""
+
+    def unload(self, ctx: Context) -> None:
+        """Lets go of the module in `ctx` so its exports can be garbage
+        collected, loading the file again evaluates it anew. V8 can't forget
+        a module so its compiled record stays until the context is recycled.
+        Using the handle afterwards, calling its exports included, raises
+        `InvalidStateError`"""
+
+
+class JsSymbol:
+    """A Javascript `Symbol` carried over into python with its description"""
+    @property
+    def description(self) -> str | None:...
+
+    @property
+    def is_well_known(self) -> bool:
+        """true for builtin symbols such as `Symbol.iterator`"""
+
+
+class JsRegExp:
+    """A Javascript `RegExp` carried over as its source and flags"""
+    @property
+    def source(self) -> str:...
+    @property
+    def flags(self) -> str:...
+    def compile(self) -> re.Pattern[str]:
+        """Compiles the pattern with `re`, named groups are translated and the
+        `i`, `m` and `s` flags carry over while `g`, `y`, `d` and `u` are dropped"""
+
+
+class JsResult:
+    """The outcome of `Context.eval_result`, either a value or the exception
+    that would have been raised"""
+    def is_ok(self) -> bool:...
+    def is_err(self) -> bool:...
+    def unwrap(self) -> Any:
+        """The value, raises the exception if the code failed"""
+    def err(self) -> BaseException | None:
+        """The exception, `None` if the code succeeded"""
+    def __bool__(self) -> bool:...
+
+
+class JsObjectProxy:
+    """A javascript object from `Context.eval_lazy`, read a property at a time"""
+    def __getitem__(self, key: str | int) -> Any:
+        """Converts one property, missing ones raise `KeyError`"""
+    def __contains__(self, key: str | int) -> bool:...
+    def __len__(self) -> int:...
+    def keys(self) -> list[str]:
+        """The object's own enumerable keys"""
+    def to_python(self) -> Any:
+        """Converts the whole object the way `eval` would have"""
+
+
+class ConsoleStream:
+    """Async iterator over console output from `Context.console_stream`"""
+    def __aiter__(self) -> "ConsoleStream":...
+    def __anext__(self) -> Coroutine[Any, Any, str]:...
+    async def next_line(self) -> str:
+        """Waits for the next line, raises `StopAsyncIteration` once closed"""
+    def close(self) -> None:
+        """Ends iteration after the lines already received are read"""
+
+
+class JsBuffer:
+    """Keeps javascript memory alive for the memoryviews made over it"""
+    def __len__(self) -> int:...
+
+
+class CancelToken:
+    """Stops code it was passed to once tripped, safe to use from any thread"""
+    def __init__(self) -> None:...
+    @property
+    def cancelled(self) -> bool:...
+    def cancel(self) -> None:...
+
+
+class Realm:
+    """A separate set of globals sharing the isolate of the `Context` that
+    made it, only the javascript builtins are available inside of it"""
+    def eval(self, code: str) -> Any:...
+    def call(self, name: str, *args) -> Any:...
+
+
+
+def v8_version() -> str:
+    """The version of v8 the runtime is built on"""
+def deno_core_version() -> str:
+    """The version of deno_core the runtime is built on"""
+def rustyscript_version() -> str:
+    """The version of rustyscript the runtime is built on"""
+def gather_results(
+    promises: Iterable[JsPromise], return_exceptions: bool | None = ...
+) -> list[Any]:
+    """Results of promises that have all settled already, in order. The first
+    exception is raised unless `return_exceptions` puts the exceptions in the
+    list in place of results. Pending promises raise `InvalidStateError`"""
+def set_shared_runtime(threads: int) -> None:
+    """Starts the runtime used by contexts made with `shared_pool=True` with
+    `threads` worker threads, must be called before any such context exists
+    otherwise tokio's default thread count is used"""
+
+def enable_lock_tracing(callback: Callable[[str, str], object] | None) -> None:
+    """Calls `callback(event, site)` whenever one of pyrv8's internal locks is
+    `"waiting"`, `"acquired"` or `"released"`, `site` is the `file:line:column`
+    in pyrv8 taking it. Meant for finding contention between threads, `None`
+    turns it off. The callback must not use pyrv8 itself"""
 
 
 # mimics asyncio.InvalidStateError...
 class InvalidStateError(Exception):
     """The operation is not allowed in this state."""
 
+class TimeoutError(builtins.TimeoutError):
+    """The runtime's timeout ran out before the javascript finished."""
+
+class ModuleResolutionError(RuntimeError):
+    """An import could not be resolved or the file it points at doesn't
+    exist, `specifier` is what was imported and `referrer` the module that
+    imported it when that's known."""
+    specifier: str
+    referrer: str | None
+
+class JsConsoleError(Exception):
+    """Javascript wrote to `console.error` in a context made with
+    `console_error_raises`, `args` are what it was called with."""
+
@@ -0,0 +1,246 @@
+//! Async python callables exposed to javascript as functions returning promises.
+//! Their coroutines run on an asyncio loop in another thread and wake the runtime
+//! once they're done. While one is pending the runtime lets go of the GIL whenever
+//! it idles so that loop gets to run and the javascript event loop keeps turning.
+//! A runtime on the shared pool can't tell when it idles, it waits each call out
+//! with the GIL released instead.
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    task::{Context as TaskContext, Poll, Waker},
+};
+
+use pyo3::{
+    exceptions::{PyRuntimeError, PyValueError},
+    prelude::*,
+    sync::GILOnceCell,
+    types::{PyDict, PyTuple},
+};
+use rustyscript::tokio::runtime::{Handle, RuntimeFlavor};
+use rustyscript::{Error as RSError, RsAsyncFunction, Runtime, Undefined};
+use serde_pyobject::{from_pyobject, to_pyobject};
+
+use crate::scripts;
+
+/// How many async python calls runtimes are waiting on
+static PENDING: AtomicUsize = AtomicUsize::new(0);
+
+/// Whether a runtime is waiting on an async python call, they only let go of
+/// the GIL while they idle when one is
+pub fn pending() -> bool {
+    PENDING.load(Ordering::Acquire) > 0
+}
+
+/// Runs the coroutines of functions registered without a loop of their own
+static BACKGROUND_LOOP: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
+
+fn background_loop(py: Python<'_>) -> PyResult<Bound<'_, PyAny>> {
+    let event_loop = BACKGROUND_LOOP.get_or_try_init(py, || {
+        let event_loop = py.import("asyncio")?.call_method0("new_event_loop")?;
+        let kwargs = PyDict::new(py);
+        kwargs.set_item("target", event_loop.getattr("run_forever")?)?;
+        kwargs.set_item("name", "pyrv8-async")?;
+        kwargs.set_item("daemon", true)?;
+        let thread = py.import("threading")?.getattr("Thread")?;
+        thread.call((), Some(&kwargs))?.call_method0("start")?;
+        Ok::<_, PyErr>(event_loop.unbind())
+    })?;
+    Ok(event_loop.bind(py).clone())
+}
+
+/// An async python callable and the loop its coroutines are run on
+pub struct AsyncFunction {
+    pub func: Py<PyAny>,
+    /// `None` runs them on a loop pyrv8 keeps in a thread of its own
+    pub event_loop: Option<Py<PyAny>>,
+}
+
+impl AsyncFunction {
+    pub fn clone_ref(&self, py: Python<'_>) -> Self {
+        Self {
+            func: self.func.clone_ref(py),
+            event_loop: self.event_loop.as_ref().map(|l| l.clone_ref(py)),
+        }
+    }
+}
+
+/// Set from the loop's thread once the coroutine is done
+#[derive(Default)]
+struct Signal {
+    state: Mutex<State>,
+    ready: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    done: bool,
+    /// Woken once it's done
+    waker: Option<Waker>,
+}
+
+impl Signal {
+    /// Whether it's done, `waker` gets woken once it is otherwise
+    fn poll(&self, waker: &Waker) -> bool {
+        let Ok(mut state) = self.state.lock() else {
+            return true;
+        };
+        if !state.done {
+            state.waker = Some(waker.clone());
+        }
+        state.done
+    }
+
+    fn wait(&self) {
+        if let Ok(state) = self.state.lock() {
+            drop(self.ready.wait_while(state, |state| !state.done));
+        }
+    }
+
+    fn finish(&self) {
+        let waker = match self.state.lock() {
+            Ok(mut state) => {
+                state.done = true;
+                state.waker.take()
+            }
+            Err(_) => None,
+        };
+        self.ready.notify_all();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/// Handed to `add_done_callback` of the scheduled coroutine
+#[pyclass]
+struct DoneCallback(Arc<Signal>);
+
+#[pymethods]
+impl DoneCallback {
+    fn __call__(&self, _future: &Bound<'_, PyAny>) {
+        self.0.finish();
+    }
+}
+
+/// A coroutine scheduled on its loop, resolving to what it returns
+struct PendingCall {
+    future: Py<PyAny>,
+    signal: Arc<Signal>,
+    /// Counted in [`PENDING`] until it's dropped
+    counted: bool,
+}
+
+impl Drop for PendingCall {
+    fn drop(&mut self) {
+        if self.counted {
+            PENDING.fetch_sub(1, Ordering::AcqRel);
+        }
+    }
+}
+
+impl Future for PendingCall {
+    type Output = Result<serde_json::Value, RSError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Self::Output> {
+        if !self.signal.poll(cx.waker()) {
+            let flavor = Handle::try_current().map(|handle| handle.runtime_flavor());
+            if matches!(flavor, Ok(RuntimeFlavor::CurrentThread)) {
+                if !self.counted {
+                    self.counted = true;
+                    PENDING.fetch_add(1, Ordering::AcqRel);
+                }
+                return Poll::Pending;
+            }
+            // The shared pool parks the thread with the GIL still held
+            let signal = self.signal.clone();
+            Python::with_gil(|py| py.allow_threads(|| signal.wait()));
+        }
+        Poll::Ready(Python::with_gil(|py| {
+            let result = self
+                .future
+                .bind(py)
+                .call_method0("result")
+                .map_err(crate::raised)?;
+            from_pyobject(result).map_err(|e| RSError::Runtime(e.to_string()))
+        }))
+    }
+}
+
+/// Calls `function` and schedules the coroutine it returns, anything that isn't
+/// a coroutine resolves the promise right away
+fn start(
+    py: Python<'_>,
+    function: &AsyncFunction,
+    args: &[serde_json::Value],
+) -> PyResult<Result<PendingCall, Py<PyAny>>> {
+    let mut py_args = Vec::with_capacity(args.len());
+    for a in args {
+        py_args.push(to_pyobject(py, a)?);
+    }
+    let result = function.func.bind(py).call1(PyTuple::new(py, py_args)?)?;
+    let asyncio = py.import("asyncio")?;
+    if !asyncio
+        .call_method1("iscoroutine", (&result,))?
+        .is_truthy()?
+    {
+        return Ok(Err(result.unbind()));
+    }
+    let event_loop = match &function.event_loop {
+        Some(event_loop) => event_loop.bind(py).clone(),
+        None => background_loop(py)?,
+    };
+    // The loop would be stuck waiting on the very thread that's waiting on it
+    if asyncio.call_method0("_get_running_loop")?.is(&event_loop) {
+        result.call_method0("close")?;
+        return Err(PyRuntimeError::new_err(
+            "can't wait on the event loop running on this thread, pass a loop running in another thread",
+        ));
+    }
+    let future = asyncio.call_method1("run_coroutine_threadsafe", (result, event_loop))?;
+    let signal = Arc::new(Signal::default());
+    future.call_method1("add_done_callback", (DoneCallback(signal.clone()),))?;
+    Ok(Ok(PendingCall {
+        future: future.unbind(),
+        signal,
+        counted: false,
+    }))
+}
+
+/// Wraps an async python callable for rustyscript's async functions
+fn python_async_function(function: AsyncFunction) -> impl RsAsyncFunction {
+    move |args: Vec<serde_json::Value>| {
+        let started = Python::with_gil(|py| start(py, &function, &args));
+        let future: Pin<Box<dyn Future<Output = Result<serde_json::Value, RSError>>>> =
+            match started {
+                Ok(Ok(pending)) => Box::pin(pending),
+                Ok(Err(value)) => {
+                    let value = Python::with_gil(|py| from_pyobject(value.into_bound(py)))
+                        .map_err(|e| RSError::Runtime(e.to_string()));
+                    Box::pin(std::future::ready(value))
+                }
+                Err(e) => Box::pin(std::future::ready(Err(crate::raised(e)))),
+            };
+        future
+    }
+}
+
+/// Registers async python callables with the runtime and binds them to globals
+pub fn install(runtime: &mut Runtime, functions: &[(String, AsyncFunction)]) -> PyResult<()> {
+    let mut names = Vec::with_capacity(functions.len());
+    for (name, function) in functions {
+        let function = Python::with_gil(|py| function.clone_ref(py));
+        if let Err(e) = runtime.register_async_function(name, python_async_function(function)) {
+            return Err(PyRuntimeError::new_err(e.to_string()));
+        }
+        names.push(name);
+    }
+    let names = serde_json::to_string(&names).map_err(|e| PyValueError::new_err(e.to_string()))?;
+    match runtime.eval::<Undefined>(format!("{}({names})", scripts::BIND_ASYNC_FUNCTIONS)) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(PyRuntimeError::new_err(e.to_string())),
+    }
+}
@@ -0,0 +1,191 @@
+//! Decoding preserialized argument blobs straight into call arguments so large
+//! payloads never have to be walked as python objects.
+//! Binary strings come through as `Uint8Array`s and `NaN` or the infinities as
+//! themselves, msgpack extensions and cbor tags that change what the value means
+//! are refused.
+use std::fmt;
+
+use pyo3::{exceptions::PyValueError, prelude::*};
+use serde::de::{self, Deserialize, Deserializer, EnumAccess, MapAccess, SeqAccess, VariantAccess};
+use serde_json::{Number, Value};
+
+use crate::convert::Argument;
+
+/// Cbor tags whose value reads the same without them: date strings, epoch
+/// times, URIs and the self-describe marker
+const CBOR_TAGS: &[u64] = &[0, 1, 32, 55799];
+
+#[derive(Clone, Copy)]
+pub enum BlobFormat {
+    Json,
+    MsgPack,
+    Cbor,
+}
+
+impl BlobFormat {
+    pub fn parse(name: &str) -> PyResult<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "msgpack" | "messagepack" => Ok(Self::MsgPack),
+            "cbor" => Ok(Self::Cbor),
+            _ => Err(PyValueError::new_err(format!(
+                "unknown blob format {name}, expected msgpack, cbor or json"
+            ))),
+        }
+    }
+}
+
+/// Decodes `blob` into the arguments for a call, an array is spread into the
+/// arguments and any other value is passed as the only one.
+pub fn decode_args(blob: &[u8], format: BlobFormat) -> PyResult<Vec<Argument>> {
+    let decoded = match format {
+        BlobFormat::Json => serde_json::from_slice(blob).map_err(|e| e.to_string()),
+        BlobFormat::MsgPack => rmp_serde::from_slice(blob).map_err(|e| e.to_string()),
+        BlobFormat::Cbor => ciborium::de::from_reader(blob).map_err(|e| e.to_string()),
+    };
+    match decoded {
+        Ok(Decoded(Argument::Array(args))) => Ok(args),
+        Ok(Decoded(value)) => Ok(vec![value]),
+        Err(e) => Err(PyValueError::new_err(e)),
+    }
+}
+
+/// An argument read from a blob
+struct Decoded(Argument);
+
+impl<'de> Deserialize<'de> for Decoded {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ArgumentVisitor).map(Decoded)
+    }
+}
+
+/// A map key, msgpack and cbor allow more than strings
+struct Key(String);
+
+impl<'de> Deserialize<'de> for Key {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match deserializer.deserialize_any(ArgumentVisitor)? {
+            Argument::Json(Value::String(key)) => Ok(Key(key)),
+            Argument::Json(key @ (Value::Number(_) | Value::Bool(_))) => Ok(Key(key.to_string())),
+            _ => Err(de::Error::custom("map keys must be strings or numbers")),
+        }
+    }
+}
+
+struct ArgumentVisitor;
+
+impl<'de> de::Visitor<'de> for ArgumentVisitor {
+    type Value = Argument;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a value javascript can take")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Argument, E> {
+        Ok(Argument::Json(Value::Bool(v)))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Argument, E> {
+        Ok(Argument::Json(Value::from(v)))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Argument, E> {
+        Ok(Argument::Json(Value::from(v)))
+    }
+
+    fn visit_i128<E: de::Error>(self, v: i128) -> Result<Argument, E> {
+        match i64::try_from(v) {
+            Ok(v) => self.visit_i64(v),
+            Err(_) => Err(E::custom(format!("integer {v} is out of range"))),
+        }
+    }
+
+    fn visit_u128<E: de::Error>(self, v: u128) -> Result<Argument, E> {
+        match u64::try_from(v) {
+            Ok(v) => self.visit_u64(v),
+            Err(_) => Err(E::custom(format!("integer {v} is out of range"))),
+        }
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Argument, E> {
+        match Number::from_f64(v) {
+            Some(n) => Ok(Argument::Json(Value::Number(n))),
+            None => Ok(Argument::NonFinite(v)),
+        }
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Argument, E> {
+        Ok(Argument::Json(Value::String(v.to_string())))
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<Argument, E> {
+        Ok(Argument::Json(Value::String(v)))
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Argument, E> {
+        Ok(Argument::Bytes(v.to_vec()))
+    }
+
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Argument, E> {
+        Ok(Argument::Bytes(v))
+    }
+
+    fn visit_unit<E>(self) -> Result<Argument, E> {
+        Ok(Argument::Json(Value::Null))
+    }
+
+    fn visit_none<E>(self) -> Result<Argument, E> {
+        Ok(Argument::Json(Value::Null))
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Argument, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Argument, A::Error> {
+        // Lengths come from the blob so don't trust them for the allocation
+        let mut items = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(Decoded(item)) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(Argument::Array(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Argument, A::Error> {
+        let mut entries = Vec::with_capacity(map.size_hint().unwrap_or(0).min(4096));
+        while let Some((Key(key), Decoded(value))) = map.next_entry()? {
+            entries.push((key, value));
+        }
+        Ok(Argument::Object(entries))
+    }
+
+    /// Cbor tags come through as enums of the tag and the value under it
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Argument, A::Error> {
+        let (_, variant) = data.variant::<de::IgnoredAny>()?;
+        variant.tuple_variant(2, TaggedVisitor)
+    }
+}
+
+struct TaggedVisitor;
+
+impl<'de> de::Visitor<'de> for TaggedVisitor {
+    type Value = Argument;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a tagged value")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Argument, A::Error> {
+        let tag: u64 = match seq.next_element()? {
+            Some(tag) => tag,
+            None => return Err(de::Error::custom("tag is missing")),
+        };
+        if !CBOR_TAGS.contains(&tag) {
+            return Err(de::Error::custom(format!("unsupported cbor tag {tag}")));
+        }
+        match seq.next_element()? {
+            Some(Decoded(value)) => Ok(value),
+            None => Err(de::Error::custom(format!("cbor tag {tag} has no value"))),
+        }
+    }
+}
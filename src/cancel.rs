@@ -0,0 +1,64 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Mutex,
+};
+
+use pyo3::{
+    exceptions::{asyncio::CancelledError, PyRuntimeError},
+    prelude::*,
+};
+use rustyscript::deno_core::v8;
+
+/// A flag that can be tripped from any thread to stop the code it was handed to.
+/// Tripping it terminates execution in every isolate currently running with it.
+#[pyclass(frozen)]
+#[derive(Default)]
+pub struct CancelToken {
+    cancelled: AtomicBool,
+    isolates: Mutex<Vec<v8::IsolateHandle>>,
+}
+
+#[pymethods]
+impl CancelToken {
+    #[new]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[getter]
+    pub fn cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    pub fn cancel(&self) -> PyResult<()> {
+        self.cancelled.store(true, Ordering::SeqCst);
+        for isolate in self.lock()?.iter() {
+            isolate.terminate_execution();
+        }
+        Ok(())
+    }
+}
+
+impl CancelToken {
+    fn lock(&self) -> PyResult<std::sync::MutexGuard<'_, Vec<v8::IsolateHandle>>> {
+        self.isolates
+            .lock()
+            .map_err(|e| PyRuntimeError::new_err(e.to_string()))
+    }
+
+    /// Starts watching an isolate, fails right away if the token was already tripped
+    pub fn attach(&self, isolate: v8::IsolateHandle) -> PyResult<()> {
+        let mut isolates = self.lock()?;
+        if self.cancelled() {
+            return Err(CancelledError::new_err("cancel token was already tripped"));
+        }
+        isolates.push(isolate);
+        Ok(())
+    }
+
+    /// Stops watching the isolates, from here on tripping the token won't touch them
+    pub fn detach(&self) -> PyResult<()> {
+        self.lock()?.clear();
+        Ok(())
+    }
+}
@@ -0,0 +1,206 @@
+use std::{
+    collections::VecDeque,
+    future::poll_fn,
+    sync::{Arc, Mutex, Weak},
+    task::{Poll, Waker},
+};
+
+use pyo3::{
+    exceptions::{PyRuntimeError, PyStopAsyncIteration, PyValueError},
+    prelude::*,
+    types::PyTuple,
+};
+use rustyscript::{Error as RSError, Runtime, Undefined};
+use serde_pyobject::to_pyobject;
+
+use crate::{scripts, JsConsoleError};
+
+#[derive(Default)]
+struct StreamState {
+    lines: VecDeque<String>,
+    waker: Option<Waker>,
+    closed: bool,
+}
+
+impl StreamState {
+    fn close(&mut self) {
+        self.closed = true;
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Hands console lines out to every stream that is still around,
+/// shared with the function javascript calls into.
+#[derive(Clone, Default)]
+pub struct ConsoleBroadcast {
+    streams: Arc<Mutex<Vec<Weak<Mutex<StreamState>>>>>,
+}
+
+impl ConsoleBroadcast {
+    fn send(&self, line: &str) {
+        let Ok(mut streams) = self.streams.lock() else {
+            return;
+        };
+        streams.retain(|stream| {
+            let Some(stream) = stream.upgrade() else {
+                return false;
+            };
+            if let Ok(mut state) = stream.lock() {
+                state.lines.push_back(line.to_string());
+                if let Some(waker) = state.waker.take() {
+                    waker.wake();
+                }
+            }
+            true
+        });
+    }
+
+    /// Ends every stream once the lines it already received have been read
+    pub fn close(&self) {
+        let Ok(mut streams) = self.streams.lock() else {
+            return;
+        };
+        for stream in streams.drain(..).filter_map(|stream| stream.upgrade()) {
+            if let Ok(mut state) = stream.lock() {
+                state.close();
+            }
+        }
+    }
+
+    pub fn subscribe(&self) -> PyResult<ConsoleStream> {
+        let state = Arc::new(Mutex::new(StreamState::default()));
+        match self.streams.lock() {
+            Ok(mut streams) => streams.push(Arc::downgrade(&state)),
+            Err(e) => return Err(PyRuntimeError::new_err(e.to_string())),
+        }
+        Ok(ConsoleStream { state })
+    }
+
+    /// Wraps `console` so everything written to it is also sent to the streams
+    pub fn install(&self, runtime: &mut Runtime) -> PyResult<()> {
+        let broadcast = self.clone();
+        let result = runtime.register_function(
+            scripts::CONSOLE_STREAM_HANDLER,
+            move |args: &[serde_json::Value]| {
+                if let Some(line) = args.first().and_then(|l| l.as_str()) {
+                    broadcast.send(line);
+                }
+                Ok::<_, RSError>(serde_json::Value::Null)
+            },
+        );
+        if let Err(e) = result {
+            return Err(PyRuntimeError::new_err(e.to_string()));
+        }
+        match runtime.eval::<Undefined>(scripts::CONSOLE_STREAM) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(PyRuntimeError::new_err(e.to_string())),
+        }
+    }
+}
+
+/// The arguments of every `console.error` call since the last check, for
+/// contexts made with `console_error_raises=True`
+#[derive(Clone, Default)]
+pub struct ConsoleErrors {
+    calls: Arc<Mutex<VecDeque<Vec<serde_json::Value>>>>,
+}
+
+impl ConsoleErrors {
+    /// Wraps `console.error` so every call is recorded
+    pub fn install(&self, runtime: &mut Runtime) -> PyResult<()> {
+        let calls = self.calls.clone();
+        let result = runtime.register_function(
+            scripts::CONSOLE_ERROR_HANDLER,
+            move |args: &[serde_json::Value]| {
+                let logged = match args.first() {
+                    Some(serde_json::Value::Array(logged)) => logged.clone(),
+                    _ => Vec::new(),
+                };
+                if let Ok(mut calls) = calls.lock() {
+                    calls.push_back(logged);
+                }
+                Ok::<_, RSError>(serde_json::Value::Null)
+            },
+        );
+        if let Err(e) = result {
+            return Err(PyRuntimeError::new_err(e.to_string()));
+        }
+        match runtime.eval::<Undefined>(scripts::CONSOLE_ERROR_HOOK) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(PyRuntimeError::new_err(e.to_string())),
+        }
+    }
+
+    /// Raises `JsConsoleError` with the arguments of the first call made since
+    /// the last check, the rest are dropped along with it
+    pub fn check(&self, py: Python<'_>) -> PyResult<()> {
+        let first = match self.calls.lock() {
+            Ok(mut calls) => {
+                let first = calls.pop_front();
+                calls.clear();
+                first
+            }
+            Err(e) => return Err(PyRuntimeError::new_err(e.to_string())),
+        };
+        let Some(logged) = first else {
+            return Ok(());
+        };
+        let mut args = Vec::with_capacity(logged.len());
+        for a in &logged {
+            args.push(to_pyobject(py, a).map_err(|e| PyValueError::new_err(e.to_string()))?);
+        }
+        Err(JsConsoleError::new_err(PyTuple::new(py, args)?.unbind()))
+    }
+}
+
+/// Async iterator over console lines, new lines show up as the event loop
+/// runs the code writing them so something else has to keep advancing it.
+#[pyclass(frozen)]
+pub struct ConsoleStream {
+    state: Arc<Mutex<StreamState>>,
+}
+
+#[pymethods]
+impl ConsoleStream {
+    pub fn __aiter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    // pyo3 can't make magic methods async so this hands out the coroutine of `next_line`
+    pub fn __anext__(slf: &Bound<'_, Self>) -> PyResult<Py<PyAny>> {
+        Ok(slf.call_method0("next_line")?.unbind())
+    }
+
+    /// Waits for the next line, raising `StopAsyncIteration` once closed
+    pub async fn next_line(&self) -> PyResult<String> {
+        let state = self.state.clone();
+        poll_fn(move |cx| {
+            let mut state = match state.lock() {
+                Ok(state) => state,
+                Err(e) => return Poll::Ready(Err(PyRuntimeError::new_err(e.to_string()))),
+            };
+            if let Some(line) = state.lines.pop_front() {
+                return Poll::Ready(Ok(line));
+            }
+            if state.closed {
+                return Poll::Ready(Err(PyStopAsyncIteration::new_err(())));
+            }
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        })
+        .await
+    }
+
+    /// Ends the iteration once the lines already received have been read
+    pub fn close(&self) -> PyResult<()> {
+        match self.state.lock() {
+            Ok(mut state) => {
+                state.close();
+                Ok(())
+            }
+            Err(e) => Err(PyRuntimeError::new_err(e.to_string())),
+        }
+    }
+}
@@ -0,0 +1,1024 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    ffi::c_int,
+    hash::{Hash, Hasher},
+    sync::{Arc, Mutex},
+};
+
+use pyo3::{
+    exceptions::{PyRuntimeError, PyTypeError, PyValueError},
+    ffi,
+    prelude::*,
+    types::{PyBytes, PyDict, PyFloat, PyInt, PyList, PyMemoryView, PyTuple, PyType},
+};
+use rustyscript::{
+    deno_core::v8::{self, GetPropertyNamesArgsBuilder, KeyConversionMode, PropertyFilter},
+    js_value::Value,
+    Runtime,
+};
+use serde::{Serialize, Serializer};
+use serde_pyobject::from_pyobject;
+
+use crate::caught_exception;
+use crate::locking::GIL;
+
+/// Knobs for walking javascript values into python objects.
+/// Everything defaults to mirroring what serde_v8 would have produced.
+#[derive(Clone, Default)]
+pub struct ConvertOptions {
+    /// Surface symbol-keyed properties of objects under a `__symbols__` key
+    pub symbol_keys: bool,
+    /// Reuse python objects built for earlier results that hash the same
+    pub cache: Option<ResultCache>,
+    /// Hand out read only memoryviews over `ArrayBuffer`s and typed arrays
+    /// instead of copying them, buffers that can change size are still copied
+    pub memoryview: bool,
+    /// Hand out strings that are written like a decimal number as `decimal.Decimal`
+    pub numeric_strings: bool,
+    /// Compile `RegExp`s into `re.Pattern`s instead of handing out a `JsRegExp`
+    pub regex_patterns: bool,
+    /// Keep lone surrogates in strings the way python's `surrogatepass` does
+    /// instead of replacing them with U+FFFD
+    pub preserve_surrogates: bool,
+    /// Results that come to more than about this many bytes as json are
+    /// refused as soon as the conversion gets that far
+    pub max_result_bytes: Option<usize>,
+    /// Turn references back to an object that's still being converted into
+    /// [`CIRCULAR`] instead of refusing the value
+    pub mark_circular: bool,
+    /// Keys of plain objects written like an integer, such as `"0"` or `"-3"`,
+    /// become python ints
+    pub integer_keys: bool,
+}
+
+/// What an object referring back to one of its parents is replaced with
+pub const CIRCULAR: &str = "[Circular]";
+
+/// Python objects built from earlier results keyed by the [`Fingerprint`] of the
+/// javascript value, shared between the context and the promises it hands out.
+pub type ResultCache = Arc<Mutex<HashMap<Vec<u8>, Py<PyAny>>>>;
+
+/// Keeps everything hashed into it instead of mixing it down, the bytes are an
+/// encoding of the value so only values [`v8_to_python`] sees the same way get
+/// equal fingerprints
+#[derive(Default)]
+struct Fingerprint(Vec<u8>);
+
+impl Hasher for Fingerprint {
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.extend_from_slice(bytes);
+    }
+
+    fn finish(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        hasher.write(&self.0);
+        hasher.finish()
+    }
+}
+
+/// The cache is emptied once it holds this many results
+const RESULT_CACHE_LIMIT: usize = 256;
+
+/// Knobs for turning python arguments into javascript values
+#[derive(Clone, Default)]
+pub struct ArgOptions {
+    /// Fall back to serializing the fields of dataclasses, pydantic models
+    /// and other plain objects when serde can't take them as is
+    pub objects: bool,
+    /// Called with anything that still can't be serialized to get a
+    /// representation that can
+    pub encoder: Option<Arc<Py<PyAny>>>,
+    /// Pass `Decimal`s and `Fraction`s as their exact text instead of a number
+    pub decimals_as_strings: bool,
+}
+
+/// A Javascript `Symbol`, carried over with its description since
+/// symbols have no python equivalent.
+#[pyclass(frozen, eq, hash)]
+#[derive(PartialEq, Hash)]
+pub struct JsSymbol {
+    description: Option<String>,
+    well_known: bool,
+}
+
+#[pymethods]
+impl JsSymbol {
+    #[getter]
+    pub fn description(&self) -> Option<String> {
+        self.description.clone()
+    }
+
+    /// Returns true for symbols like `Symbol.iterator` that are built into javascript
+    #[getter]
+    pub fn is_well_known(&self) -> bool {
+        self.well_known
+    }
+
+    pub fn __repr__(&self) -> String {
+        format!(
+            "Symbol({})",
+            self.description.as_deref().unwrap_or_default()
+        )
+    }
+}
+
+impl JsSymbol {
+    fn from_v8(scope: &mut v8::HandleScope<'_>, symbol: v8::Local<'_, v8::Symbol>) -> Self {
+        let description = symbol.description(scope);
+        let description = if description.is_undefined() {
+            None
+        } else {
+            Some(description.to_rust_string_lossy(scope))
+        };
+        let well_known = [
+            v8::Symbol::get_async_iterator(scope),
+            v8::Symbol::get_has_instance(scope),
+            v8::Symbol::get_is_concat_spreadable(scope),
+            v8::Symbol::get_iterator(scope),
+            v8::Symbol::get_match(scope),
+            v8::Symbol::get_replace(scope),
+            v8::Symbol::get_search(scope),
+            v8::Symbol::get_split(scope),
+            v8::Symbol::get_to_primitive(scope),
+            v8::Symbol::get_to_string_tag(scope),
+            v8::Symbol::get_unscopables(scope),
+        ]
+        .iter()
+        .any(|s| s.strict_equals(symbol.into()));
+        Self {
+            description,
+            well_known,
+        }
+    }
+}
+
+/// A Javascript `RegExp`, kept as its source and flags since the two
+/// regex dialects don't line up exactly.
+#[pyclass(frozen, eq, hash)]
+#[derive(PartialEq, Hash)]
+pub struct JsRegExp {
+    source: String,
+    flags: String,
+}
+
+#[pymethods]
+impl JsRegExp {
+    #[getter]
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    #[getter]
+    pub fn flags(&self) -> &str {
+        &self.flags
+    }
+
+    /// Compiles the pattern with python's `re`, named groups are rewritten to python's
+    /// syntax and `i`, `m` and `s` carry over. `g`, `y`, `d` and `u` only change how
+    /// javascript runs the pattern so they are dropped.
+    pub fn compile(&self, py: Python<'_>) -> PyResult<Py<PyAny>> {
+        let re = py.import("re")?;
+        let mut flags = 0i64;
+        for flag in self.flags.chars() {
+            let name = match flag {
+                'i' => "IGNORECASE",
+                'm' => "MULTILINE",
+                's' => "DOTALL",
+                'g' | 'y' | 'd' | 'u' => continue,
+                _ => {
+                    return Err(PyValueError::new_err(format!(
+                        "the {flag} flag has no python equivalent"
+                    )));
+                }
+            };
+            flags |= re.getattr(name)?.extract::<i64>()?;
+        }
+        let compiled = re.call_method1("compile", (python_pattern(&self.source), flags));
+        match compiled {
+            Ok(pattern) => Ok(pattern.unbind()),
+            Err(e) => Err(PyValueError::new_err(format!(
+                "/{}/ can't be compiled by python: {e}",
+                self.source
+            ))),
+        }
+    }
+
+    pub fn __repr__(&self) -> String {
+        format!("/{}/{}", self.source, self.flags)
+    }
+}
+
+impl JsRegExp {
+    fn from_v8(scope: &mut v8::HandleScope<'_>, regexp: v8::Local<'_, v8::RegExp>) -> Self {
+        let source = regexp.get_source(scope).to_rust_string_lossy(scope);
+        let flags = v8::String::new(scope, "flags")
+            .and_then(|key| regexp.get(scope, key.into()))
+            .map(|flags| flags.to_rust_string_lossy(scope))
+            .unwrap_or_default();
+        Self { source, flags }
+    }
+}
+
+/// Rewrites the named groups and backreferences javascript spells differently,
+/// `(?<name>...)` becomes `(?P<name>...)` and `\k<name>` becomes `(?P=name)`.
+fn python_pattern(source: &str) -> String {
+    let mut pattern = String::with_capacity(source.len());
+    let mut chars = source.chars().peekable();
+    let mut in_class = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('k') if !in_class && chars.peek() == Some(&'<') => {
+                    chars.next();
+                    let name: String = chars.by_ref().take_while(|&c| c != '>').collect();
+                    pattern.push_str(&format!("(?P={name})"));
+                }
+                Some(escaped) => {
+                    pattern.push('\\');
+                    pattern.push(escaped);
+                }
+                None => pattern.push('\\'),
+            },
+            '[' => {
+                in_class = true;
+                pattern.push(c);
+            }
+            ']' => {
+                in_class = false;
+                pattern.push(c);
+            }
+            '(' if !in_class
+                && source_at(&chars, "?<")
+                && !source_at(&chars, "?<=")
+                && !source_at(&chars, "?<!") =>
+            {
+                chars.next();
+                chars.next();
+                pattern.push_str("(?P<");
+            }
+            _ => pattern.push(c),
+        }
+    }
+    pattern
+}
+
+fn source_at(chars: &std::iter::Peekable<std::str::Chars<'_>>, prefix: &str) -> bool {
+    chars.clone().take(prefix.len()).eq(prefix.chars())
+}
+
+/// Read only view of the memory behind an `ArrayBuffer`, holding on to the
+/// backing store keeps the memory alive for as long as python needs it.
+#[pyclass(frozen)]
+pub struct JsBuffer {
+    /// Never read, only held so the memory stays put
+    _store: GIL<v8::SharedRef<v8::BackingStore>>,
+    data: usize,
+    len: usize,
+}
+
+#[pymethods]
+impl JsBuffer {
+    unsafe fn __getbuffer__(
+        slf: Bound<'_, Self>,
+        view: *mut ffi::Py_buffer,
+        flags: c_int,
+    ) -> PyResult<()> {
+        let (data, len) = {
+            let this = slf.get();
+            (
+                this.data as *mut std::ffi::c_void,
+                this.len as ffi::Py_ssize_t,
+            )
+        };
+        // Fills the view in as a flat run of bytes and rejects requests for writable buffers
+        if unsafe { ffi::PyBuffer_FillInfo(view, slf.as_ptr(), data, len, 1, flags) } == -1 {
+            return Err(PyErr::fetch(slf.py()));
+        }
+        Ok(())
+    }
+
+    pub fn __len__(&self) -> usize {
+        self.len
+    }
+}
+
+/// The backing store behind an `ArrayBuffer` or one of its views along with
+/// the address and length of the bytes it covers, `None` for anything else.
+fn buffer_parts(
+    value: v8::Local<'_, v8::Value>,
+) -> Option<(v8::SharedRef<v8::BackingStore>, usize, usize)> {
+    let (store, offset, len) = if let Ok(buffer) = v8::Local::<v8::ArrayBuffer>::try_from(value) {
+        (buffer.get_backing_store(), 0, buffer.byte_length())
+    } else {
+        let view = v8::Local::<v8::ArrayBufferView>::try_from(value).ok()?;
+        (
+            view.get_backing_store()?,
+            view.byte_offset(),
+            view.byte_length(),
+        )
+    };
+    // Detached and empty buffers have no memory at all
+    let Some(base) = store.data() else {
+        return Some((store, 0, 0));
+    };
+    if offset + len > store.byte_length() {
+        return None;
+    }
+    let data = base.as_ptr() as usize + offset;
+    Some((store, data, len))
+}
+
+/// Borrows the bytes found by [`buffer_parts`], the store has to outlive the slice
+fn buffer_slice(_store: &v8::SharedRef<v8::BackingStore>, data: usize, len: usize) -> &[u8] {
+    if len == 0 {
+        return &[];
+    }
+    unsafe { std::slice::from_raw_parts(data as *const u8, len) }
+}
+
+/// Wraps binary data in a memoryview when it's safe to share, copying it into bytes otherwise
+fn buffer_to_python(
+    py: Python<'_>,
+    value: v8::Local<'_, v8::Value>,
+) -> PyResult<Option<Py<PyAny>>> {
+    let Some((store, data, len)) = buffer_parts(value) else {
+        return Ok(None);
+    };
+    // Memory that can shrink or be written from other threads can't be lent out
+    if len == 0 || store.is_shared() || store.is_resizable_by_user_javascript() {
+        let bytes = PyBytes::new(py, buffer_slice(&store, data, len));
+        return Ok(Some(bytes.into_any().unbind()));
+    }
+    let buffer = Bound::new(
+        py,
+        JsBuffer {
+            _store: GIL::new(store),
+            data,
+            len,
+        },
+    )?;
+    Ok(Some(
+        PyMemoryView::from(&buffer.into_any())?.into_any().unbind(),
+    ))
+}
+
+/// Copies the bytes of an `ArrayBuffer` or one of its views straight into python
+pub fn value_to_bytes(runtime: &mut Runtime, value: &Value) -> PyResult<Py<PyBytes>> {
+    let mut scope = runtime.deno_runtime().handle_scope();
+    let local = v8::Local::new(&mut scope, value.as_v8());
+    let Some((store, data, len)) = buffer_parts(local) else {
+        let kind = local.type_of(&mut scope).to_rust_string_lossy(&mut scope);
+        return Err(PyTypeError::new_err(format!(
+            "expected an ArrayBuffer or typed array, got {kind}"
+        )));
+    };
+    Python::with_gil(|py| Ok(PyBytes::new(py, buffer_slice(&store, data, len)).unbind()))
+}
+
+/// Converts a value returned from the runtime into a python object
+#[inline]
+pub fn value_to_python(
+    runtime: &mut Runtime,
+    value: &Value,
+    options: &ConvertOptions,
+) -> PyResult<Py<PyAny>> {
+    let mut scope = runtime.deno_runtime().handle_scope();
+    let local = v8::Local::new(&mut scope, value.as_v8());
+    Python::with_gil(|py| {
+        // Primitives are cheaper to build than to hash
+        let Some(cache) = options.cache.as_ref().filter(|_| local.is_object()) else {
+            return v8_to_python(py, &mut scope, local, options);
+        };
+        let mut fingerprint = Fingerprint::default();
+        hash_v8(&mut scope, local, options, &mut fingerprint);
+        let key = fingerprint.0;
+        let mut cache = cache
+            .lock()
+            .map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+        if let Some(hit) = cache.get(&key) {
+            return Ok(hit.clone_ref(py));
+        }
+        let result = v8_to_python(py, &mut scope, local, options)?;
+        if cache.len() >= RESULT_CACHE_LIMIT {
+            cache.clear();
+        }
+        cache.insert(key, result.clone_ref(py));
+        Ok(result)
+    })
+}
+
+/// Hashes everything about a value that [`v8_to_python`] would look at,
+/// values which hash the same convert into equal python objects. Everything
+/// of variable length is either prefixed with its length or ended so the
+/// written bytes can only be read back one way.
+fn hash_v8<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    value: v8::Local<'s, v8::Value>,
+    options: &ConvertOptions,
+    state: &mut impl Hasher,
+) {
+    hash_tracked(scope, value, options, state, &mut Vec::new());
+}
+
+/// [`hash_v8`] keeping track of the objects it's inside of so cycles end
+fn hash_tracked<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    value: v8::Local<'s, v8::Value>,
+    options: &ConvertOptions,
+    state: &mut impl Hasher,
+    ancestors: &mut Vec<v8::Local<'s, v8::Object>>,
+) {
+    let Ok(object) = v8::Local::<v8::Object>::try_from(value) else {
+        return hash_value(scope, value, options, state, ancestors);
+    };
+    if ancestors.iter().any(|a| a.strict_equals(value)) {
+        13u8.hash(state);
+        return;
+    }
+    ancestors.push(object);
+    hash_value(scope, value, options, state, ancestors);
+    ancestors.pop();
+}
+
+fn hash_value<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    value: v8::Local<'s, v8::Value>,
+    options: &ConvertOptions,
+    state: &mut impl Hasher,
+    ancestors: &mut Vec<v8::Local<'s, v8::Object>>,
+) {
+    if value.is_null_or_undefined() {
+        0u8.hash(state);
+    } else if value.is_boolean() {
+        1u8.hash(state);
+        value.is_true().hash(state);
+    } else if value.is_uint32() || value.is_int32() {
+        2u8.hash(state);
+        value.integer_value(scope).unwrap_or_default().hash(state);
+    } else if value.is_number() {
+        3u8.hash(state);
+        value
+            .number_value(scope)
+            .unwrap_or(f64::NAN)
+            .to_bits()
+            .hash(state);
+    } else if options.preserve_surrogates
+        && let Ok(string) = v8::Local::<v8::String>::try_from(value)
+    {
+        4u8.hash(state);
+        code_units(scope, string).hash(state);
+    } else if value.is_big_int() || value.is_string() {
+        (if value.is_string() { 4u8 } else { 5u8 }).hash(state);
+        value.to_rust_string_lossy(scope).hash(state);
+    } else if let Ok(symbol) = v8::Local::<v8::Symbol>::try_from(value) {
+        6u8.hash(state);
+        JsSymbol::from_v8(scope, symbol).hash(state);
+    } else if let Some((store, data, len)) = buffer_parts(value) {
+        11u8.hash(state);
+        buffer_slice(&store, data, len).hash(state);
+    } else if let Ok(regexp) = v8::Local::<v8::RegExp>::try_from(value) {
+        12u8.hash(state);
+        JsRegExp::from_v8(scope, regexp).hash(state);
+    } else if let Ok(array) = v8::Local::<v8::Array>::try_from(value) {
+        7u8.hash(state);
+        array.length().hash(state);
+        for i in 0..array.length() {
+            match array.get_index(scope, i) {
+                Some(item) => hash_tracked(scope, item, options, state, ancestors),
+                None => 0u8.hash(state),
+            }
+        }
+    } else if let Ok(map) = v8::Local::<v8::Map>::try_from(value) {
+        8u8.hash(state);
+        let pairs = map.as_array(scope);
+        pairs.length().hash(state);
+        for i in 0..pairs.length() {
+            if let Some(item) = pairs.get_index(scope, i) {
+                hash_tracked(scope, item, options, state, ancestors);
+            }
+        }
+    } else if let Ok(object) = v8::Local::<v8::Object>::try_from(value) {
+        9u8.hash(state);
+        let mut filter = PropertyFilter::ONLY_ENUMERABLE;
+        if !options.symbol_keys {
+            filter = filter | PropertyFilter::SKIP_SYMBOLS;
+        }
+        let args = GetPropertyNamesArgsBuilder::new()
+            .property_filter(filter)
+            .key_conversion(KeyConversionMode::ConvertToString)
+            .build();
+        if let Some(keys) = object.get_own_property_names(scope, args) {
+            for i in 0..keys.length() {
+                let Some(key) = keys.get_index(scope, i) else {
+                    continue;
+                };
+                let Some(item) = object.get(scope, key) else {
+                    continue;
+                };
+                // Only string keys skip undefined values when converting
+                if item.is_undefined() && key.is_string() {
+                    continue;
+                }
+                hash_tracked(scope, key, options, state, ancestors);
+                hash_tracked(scope, item, options, state, ancestors);
+            }
+        }
+        // Keys are all strings or symbols, so this can't be mistaken for one
+        14u8.hash(state);
+    } else {
+        10u8.hash(state);
+    }
+}
+
+/// Serializes a value returned from the runtime with `JSON.stringify`
+pub fn value_to_json(runtime: &mut Runtime, value: &Value) -> PyResult<String> {
+    let mut scope = runtime.deno_runtime().handle_scope();
+    let local = v8::Local::new(&mut scope, value.as_v8());
+    let tc = &mut v8::TryCatch::new(&mut scope);
+    match v8::json::stringify(tc, local) {
+        // Values json has no room for stringify to undefined
+        Some(json) if !local.is_undefined() && !local.is_function() && !local.is_symbol() => {
+            Ok(json.to_rust_string_lossy(tc))
+        }
+        Some(_) => Ok("null".to_string()),
+        None => Err(caught_exception(tc)),
+    }
+}
+
+/// Matches `-12`, `1.10` and `2.5e-3` but nothing with spaces, a bare `.5`,
+/// or `NaN` and `Infinity` which `Decimal` would also have taken.
+fn is_decimal(s: &str) -> bool {
+    fn digits(s: &str) -> (&str, bool) {
+        let rest = s.trim_start_matches(|c: char| c.is_ascii_digit());
+        (rest, rest.len() < s.len())
+    }
+    let s = s.strip_prefix(['-', '+']).unwrap_or(s);
+    let (mut rest, whole) = digits(s);
+    if !whole {
+        return false;
+    }
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let (after, any) = digits(fraction);
+        if !any {
+            return false;
+        }
+        rest = after;
+    }
+    if let Some(exponent) = rest.strip_prefix(['e', 'E']) {
+        let exponent = exponent.strip_prefix(['-', '+']).unwrap_or(exponent);
+        let (after, any) = digits(exponent);
+        if !any {
+            return false;
+        }
+        rest = after;
+    }
+    rest.is_empty()
+}
+
+/// The UTF-16 code units of a string, lone surrogates included
+fn code_units(scope: &mut v8::HandleScope<'_>, string: v8::Local<'_, v8::String>) -> Vec<u16> {
+    let mut units = vec![0u16; string.length()];
+    string.write_v2(scope, 0, &mut units, v8::WriteFlags::empty());
+    units
+}
+
+/// Builds a python str that keeps the lone surrogates of `string`, or `None`
+/// when it has none and rust's own utf-8 conversion is just as good
+fn surrogate_string(
+    py: Python<'_>,
+    scope: &mut v8::HandleScope<'_>,
+    string: v8::Local<'_, v8::String>,
+) -> PyResult<Option<Py<PyAny>>> {
+    // One byte strings can't hold a surrogate
+    if string.is_onebyte() {
+        return Ok(None);
+    }
+    let units = code_units(scope, string);
+    if char::decode_utf16(units.iter().copied()).all(|c| c.is_ok()) {
+        return Ok(None);
+    }
+    let mut byteorder: c_int = if cfg!(target_endian = "little") {
+        -1
+    } else {
+        1
+    };
+    // SAFETY: the pointer and length describe `units` which outlives the call,
+    // and the errors name is a nul terminated static string
+    unsafe {
+        let decoded = ffi::PyUnicode_DecodeUTF16(
+            units.as_ptr().cast(),
+            (units.len() * 2) as ffi::Py_ssize_t,
+            c"surrogatepass".as_ptr(),
+            &mut byteorder,
+        );
+        Ok(Some(Bound::from_owned_ptr_or_err(py, decoded)?.unbind()))
+    }
+}
+
+/// Walks a v8 value and builds the equivalent python object
+pub fn v8_to_python<'s>(
+    py: Python<'_>,
+    scope: &mut v8::HandleScope<'s>,
+    value: v8::Local<'s, v8::Value>,
+    options: &ConvertOptions,
+) -> PyResult<Py<PyAny>> {
+    let mut progress = Progress {
+        ancestors: Vec::new(),
+        size: 0,
+    };
+    to_python(py, scope, value, options, &mut progress)
+}
+
+/// How far [`v8_to_python`] has got through a value
+struct Progress<'s> {
+    /// The objects it's inside of, so one that contains itself is caught
+    /// instead of recursing until the stack runs out
+    ancestors: Vec<v8::Local<'s, v8::Object>>,
+    /// Roughly how many bytes of json what's been converted comes to, only
+    /// kept with `max_result_bytes`
+    size: usize,
+}
+
+impl Progress<'_> {
+    /// Adds `bytes` to the size, refusing the value once it's past `max_result_bytes`
+    fn grow(&mut self, options: &ConvertOptions, bytes: impl FnOnce() -> usize) -> PyResult<()> {
+        let Some(limit) = options.max_result_bytes else {
+            return Ok(());
+        };
+        self.size = self.size.saturating_add(bytes());
+        if self.size > limit {
+            return Err(PyValueError::new_err(format!(
+                "result is over {limit} bytes as json which exceeds max_result_bytes"
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// [`v8_to_python`] for a value somewhere inside of the one it was given
+fn to_python<'s>(
+    py: Python<'_>,
+    scope: &mut v8::HandleScope<'s>,
+    value: v8::Local<'s, v8::Value>,
+    options: &ConvertOptions,
+    progress: &mut Progress<'s>,
+) -> PyResult<Py<PyAny>> {
+    let Ok(object) = v8::Local::<v8::Object>::try_from(value) else {
+        return walk(py, scope, value, options, progress);
+    };
+    if progress.ancestors.iter().any(|a| a.strict_equals(value)) {
+        if options.mark_circular {
+            progress.grow(options, || CIRCULAR.len() + 2)?;
+            return Ok(CIRCULAR.into_pyobject(py)?.into_any().unbind());
+        }
+        return Err(PyValueError::new_err("circular reference"));
+    }
+    progress.ancestors.push(object);
+    let result = walk(py, scope, value, options, progress);
+    progress.ancestors.pop();
+    result
+}
+
+/// The int a key stands for with `integer_keys`, keys like `"01"` or `"+1"`
+/// that wouldn't be written back the same way stay strings
+fn integer_key(key: &str, options: &ConvertOptions) -> Option<i64> {
+    if !options.integer_keys {
+        return None;
+    }
+    key.parse::<i64>().ok().filter(|n| n.to_string() == key)
+}
+
+fn walk<'s>(
+    py: Python<'_>,
+    scope: &mut v8::HandleScope<'s>,
+    value: v8::Local<'s, v8::Value>,
+    options: &ConvertOptions,
+    progress: &mut Progress<'s>,
+) -> PyResult<Py<PyAny>> {
+    if value.is_null_or_undefined() {
+        progress.grow(options, || 4)?;
+        return Ok(py.None());
+    }
+    if value.is_boolean() {
+        progress.grow(options, || 5)?;
+        return Ok(value
+            .is_true()
+            .into_pyobject(py)?
+            .to_owned()
+            .into_any()
+            .unbind());
+    }
+    if value.is_number() {
+        // Mirror serde_v8 which only hands out integers for 32 bit values
+        if value.is_uint32() || value.is_int32() {
+            let n = value.integer_value(scope).unwrap_or_default();
+            progress.grow(options, || n.to_string().len())?;
+            return Ok(n.into_pyobject(py)?.into_any().unbind());
+        }
+        // NaN and the infinities come through as is since json is never involved
+        let n = value.number_value(scope).unwrap_or(f64::NAN);
+        progress.grow(options, || n.to_string().len())?;
+        return Ok(n.into_pyobject(py)?.into_any().unbind());
+    }
+    if value.is_big_int() {
+        let s = value.to_rust_string_lossy(scope);
+        progress.grow(options, || s.len())?;
+        return Ok(py.get_type::<PyInt>().call1((s,))?.unbind());
+    }
+    if let Ok(string) = v8::Local::<v8::String>::try_from(value) {
+        // Measured before anything is copied out of v8
+        progress.grow(options, || string.utf8_length(scope) + 2)?;
+        if options.preserve_surrogates
+            && let Some(s) = surrogate_string(py, scope, string)?
+        {
+            return Ok(s);
+        }
+        let s = value.to_rust_string_lossy(scope);
+        if options.numeric_strings && is_decimal(&s) {
+            let decimal = py.import("decimal")?.getattr("Decimal")?;
+            return Ok(decimal.call1((s,))?.unbind());
+        }
+        return Ok(s.into_pyobject(py)?.into_any().unbind());
+    }
+    if let Ok(symbol) = v8::Local::<v8::Symbol>::try_from(value) {
+        let symbol = JsSymbol::from_v8(scope, symbol);
+        progress.grow(options, || {
+            symbol
+                .description
+                .as_ref()
+                .map_or(4, |description| description.len() + 2)
+        })?;
+        return Ok(Py::new(py, symbol)?.into_any());
+    }
+    if let Ok(regexp) = v8::Local::<v8::RegExp>::try_from(value) {
+        let regexp = JsRegExp::from_v8(scope, regexp);
+        progress.grow(options, || regexp.source.len() + regexp.flags.len() + 4)?;
+        if options.regex_patterns {
+            return regexp.compile(py);
+        }
+        return Ok(Py::new(py, regexp)?.into_any());
+    }
+    // Binary data becomes bytes wherever it sits instead of a dict of indices
+    if value.is_array_buffer() || value.is_array_buffer_view() {
+        if let Some((_, _, len)) = buffer_parts(value) {
+            progress.grow(options, || len)?;
+        }
+        if options.memoryview
+            && let Some(buffer) = buffer_to_python(py, value)?
+        {
+            return Ok(buffer);
+        }
+        if let Some((store, data, len)) = buffer_parts(value) {
+            let bytes = PyBytes::new(py, buffer_slice(&store, data, len));
+            return Ok(bytes.into_any().unbind());
+        }
+    }
+    if let Ok(array) = v8::Local::<v8::Array>::try_from(value) {
+        // The brackets and commas
+        progress.grow(options, || 2 + array.length() as usize)?;
+        let list = PyList::empty(py);
+        for i in 0..array.length() {
+            let item = match array.get_index(scope, i) {
+                Some(item) => to_python(py, scope, item, options, progress)?,
+                None => py.None(),
+            };
+            list.append(item)?;
+        }
+        return Ok(list.into_any().unbind());
+    }
+    if let Ok(map) = v8::Local::<v8::Map>::try_from(value) {
+        let dict = PyDict::new(py);
+        let pairs = map.as_array(scope);
+        // The braces, colons and commas
+        progress.grow(options, || 2 + pairs.length() as usize)?;
+        for i in (0..pairs.length()).step_by(2) {
+            let (Some(k), Some(v)) = (pairs.get_index(scope, i), pairs.get_index(scope, i + 1))
+            else {
+                continue;
+            };
+            dict.set_item(
+                to_python(py, scope, k, options, progress)?,
+                to_python(py, scope, v, options, progress)?,
+            )?;
+        }
+        return Ok(dict.into_any().unbind());
+    }
+    if let Ok(object) = v8::Local::<v8::Object>::try_from(value) {
+        progress.grow(options, || 2)?;
+        let dict = PyDict::new(py);
+        let args = GetPropertyNamesArgsBuilder::new()
+            .key_conversion(KeyConversionMode::ConvertToString)
+            .build();
+        if let Some(keys) = object.get_own_property_names(scope, args) {
+            for i in 0..keys.length() {
+                let Some(key) = keys.get_index(scope, i) else {
+                    continue;
+                };
+                let Some(item) = object.get(scope, key) else {
+                    continue;
+                };
+                // Undefined values are skipped like serde_v8 used to do
+                if item.is_undefined() {
+                    continue;
+                }
+                let key_text = key.to_rust_string_lossy(scope);
+                // The quotes, colon and comma around it
+                progress.grow(options, || key_text.len() + 4)?;
+                let surrogate_key = match v8::Local::<v8::String>::try_from(key) {
+                    Ok(key) if options.preserve_surrogates => surrogate_string(py, scope, key)?,
+                    _ => None,
+                };
+                let item = to_python(py, scope, item, options, progress)?;
+                match (surrogate_key, integer_key(&key_text, options)) {
+                    (_, Some(key)) => dict.set_item(key, item)?,
+                    (Some(key), None) => dict.set_item(key, item)?,
+                    (None, None) => dict.set_item(key_text, item)?,
+                }
+            }
+        }
+        if options.symbol_keys {
+            let args = GetPropertyNamesArgsBuilder::new()
+                .property_filter(PropertyFilter::ONLY_ENUMERABLE | PropertyFilter::SKIP_STRINGS)
+                .build();
+            let symbols = PyDict::new(py);
+            if let Some(keys) = object.get_own_property_names(scope, args) {
+                for i in 0..keys.length() {
+                    let Some(key) = keys.get_index(scope, i) else {
+                        continue;
+                    };
+                    let Ok(symbol) = v8::Local::<v8::Symbol>::try_from(key) else {
+                        continue;
+                    };
+                    let Some(item) = object.get(scope, key) else {
+                        continue;
+                    };
+                    symbols.set_item(
+                        Py::new(py, JsSymbol::from_v8(scope, symbol))?,
+                        to_python(py, scope, item, options, progress)?,
+                    )?;
+                }
+            }
+            if !symbols.is_empty() {
+                dict.set_item("__symbols__", symbols)?;
+            }
+        }
+        return Ok(dict.into_any().unbind());
+    }
+    Ok(py.None())
+}
+
+/// An argument on its way to javascript, serializes as the json value it holds
+/// except that `NaN` and the infinities come through as real numbers and bytes
+/// as a `Uint8Array`
+#[derive(Debug, Clone)]
+pub enum Argument {
+    Json(serde_json::Value),
+    NonFinite(f64),
+    Bytes(Vec<u8>),
+    Array(Vec<Argument>),
+    Object(Vec<(String, Argument)>),
+}
+
+impl Serialize for Argument {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Argument::Json(value) => value.serialize(serializer),
+            Argument::NonFinite(n) => serializer.serialize_f64(*n),
+            Argument::Bytes(bytes) => serializer.serialize_bytes(bytes),
+            Argument::Array(items) => serializer.collect_seq(items),
+            Argument::Object(entries) => {
+                serializer.collect_map(entries.iter().map(|(key, value)| (key, value)))
+            }
+        }
+    }
+}
+
+/// Turns a single python argument into an [`Argument`] in one walk, so
+/// `NaN` and the infinities survive wherever they sit, including the fields
+/// of objects and `Decimal`s
+pub fn python_to_argument(obj: &Bound<'_, PyAny>, options: &ArgOptions) -> PyResult<Argument> {
+    to_argument(obj, options, true)
+}
+
+fn to_argument(obj: &Bound<'_, PyAny>, options: &ArgOptions, encode: bool) -> PyResult<Argument> {
+    if let Ok(dict) = obj.downcast::<PyDict>() {
+        let mut entries = Vec::with_capacity(dict.len());
+        for (k, v) in dict.iter() {
+            let key = match k.extract::<String>() {
+                Ok(key) => key,
+                Err(_) => match from_pyobject::<serde_json::Value, _>(k) {
+                    Ok(serde_json::Value::String(key)) => key,
+                    Ok(key) => key.to_string(),
+                    Err(e) => return Err(PyValueError::new_err(e.to_string())),
+                },
+            };
+            entries.push((key, to_argument(&v, options, encode)?));
+        }
+        return Ok(Argument::Object(entries));
+    }
+    if obj.is_instance_of::<PyList>() || obj.is_instance_of::<PyTuple>() {
+        let mut items = Vec::new();
+        for item in obj.try_iter()? {
+            items.push(to_argument(&item?, options, encode)?);
+        }
+        return Ok(Argument::Array(items));
+    }
+    if let Ok(float) = obj.downcast::<PyFloat>() {
+        return Ok(float_argument(float.value()));
+    }
+    let error = match from_pyobject(obj.clone()) {
+        Ok(value) => return Ok(Argument::Json(value)),
+        Err(e) => PyValueError::new_err(e.to_string()),
+    };
+    if let Some(number) = exact_number(obj, options.decimals_as_strings)? {
+        return to_argument(&number, options, encode);
+    }
+    if options.objects {
+        let fields = object_fields(obj)?;
+        if !fields.is(obj) {
+            return to_argument(&fields, options, encode);
+        }
+    }
+    match &options.encoder {
+        Some(encoder) if encode => match encoder.bind(obj.py()).call1((obj,)) {
+            Ok(encoded) => to_argument(&encoded, options, false).map_err(|_| error),
+            Err(_) => Err(error),
+        },
+        _ => Err(error),
+    }
+}
+
+/// Serde quietly makes null out of `NaN` and the infinities
+fn float_argument(n: f64) -> Argument {
+    match serde_json::Number::from_f64(n) {
+        Some(n) => Argument::Json(serde_json::Value::Number(n)),
+        None => Argument::NonFinite(n),
+    }
+}
+
+/// Swaps a `Decimal` or `Fraction` for a float or with `as_strings` for its
+/// text, serde takes neither. Floats that can't hold the exact value come with
+/// a `RuntimeWarning`.
+fn exact_number<'py>(
+    obj: &Bound<'py, PyAny>,
+    as_strings: bool,
+) -> PyResult<Option<Bound<'py, PyAny>>> {
+    let py = obj.py();
+    let decimal = py.import("decimal")?.getattr("Decimal")?;
+    let fraction = py.import("fractions")?.getattr("Fraction")?;
+    if !obj.is_instance(&decimal)? && !obj.is_instance(&fraction)? {
+        return Ok(None);
+    }
+    if as_strings {
+        return Ok(Some(obj.str()?.into_any()));
+    }
+    let n: f64 = obj.extract()?;
+    if n.is_finite() && !obj.get_type().call1((n,))?.eq(obj)? {
+        let message = format!("{} loses precision as a javascript number", obj.repr()?);
+        let message = std::ffi::CString::new(message).unwrap_or_default();
+        let category = py.get_type::<pyo3::exceptions::PyRuntimeWarning>();
+        PyErr::warn(py, &category, &message, 1)?;
+    }
+    Ok(Some(n.into_pyobject(py)?.into_any()))
+}
+
+/// Recursively swaps objects out for dicts of their fields so serde can take over
+fn object_fields<'py>(obj: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyAny>> {
+    let py = obj.py();
+    if let Ok(dict) = obj.downcast::<PyDict>() {
+        let fields = PyDict::new(py);
+        for (k, v) in dict.iter() {
+            fields.set_item(k, object_fields(&v)?)?;
+        }
+        return Ok(fields.into_any());
+    }
+    if obj.is_instance_of::<PyList>() || obj.is_instance_of::<PyTuple>() {
+        let mut items = Vec::new();
+        for item in obj.try_iter()? {
+            items.push(object_fields(&item?)?);
+        }
+        return Ok(PyList::new(py, items)?.into_any());
+    }
+    if obj.is_instance_of::<PyType>() {
+        return Ok(obj.clone());
+    }
+    let dataclasses = py.import("dataclasses")?;
+    if dataclasses
+        .call_method1("is_dataclass", (obj,))?
+        .is_truthy()?
+    {
+        return object_fields(&dataclasses.call_method1("asdict", (obj,))?);
+    }
+    // pydantic v2 and v1 respectively
+    for method in ["model_dump", "dict"] {
+        if let Ok(f) = obj.getattr(method)
+            && f.is_callable()
+        {
+            return object_fields(&f.call0()?);
+        }
+    }
+    match obj.getattr("__dict__") {
+        Ok(fields) => object_fields(&fields),
+        Err(_) => Ok(obj.clone()),
+    }
+}
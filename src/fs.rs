@@ -0,0 +1,194 @@
+//! A small `fs` global for scripts that only reaches the host directories
+//! mounted into it, everything else on disk stays out of reach.
+use std::{
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use pyo3::{
+    exceptions::{PyNotADirectoryError, PyRuntimeError, PyValueError},
+    prelude::*,
+};
+use rustyscript::{Error as RSError, Runtime, Undefined};
+use serde_json::Value;
+
+use crate::scripts;
+
+struct Mount {
+    js_path: PathBuf,
+    host_path: PathBuf,
+    readonly: bool,
+}
+
+/// The table of virtual paths to host directories, shared with the functions
+/// the `fs` global calls into.
+#[derive(Clone, Default)]
+pub struct Mounts {
+    table: Arc<Mutex<Vec<Mount>>>,
+}
+
+/// Folds away `.` and `..` so a path can't climb out of the mount it starts in
+fn normalize(path: &Path) -> Option<PathBuf> {
+    if !path.is_absolute() {
+        return None;
+    }
+    let mut normalized = PathBuf::from("/");
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => normalized.push(part),
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            _ => {}
+        }
+    }
+    Some(normalized)
+}
+
+fn arg(args: &[Value], i: usize) -> Result<&str, RSError> {
+    args.get(i)
+        .and_then(Value::as_str)
+        .ok_or_else(|| RSError::Runtime(format!("fs: argument {i} must be a string")))
+}
+
+fn io_error(path: &str, e: std::io::Error) -> RSError {
+    RSError::Runtime(format!("{e}: {path}"))
+}
+
+/// Writes to a path `resolve` checked, a link swapped in since then isn't followed
+fn write_file(host: &Path, data: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.custom_flags(libc::O_NOFOLLOW);
+    }
+    options.open(host)?.write_all(data)
+}
+
+impl Mounts {
+    pub fn is_empty(&self) -> bool {
+        self.table.lock().map_or(true, |table| table.is_empty())
+    }
+
+    /// Exposes `host_path` to scripts under `js_path`, mounting over a path replaces it
+    pub fn add(&self, js_path: &str, host_path: &str, readonly: bool) -> PyResult<()> {
+        let Some(js_path) = normalize(Path::new(js_path)) else {
+            return Err(PyValueError::new_err("js_path must be absolute"));
+        };
+        let host_path = match std::fs::canonicalize(host_path) {
+            Ok(path) if path.is_dir() => path,
+            Ok(path) => {
+                return Err(PyNotADirectoryError::new_err(path.display().to_string()));
+            }
+            Err(e) => return Err(PyNotADirectoryError::new_err(format!("{e}: {host_path}"))),
+        };
+        let mut table = self
+            .table
+            .lock()
+            .map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+        table.retain(|m| m.js_path != js_path);
+        table.push(Mount {
+            js_path,
+            host_path,
+            readonly,
+        });
+        Ok(())
+    }
+
+    /// Maps a path scripts asked for onto the host, refusing anything outside
+    /// of a mount and writes to mounts that are read only.
+    fn resolve(&self, path: &str, write: bool) -> Result<PathBuf, RSError> {
+        let denied = || RSError::Runtime(format!("EACCES: {path} is not mounted"));
+        let virtual_path = normalize(Path::new(path)).ok_or_else(denied)?;
+        let table = self
+            .table
+            .lock()
+            .map_err(|e| RSError::Runtime(e.to_string()))?;
+        // The deepest mount wins when they're nested
+        let mount = table
+            .iter()
+            .filter(|m| virtual_path.starts_with(&m.js_path))
+            .max_by_key(|m| m.js_path.components().count())
+            .ok_or_else(denied)?;
+        if write && mount.readonly {
+            return Err(RSError::Runtime(format!(
+                "EROFS: {path} is mounted read only"
+            )));
+        }
+        let relative = virtual_path
+            .strip_prefix(&mount.js_path)
+            .map_err(|_| denied())?;
+        let host = mount.host_path.join(relative);
+        // Symlinks could still lead out of the mount so check where they really go
+        // and hand back that, a write only checks the parent when there's nothing
+        // at all at the path, not even a dangling link
+        let (existing, name) = if write && std::fs::symlink_metadata(&host).is_err() {
+            (host.parent().unwrap_or(&host), host.file_name())
+        } else {
+            (host.as_path(), None)
+        };
+        match std::fs::canonicalize(existing) {
+            Ok(real) if real.starts_with(&mount.host_path) => Ok(match name {
+                Some(name) => real.join(name),
+                None => real,
+            }),
+            Ok(_) => Err(denied()),
+            Err(e) => Err(io_error(path, e)),
+        }
+    }
+
+    /// Registers the functions behind the `fs` global and defines it
+    pub fn install(&self, runtime: &mut Runtime) -> PyResult<()> {
+        let mounts = self.clone();
+        let read = move |args: &[Value]| {
+            let path = arg(args, 0)?;
+            let data =
+                std::fs::read(mounts.resolve(path, false)?).map_err(|e| io_error(path, e))?;
+            Ok(Value::String(STANDARD.encode(data)))
+        };
+        let mounts = self.clone();
+        let read_text = move |args: &[Value]| {
+            let path = arg(args, 0)?;
+            let text = std::fs::read_to_string(mounts.resolve(path, false)?)
+                .map_err(|e| io_error(path, e))?;
+            Ok(Value::String(text))
+        };
+        let mounts = self.clone();
+        let write = move |args: &[Value]| {
+            let (path, data) = (arg(args, 0)?, arg(args, 1)?);
+            let data = STANDARD
+                .decode(data)
+                .map_err(|e| RSError::Runtime(e.to_string()))?;
+            write_file(&mounts.resolve(path, true)?, &data).map_err(|e| io_error(path, e))?;
+            Ok(Value::Null)
+        };
+        let mounts = self.clone();
+        let read_dir = move |args: &[Value]| {
+            let path = arg(args, 0)?;
+            let mut names = Vec::new();
+            for entry in
+                std::fs::read_dir(mounts.resolve(path, false)?).map_err(|e| io_error(path, e))?
+            {
+                let entry = entry.map_err(|e| io_error(path, e))?;
+                names.push(Value::String(
+                    entry.file_name().to_string_lossy().to_string(),
+                ));
+            }
+            Ok(Value::Array(names))
+        };
+        let registered = runtime
+            .register_function(scripts::FS_READ, read)
+            .and_then(|_| runtime.register_function(scripts::FS_READ_TEXT, read_text))
+            .and_then(|_| runtime.register_function(scripts::FS_WRITE, write))
+            .and_then(|_| runtime.register_function(scripts::FS_READ_DIR, read_dir))
+            .and_then(|_| runtime.eval::<Undefined>(scripts::FS_SHIM));
+        match registered {
+            Ok(_) => Ok(()),
+            Err(e) => Err(PyRuntimeError::new_err(e.to_string())),
+        }
+    }
+}
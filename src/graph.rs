@@ -0,0 +1,122 @@
+//! Walking the imports of modules without evaluating any of them.
+use std::collections::VecDeque;
+
+use pyo3::{
+    exceptions::{PyFileNotFoundError, PyNotADirectoryError, PyValueError},
+    prelude::*,
+    types::PyDict,
+};
+use rustyscript::{
+    deno_core::{resolve_import, resolve_path, v8, ModuleSpecifier},
+    Module, Runtime,
+};
+use sha2::{Digest, Sha256};
+
+use crate::compile_error;
+use crate::transpile::transpile;
+
+/// Compiles `code` as a module and lists the specifiers it imports as written,
+/// the inner error is the compile error when the source isn't valid.
+pub fn module_imports(
+    scope: &mut v8::HandleScope<'_>,
+    specifier: &ModuleSpecifier,
+    code: &str,
+) -> PyResult<Result<Vec<String>, String>> {
+    let tc = &mut v8::TryCatch::new(scope);
+    let (Some(source), Some(name)) = (
+        v8::String::new(tc, code),
+        v8::String::new(tc, specifier.as_str()),
+    ) else {
+        return Err(PyValueError::new_err("source could not be encoded"));
+    };
+    let origin = v8::ScriptOrigin::new(
+        tc,
+        name.into(),
+        0,
+        0,
+        false,
+        0,
+        None,
+        false,
+        false,
+        true,
+        None,
+    );
+    let mut source = v8::script_compiler::Source::new(source, Some(&origin));
+    let Some(compiled) = v8::script_compiler::compile_module(tc, &mut source) else {
+        return Ok(Err(compile_error(tc)));
+    };
+    let requests = compiled.get_module_requests();
+    let mut imports = Vec::with_capacity(requests.length());
+    for i in 0..requests.length() {
+        let Some(request) = requests
+            .get(tc, i)
+            .and_then(|r| v8::Local::<v8::ModuleRequest>::try_from(r).ok())
+        else {
+            continue;
+        };
+        imports.push(request.get_specifier().to_rust_string_lossy(tc));
+    }
+    Ok(Ok(imports))
+}
+
+/// Reads local modules from disk, anything else can't be hashed without fetching it
+fn read_source(specifier: &ModuleSpecifier) -> PyResult<Option<String>> {
+    if specifier.scheme() != "file" {
+        return Ok(None);
+    }
+    let Ok(path) = specifier.to_file_path() else {
+        return Err(PyFileNotFoundError::new_err(specifier.to_string()));
+    };
+    match std::fs::read_to_string(&path) {
+        Ok(source) => Ok(Some(source)),
+        Err(e) => Err(PyFileNotFoundError::new_err(format!(
+            "{}: {e}",
+            path.display()
+        ))),
+    }
+}
+
+/// Resolves every module `entry` depends on, directly or not, into a dict of
+/// resolved specifiers to the sha256 of their source and what they import.
+/// Modules that aren't on disk are listed with a hash of `None` and not followed.
+pub fn resolve_graph(runtime: &mut Runtime, entry: &Module) -> PyResult<Py<PyDict>> {
+    let root = match resolve_path(&entry.filename().to_string_lossy(), runtime.current_dir()) {
+        Ok(root) => root,
+        Err(e) => return Err(PyNotADirectoryError::new_err(e.to_string())),
+    };
+    let mut queue = VecDeque::from([(root, Some(entry.contents().to_string()))]);
+    Python::with_gil(|py| {
+        let graph = PyDict::new(py);
+        while let Some((specifier, source)) = queue.pop_front() {
+            if graph.contains(specifier.as_str())? {
+                continue;
+            }
+            let node = PyDict::new(py);
+            let mut imports = Vec::new();
+            match source {
+                Some(source) => {
+                    let digest = Sha256::digest(source.as_bytes());
+                    node.set_item("sha256", format!("{digest:x}"))?;
+                    let code = transpile(&specifier, &source).map_err(PyValueError::new_err)?;
+                    let scope = &mut runtime.deno_runtime().handle_scope();
+                    let requests = module_imports(scope, &specifier, &code)?
+                        .map_err(|e| PyValueError::new_err(format!("{specifier}: {e}")))?;
+                    for request in requests {
+                        let resolved = match resolve_import(&request, specifier.as_str()) {
+                            Ok(resolved) => resolved,
+                            Err(e) => return Err(PyValueError::new_err(e.to_string())),
+                        };
+                        imports.push(resolved.to_string());
+                        let source = read_source(&resolved)?;
+                        queue.push_back((resolved, source));
+                    }
+                }
+                None => node.set_item("sha256", py.None())?,
+            }
+            node.set_item("imports", imports)?;
+            graph.set_item(specifier.as_str(), node)?;
+        }
+        Ok(graph.unbind())
+    })
+}
@@ -0,0 +1,162 @@
+//! Web style import maps, bare specifiers such as `'react'` are rewritten to the
+//! url they map to before a module is compiled since the runtime's resolver
+//! turns them away before any import provider gets to see them.
+use std::{ops::Range, path::Path};
+
+use deno_ast::{swc::parser::token::Token, MediaType, TokenOrComment};
+use pyo3::{
+    exceptions::PyValueError,
+    prelude::*,
+    types::{PyDict, PyString},
+};
+use rustyscript::deno_core::{resolve_path, ModuleSpecifier};
+
+#[derive(Debug, Clone, Default)]
+pub struct ImportMap {
+    /// Specifiers and what they map to, longest first so the most specific
+    /// prefix wins
+    imports: Vec<(String, String)>,
+}
+
+/// Resolves the address an entry maps to, relative paths are taken from `base`
+fn address(specifier: &str, target: &str, base: &Path) -> PyResult<String> {
+    let invalid = |reason: &str| {
+        PyValueError::new_err(format!(
+            "import_map entry {specifier:?}: {target:?} {reason}"
+        ))
+    };
+    let resolved =
+        if target.starts_with("./") || target.starts_with("../") || target.starts_with('/') {
+            let mut url = resolve_path(target, base)
+                .map_err(|e| invalid(&e.to_string()))?
+                .to_string();
+            // Normalizing the path drops the slash prefixes are matched with
+            if target.ends_with('/') && !url.ends_with('/') {
+                url.push('/');
+            }
+            url
+        } else {
+            match ModuleSpecifier::parse(target) {
+                Ok(url) => url.to_string(),
+                Err(_) => return Err(invalid("is not a url or a path starting with ./, ../ or /")),
+            }
+        };
+    if specifier.ends_with('/') && !resolved.ends_with('/') {
+        return Err(invalid("has to end with / like the specifier it maps"));
+    }
+    Ok(resolved)
+}
+
+impl ImportMap {
+    /// Reads either a whole import map with an `imports` key, the way
+    /// `importmap.json` files are written, or just the mapping itself
+    pub fn from_dict(map: &Bound<'_, PyDict>, base: &Path) -> PyResult<Self> {
+        if map.contains("scopes")? {
+            return Err(PyValueError::new_err("import_map scopes aren't supported"));
+        }
+        let imports = match map.get_item("imports")? {
+            Some(imports) => imports
+                .downcast_into::<PyDict>()
+                .map_err(|_| PyValueError::new_err("import_map imports has to be a dict"))?,
+            None => map.clone(),
+        };
+        let mut entries = Vec::with_capacity(imports.len());
+        for (specifier, target) in imports.iter() {
+            let (Ok(specifier), Ok(target)) = (
+                specifier.downcast::<PyString>(),
+                target.downcast::<PyString>(),
+            ) else {
+                return Err(PyValueError::new_err(format!(
+                    "import_map entries have to map strings to strings, got {specifier}: {target}"
+                )));
+            };
+            let specifier = specifier.to_str()?;
+            if specifier.is_empty() {
+                return Err(PyValueError::new_err("import_map has an empty specifier"));
+            }
+            let target = address(specifier, target.to_str()?, base)?;
+            entries.push((specifier.to_string(), target));
+        }
+        entries.sort_by_key(|(specifier, _)| std::cmp::Reverse(specifier.len()));
+        Ok(Self { imports: entries })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.imports.is_empty()
+    }
+
+    /// The url `specifier` maps to, if any entry covers it
+    pub fn lookup(&self, specifier: &str) -> Option<String> {
+        self.imports.iter().find_map(|(key, target)| {
+            if key == specifier {
+                Some(target.clone())
+            } else if key.ends_with('/') {
+                let rest = specifier.strip_prefix(key.as_str())?;
+                Some(format!("{target}{rest}"))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Whether `url` is something the map points at, those are let through the
+    /// loader without being loaded first
+    pub fn allows(&self, url: &ModuleSpecifier) -> bool {
+        let url = url.as_str();
+        self.imports.iter().any(|(key, target)| {
+            target == url || (key.ends_with('/') && url.starts_with(target.as_str()))
+        })
+    }
+
+    /// Rewrites the specifiers the map covers, see [`rewrite_specifiers`]
+    pub fn rewrite(&self, code: &str, media_type: MediaType) -> Option<String> {
+        if self.is_empty() {
+            return None;
+        }
+        rewrite_specifiers(code, media_type, |specifier| self.lookup(specifier))
+    }
+}
+
+/// The string literal specifiers of static imports, re-exports and `import()`
+/// calls in `code` along with where they are
+pub fn specifiers(code: &str, media_type: MediaType) -> Vec<(Range<usize>, String)> {
+    let tokens: Vec<_> = deno_ast::lex(code, media_type)
+        .into_iter()
+        .filter(|item| matches!(item.inner, TokenOrComment::Token(_)))
+        .collect();
+    let text = |i: usize| &code[tokens[i].range.clone()];
+    let mut found = Vec::new();
+    for (i, item) in tokens.iter().enumerate() {
+        let TokenOrComment::Token(Token::Str { value, .. }) = &item.inner else {
+            continue;
+        };
+        let is_specifier = i > 0
+            && (matches!(text(i - 1), "from" | "import")
+                || (text(i - 1) == "(" && i > 1 && text(i - 2) == "import"));
+        if is_specifier {
+            found.push((item.range.clone(), value.to_string()));
+        }
+    }
+    found
+}
+
+/// Rewrites the [`specifiers`] that `lookup` has a replacement for
+pub fn rewrite_specifiers(
+    code: &str,
+    media_type: MediaType,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Option<String> {
+    let replacements: Vec<_> = specifiers(code, media_type)
+        .into_iter()
+        .filter_map(|(range, specifier)| Some((range, lookup(&specifier)?)))
+        .collect();
+    if replacements.is_empty() {
+        return None;
+    }
+    let mut rewritten = code.to_string();
+    for (range, target) in replacements.into_iter().rev() {
+        let quoted = serde_json::to_string(&target).unwrap_or(target);
+        rewritten.replace_range(range, &quoted);
+    }
+    Some(rewritten)
+}
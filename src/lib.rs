@@ -1,23 +1,80 @@
-use std::{ffi::OsStr, fs::read_dir, path::Path, task::Poll, time::Duration};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    ffi::{CString, OsStr},
+    fs::{read_dir, File},
+    io::BufReader,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{self, RecvTimeoutError},
+        Arc, Mutex, OnceLock, Weak,
+    },
+    task::Poll,
+    time::{Duration, Instant},
+};
 
 use pyo3::create_exception;
 use pyo3::exceptions::PyException;
 use pyo3::{
     exceptions::{
-        PyFileNotFoundError, PyKeyError, PyNotADirectoryError, PyRuntimeError, PyValueError,
+        asyncio::CancelledError, PyAssertionError, PyFileNotFoundError, PyKeyError,
+        PyNotADirectoryError, PyRuntimeError, PyRuntimeWarning, PyTimeoutError, PyTypeError,
+        PyValueError,
     },
     prelude::*,
-    types::PyTuple,
+    types::{PyBool, PyBytes, PyDict, PyTuple, PyType},
 };
-use rustyscript::js_value::Promise;
+use rustyscript::js_value::{Function, Promise, Value};
 use rustyscript::{
-    deno_core::PollEventLoopOptions, Error as RSError, Module, ModuleHandle, Runtime,
-    RuntimeOptions,
+    deno_core::{
+        error::JsError, resolve_import, resolve_path, v8, ModuleSpecifier, PollEventLoopOptions,
+    },
+    Error as RSError, Module, ModuleHandle, RsAsyncFunction, RsFunction, Runtime, RuntimeOptions,
+    Undefined,
 };
 
+use deno_ast::MediaType;
 use serde_pyobject::{from_pyobject, to_pyobject};
+use sha2::{Digest, Sha256};
+pub mod async_function;
+pub mod blob;
+pub mod cancel;
+pub mod console;
+pub mod convert;
+pub mod fs;
+pub mod graph;
+pub mod import_map;
+pub mod loader;
+pub mod locale;
 pub mod locking;
-use locking::GIL;
+pub mod module_cache;
+pub mod packages;
+pub mod pool;
+#[cfg(feature = "profiler")]
+pub mod profile;
+pub mod proxy;
+pub mod realm;
+pub mod result;
+mod scripts;
+pub mod timing;
+pub mod transpile;
+use async_function::AsyncFunction;
+use blob::BlobFormat;
+use cancel::CancelToken;
+use console::{ConsoleBroadcast, ConsoleErrors, ConsoleStream};
+use convert::{
+    python_to_argument, value_to_bytes, value_to_json, value_to_python, ArgOptions, Argument,
+    ConvertOptions, JsBuffer, JsRegExp, JsSymbol, ResultCache,
+};
+use fs::Mounts;
+use import_map::ImportMap;
+use locking::{Detached, Guard, Released, GIL};
+use packages::Packages;
+use proxy::JsObjectProxy;
+use realm::Realm;
+use result::JsResult;
+use timing::Timing;
 
 create_exception!(
     pyrv8,
@@ -26,40 +83,383 @@ create_exception!(
     "The operation is not allowed in this state."
 );
 
+create_exception!(
+    pyrv8,
+    TimeoutError,
+    PyTimeoutError,
+    "The runtime's timeout ran out before the javascript finished."
+);
+
+create_exception!(
+    pyrv8,
+    ModuleResolutionError,
+    PyRuntimeError,
+    "An import could not be resolved, `specifier` and `referrer` say which import in which module."
+);
+
+create_exception!(
+    pyrv8,
+    JsConsoleError,
+    PyException,
+    "Javascript wrote to console.error in a context made with console_error_raises."
+);
+
 #[pyclass]
 struct Context {
     runtime: GIL<Runtime>,
+    config: RuntimeConfig,
+    convert: ConvertOptions,
+    args: ArgOptions,
+    /// Kept around so they can be replayed onto a recycled runtime
+    functions: HashMap<String, Py<PyAny>>,
+    async_functions: HashMap<String, AsyncFunction>,
+    /// Promises hold a weak reference to this so they can tell when the runtime
+    /// that made them is gone, a new one is made whenever the runtime is recycled.
+    alive: Arc<()>,
+    /// Replaced by `abort_all` so the promises made before it know to give up
+    epoch: Arc<()>,
+    /// Every timer up to this id has already been cancelled by `abort_all`
+    timers_cleared: u64,
+    /// Sources longer than this are turned away before v8 ever sees them
+    max_source_bytes: Option<usize>,
+    /// How long methods wait on another thread holding the runtime before giving up
+    lock_timeout: Option<Duration>,
+    rejection_handler: Option<Py<PyAny>>,
+    /// Only installed into the runtime once a stream is asked for
+    console: Option<ConsoleBroadcast>,
+    /// Set with `console_error_raises`
+    console_errors: Option<ConsoleErrors>,
+    /// Memory behind the buffers from `create_shared_buffer`, bound again
+    /// after a recycle so their contents carry over
+    shared_buffers: GIL<HashMap<String, v8::SharedRef<v8::BackingStore>>>,
+    /// The `fs` global is only defined once something has been mounted
+    mounts: Mounts,
+    /// Handles of the modules `module_dir` loaded, in the order they were loaded
+    modules: Vec<Py<JsHandle>>,
+    /// Code `close` runs before the context goes away, with how long it gets
+    exit_handler: Option<(String, Duration)>,
+    closed: bool,
+    /// Put in front of the errors this context raises so they can be told apart
+    name: String,
+    /// Where `Date` is frozen at in milliseconds since the epoch, if it is
+    mock_time: Option<f64>,
+    /// How much of a thrown error goes into the message, see `named_js_error`
+    verbose_errors: Option<bool>,
+    /// Every module loaded so far by its resolved specifier with the hash of its
+    /// source, loading the same source again hands back its handle instead of
+    /// evaluating it twice
+    loaded: HashMap<String, (String, Py<JsHandle>)>,
 }
 
-/// Used multiple times throughout the code this is used to get rid of the annoyance
-/// of attaching the gil or detaching it when done.
-#[inline]
-pub fn serde_to_python(value: serde_json::Value) -> PyResult<Py<PyAny>> {
-    Python::with_gil(|py| match to_pyobject(py, &value) {
-        Ok(res) => Ok(res.unbind()),
-        Err(e) => Err(PyRuntimeError::new_err(e.to_string())),
-    })
+/// The options a `Context` was made with, kept so the runtime can be rebuilt
+#[derive(Clone, Default)]
+pub struct RuntimeConfig {
+    pub timeout: Option<Duration>,
+    pub max_heap_size: Option<usize>,
+    pub stack_trace_limit: Option<u32>,
+    /// Run async ops on the tokio runtime shared between contexts
+    pub shared_pool: bool,
+    /// Reuse code caches of imported modules other contexts already compiled
+    pub shared_module_cache: bool,
+    /// Leave nothing but the ECMAScript builtins in the global scope
+    pub minimal: bool,
+    /// Seed for a deterministic `Math.random`
+    pub random_seed: Option<u64>,
+    /// Locale the `Intl` apis use when none is given
+    pub default_locale: Option<String>,
+    /// Called with every module specifier as it's loaded
+    pub load_hook: loader::LoadHook,
+    /// Where the bare specifiers of imports point
+    pub import_map: Arc<ImportMap>,
+    /// Export conditions bare specifiers naming packages are resolved with,
+    /// `None` leaves `node_modules` alone
+    pub packages: Option<Arc<Packages>>,
+    /// Only the web globals with these names are kept, `None` keeps them all
+    pub web_apis: Option<Vec<String>>,
+    /// How many async ops can be in flight at once
+    pub max_concurrent_ops: Option<usize>,
+    /// Variables for `process.env`, and whether scripts get to change them
+    pub env: Option<(serde_json::Map<String, serde_json::Value>, bool)>,
+    /// Write uncaught async errors to `sys.stderr` instead of failing the event loop
+    pub log_errors: bool,
+    /// Modules larger than this many bytes fail to import
+    pub max_source_bytes: Option<usize>,
+    /// Kept across `recycle` so an error from before it can still be taken
+    pub last_error: LastError,
+}
+
+/// The most recent error the event loop ran into, shared with the callbacks
+/// the runtime hands errors nothing caught to
+#[derive(Clone, Default)]
+pub struct LastError(Arc<Mutex<Option<PyErr>>>);
+
+impl LastError {
+    pub fn set(&self, error: &PyErr) {
+        let error = Python::with_gil(|py| error.clone_ref(py));
+        if let Ok(mut last) = self.0.lock() {
+            *last = Some(error);
+        }
+    }
+
+    pub fn take(&self) -> Option<PyErr> {
+        self.0.lock().ok().and_then(|mut last| last.take())
+    }
 }
 
 /// Shortcut for creating runtime variables
 #[inline]
-pub fn create_runtime(
-    timeout: Option<f64>,
-    max_heap_size: Option<usize>,
-) -> PyResult<GIL<Runtime>> {
+pub fn create_runtime(config: &RuntimeConfig) -> PyResult<Runtime> {
     let mut options = RuntimeOptions::default();
-    if let Some(timeout) = timeout {
-        options.timeout = Duration::from_secs_f64(timeout);
+    if let Some(timeout) = config.timeout {
+        options.timeout = timeout;
+    }
+    options.max_heap_size = config.max_heap_size;
+    options.import_provider = Some(Box::new(loader::Loader::new(
+        config.shared_module_cache,
+        config.load_hook.clone(),
+        config.import_map.clone(),
+        config.packages.clone(),
+        config.max_source_bytes,
+    )));
+    let build = || {
+        let runtime = if config.shared_pool {
+            Runtime::with_tokio_runtime_handle(options, pool::shared_handle()?)
+        } else {
+            Runtime::with_tokio_runtime(options, pool::owned()?)
+        };
+        runtime.map_err(|e| PyRuntimeError::new_err(e.to_string()))
+    };
+    let mut runtime = match &config.default_locale {
+        Some(locale) => locale::with_default_locale(locale, build)?,
+        None => build()?,
+    };
+    runtime
+        .deno_runtime()
+        .v8_isolate()
+        .set_slot(Released::default());
+    if let Err(e) = runtime.eval::<Undefined>(scripts::INSTALL_RAISED) {
+        return Err(PyRuntimeError::new_err(e.to_string()));
+    }
+    if let Some(limit) = config.stack_trace_limit
+        && let Err(e) = runtime.eval::<Undefined>(format!("Error.stackTraceLimit = {limit}"))
+    {
+        return Err(PyRuntimeError::new_err(e.to_string()));
+    }
+    if let Some(seed) = config.random_seed {
+        let code = format!("{}({}, {})", scripts::SEED_RANDOM, seed as u32, seed >> 32);
+        if let Err(e) = runtime.eval::<Undefined>(code) {
+            return Err(PyRuntimeError::new_err(e.to_string()));
+        }
+    }
+    if config.minimal
+        && let Err(e) = runtime.eval::<Undefined>(scripts::MINIMAL_GLOBALS)
+    {
+        return Err(PyRuntimeError::new_err(e.to_string()));
+    }
+    if let Some(web_apis) = &config.web_apis {
+        let removed: Vec<_> = scripts::WEB_API_GLOBALS
+            .iter()
+            .filter(|(api, _)| !web_apis.iter().any(|name| name == api))
+            .flat_map(|(_, globals)| globals.iter())
+            .collect();
+        let removed =
+            serde_json::to_string(&removed).map_err(|e| PyValueError::new_err(e.to_string()))?;
+        if let Err(e) = runtime.eval::<Undefined>(format!("{}({removed})", scripts::REMOVE_GLOBALS))
+        {
+            return Err(PyRuntimeError::new_err(e.to_string()));
+        }
+    }
+    if let Some((vars, writable)) = &config.env {
+        let vars = serde_json::to_string(vars).map_err(|e| PyValueError::new_err(e.to_string()))?;
+        let code = format!("{}({vars}, {writable})", scripts::INSTALL_ENV);
+        if let Err(e) = runtime.eval::<Undefined>(code) {
+            return Err(PyRuntimeError::new_err(e.to_string()));
+        }
+    }
+    if let Some(limit) = config.max_concurrent_ops
+        && let Err(e) = runtime.eval::<Undefined>(format!("{}({limit})", scripts::LIMIT_OPS))
+    {
+        return Err(PyRuntimeError::new_err(e.to_string()));
+    }
+    if config.log_errors {
+        install_error_log(&mut runtime, config.last_error.clone())?;
+    }
+    Ok(runtime)
+}
+
+/// The timeout given as either seconds or whole milliseconds, milliseconds skip
+/// the float rounding that seconds have near the microsecond range
+fn either_timeout(timeout: Option<f64>, timeout_ms: Option<u64>) -> PyResult<Option<Duration>> {
+    match (timeout, timeout_ms) {
+        (Some(_), Some(_)) => Err(PyValueError::new_err(
+            "timeout and timeout_ms can't be used together",
+        )),
+        (Some(secs), None) => match Duration::try_from_secs_f64(secs) {
+            Ok(timeout) => Ok(Some(timeout)),
+            Err(e) => Err(PyValueError::new_err(format!("timeout: {e}"))),
+        },
+        (None, Some(ms)) => Ok(Some(Duration::from_millis(ms))),
+        (None, None) => Ok(None),
+    }
+}
+
+/// Binds `globalThis[name]` to a `SharedArrayBuffer` over `store`
+fn bind_shared_buffer(
+    runtime: &mut Runtime,
+    name: &str,
+    store: &v8::SharedRef<v8::BackingStore>,
+) -> PyResult<()> {
+    let scope = &mut runtime.deno_runtime().handle_scope();
+    let Some(key) = v8::String::new(scope, name) else {
+        return Err(PyValueError::new_err("name could not be encoded"));
+    };
+    let buffer = v8::SharedArrayBuffer::with_backing_store(scope, store);
+    let global = scope.get_current_context().global(scope);
+    let tc = &mut v8::TryCatch::new(scope);
+    match global.set(tc, key.into(), buffer.into()) {
+        Some(_) => Ok(()),
+        None => Err(caught_exception(tc)),
+    }
+}
+
+/// Freezes `Date` at `now`, or puts the real one back for `None`
+fn install_mock_time(runtime: &mut Runtime, now: Option<f64>) -> PyResult<()> {
+    let now = serde_json::to_string(&now).map_err(|e| PyValueError::new_err(e.to_string()))?;
+    match runtime.eval::<Undefined>(format!("{}({now})", scripts::MOCK_TIME)) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(PyRuntimeError::new_err(e.to_string())),
+    }
+}
+
+/// Registers python callables with the runtime and binds them to globals
+pub fn install_functions(runtime: &mut Runtime, funcs: &[(String, Py<PyAny>)]) -> PyResult<()> {
+    let mut names = Vec::with_capacity(funcs.len());
+    for (name, func) in funcs {
+        let func = Python::with_gil(|py| func.clone_ref(py));
+        if let Err(e) = runtime.register_function(name, python_function(func)) {
+            return Err(PyRuntimeError::new_err(e.to_string()));
+        }
+        names.push(name);
+    }
+    let names = serde_json::to_string(&names).map_err(|e| PyValueError::new_err(e.to_string()))?;
+    match runtime.eval::<Undefined>(format!("{}({names})", scripts::BIND_FUNCTIONS)) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(PyRuntimeError::new_err(e.to_string())),
+    }
+}
+
+/// Takes a function added by [`install_functions`] or [`async_function::install`]
+/// out of the runtime along with its global
+pub fn remove_function(runtime: &mut Runtime, name: &str) -> PyResult<()> {
+    {
+        let state = runtime.deno_runtime().op_state();
+        let mut state = match state.try_borrow_mut() {
+            Ok(state) => state,
+            Err(e) => return Err(PyRuntimeError::new_err(e.to_string())),
+        };
+        if let Some(functions) = state.try_borrow_mut::<HashMap<String, Box<dyn RsFunction>>>() {
+            functions.remove(name);
+        }
+        if let Some(functions) = state.try_borrow_mut::<HashMap<String, Box<dyn RsAsyncFunction>>>()
+        {
+            functions.remove(name);
+        }
+    }
+    let name = serde_json::to_string(name).map_err(|e| PyValueError::new_err(e.to_string()))?;
+    match runtime.eval::<Undefined>(format!("{}({name})", scripts::UNBIND_FUNCTION)) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(PyRuntimeError::new_err(e.to_string())),
+    }
+}
+
+/// Hands unhandled promise rejections over to a python callable, each one is
+/// kept as the last error too
+pub fn install_rejection_handler(
+    runtime: &mut Runtime,
+    func: Py<PyAny>,
+    last_error: LastError,
+) -> PyResult<()> {
+    let handler = python_function(func);
+    let handle = move |args: &[serde_json::Value]| {
+        let reason = match args.first() {
+            Some(serde_json::Value::String(reason)) => reason.clone(),
+            Some(other) => other.to_string(),
+            None => String::new(),
+        };
+        last_error.set(&PyRuntimeError::new_err(format!(
+            "Uncaught (in promise) {reason}"
+        )));
+        handler(args)
+    };
+    if let Err(e) = runtime.register_function(scripts::REJECTION_HANDLER, handle) {
+        return Err(PyRuntimeError::new_err(e.to_string()));
     }
-    options.max_heap_size = max_heap_size;
-    match Runtime::new(options) {
-        Ok(runtime) => Ok(GIL::new(runtime)),
+    match runtime.eval::<Undefined>(scripts::INSTALL_REJECTION_HANDLER) {
+        Ok(_) => Ok(()),
         Err(e) => Err(PyRuntimeError::new_err(e.to_string())),
     }
 }
 
+/// Writes `message` to `sys.stderr` as a line of its own
+fn write_stderr(message: &str) -> Result<serde_json::Value, RSError> {
+    Python::with_gil(|py| {
+        let stderr = py.import("sys")?.getattr("stderr")?;
+        stderr.call_method1("write", (format!("{message}\n"),))?;
+        stderr.call_method0("flush").map(|_| ())
+    })
+    .map_err(|e| RSError::Runtime(e.to_string()))?;
+    Ok(serde_json::Value::Null)
+}
+
+/// Logs uncaught async errors and unhandled rejections to `sys.stderr` and
+/// keeps them as the last error, a rejection handler installed afterwards
+/// takes over the rejections
+pub fn install_error_log(runtime: &mut Runtime, last_error: LastError) -> PyResult<()> {
+    let log = move |args: &[serde_json::Value]| {
+        let message = match args.first() {
+            Some(serde_json::Value::String(message)) => message.clone(),
+            Some(other) => other.to_string(),
+            None => String::new(),
+        };
+        last_error.set(&PyRuntimeError::new_err(message.clone()));
+        write_stderr(&message)
+    };
+    if let Err(e) = runtime.register_function(scripts::ERROR_LOG, log) {
+        return Err(PyRuntimeError::new_err(e.to_string()));
+    }
+    match runtime.eval::<Undefined>(scripts::INSTALL_ERROR_LOG) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(PyRuntimeError::new_err(e.to_string())),
+    }
+}
+
+/// Describes whatever a `TryCatch` caught
+fn exception_message(tc: &mut v8::TryCatch<'_, v8::HandleScope<'_>>) -> String {
+    match tc.exception() {
+        Some(e) => e.to_rust_string_lossy(tc),
+        None => "execution was terminated".to_string(),
+    }
+}
+
+/// Turns whatever a `TryCatch` caught into a `RuntimeError`
+pub fn caught_exception(tc: &mut v8::TryCatch<'_, v8::HandleScope<'_>>) -> PyErr {
+    PyRuntimeError::new_err(exception_message(tc))
+}
+
+/// Formats a caught compile error along with the line it happened on
+fn compile_error(tc: &mut v8::TryCatch<'_, v8::HandleScope<'_>>) -> String {
+    let error = exception_message(tc);
+    match tc.message().and_then(|m| m.get_line_number(tc)) {
+        Some(line) => format!("{error} (line {line})"),
+        None => error,
+    }
+}
+
 #[inline]
-pub fn python_args_to_serde(py_args: &Bound<'_, PyTuple>) -> PyResult<Vec<serde_json::Value>> {
+pub fn python_args_to_serde(
+    py_args: &Bound<'_, PyTuple>,
+    options: &ArgOptions,
+) -> PyResult<Vec<Argument>> {
     if py_args.len() < 1 {
         return Ok(Vec::new());
     }
@@ -67,15 +467,272 @@ pub fn python_args_to_serde(py_args: &Bound<'_, PyTuple>) -> PyResult<Vec<serde_
 
     // My Logic on rust may not be as clean as someone else's
     // if you think you can do better than me, make me a pull request - Vizonex
-    for a in py_args.iter().map(|a| from_pyobject(a)) {
+    for a in py_args.iter().map(|a| python_to_argument(&a, options)) {
         match a {
             Ok(r) => {
                 s.push(r);
             }
-            Err(e) => return Err(PyValueError::new_err(e.to_string())),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(s)
+}
+
+/// The global `eval_stream` gives code to emit chunks with
+const EMIT: &str = "__emit";
+
+/// Numbers the contexts made without a name
+static CONTEXT_IDS: AtomicU64 = AtomicU64::new(1);
+
+/// Numbers the exceptions registered functions raise
+static RAISED_IDS: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    /// The exceptions registered functions raised on this thread during the
+    /// current call, each with the token javascript keeps on the error for it
+    static RAISED: RefCell<Vec<(String, PyErr)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Hands javascript the message of an exception a registered function raised,
+/// keeping the exception itself so `js_error` can re-raise it if nothing catches it
+pub fn raised(error: PyErr) -> RSError {
+    let token = format!(
+        "[python exception {}]",
+        RAISED_IDS.fetch_add(1, Ordering::Relaxed)
+    );
+    let message = format!("{error} {token}");
+    RAISED.with(|raised| raised.borrow_mut().push((token, error)));
+    RSError::Runtime(message)
+}
+
+impl GIL<Runtime> {
+    /// Locks a context's runtime for a call, the exceptions registered functions
+    /// raised during earlier calls are let go first
+    #[track_caller]
+    pub fn lock(&self, timeout: Option<Duration>) -> PyResult<Guard<'_, Runtime>> {
+        RAISED.with(|raised| raised.borrow_mut().clear());
+        let mut rt = self.get_timeout(timeout)?;
+        released(&mut rt).drain();
+        Ok(rt)
+    }
+}
+
+/// Runs `run` against the runtime for at most `limit`, sync code never yields to
+/// the timer so the limit is also kept from another thread. Running out of heap
+/// ends it too, the way it does the runtime's own calls.
+pub(crate) fn bounded<T>(
+    rt: &mut Runtime,
+    limit: Duration,
+    name: &str,
+    run: impl AsyncFnOnce(&mut Runtime) -> Result<T, RSError>,
+) -> Result<T, RSError> {
+    let isolate = rt.deno_runtime().v8_isolate().thread_safe_handle();
+    let (done, finished) = mpsc::channel::<()>();
+    let watchdog = std::thread::spawn(move || {
+        let expired = matches!(finished.recv_timeout(limit), Err(RecvTimeoutError::Timeout));
+        if expired {
+            isolate.terminate_execution();
+        }
+        expired
+    });
+    let tokio = rt.tokio_runtime();
+    let heap_exhausted = rt.heap_exhausted_token();
+    let result = tokio.block_on(async {
+        rustyscript::tokio::select! {
+            result = rustyscript::tokio::time::timeout(limit, run(&mut *rt)) => {
+                result.unwrap_or_else(|_| Err(RSError::Timeout(String::new())))
+            }
+            () = heap_exhausted.cancelled() => Err(RSError::HeapExhausted),
+        }
+    });
+    drop(done);
+    let expired = watchdog.join().unwrap_or(false);
+    // Leaving the isolate terminated would break whatever runs next
+    rt.deno_runtime().v8_isolate().cancel_terminate_execution();
+    match result {
+        Err(e) if expired || matches!(e, RSError::Timeout(_)) => Err(RSError::Timeout(format!(
+            "{name} timed out after {}s",
+            limit.as_secs_f64()
+        ))),
+        result => result,
+    }
+}
+
+/// Where values held from outside of the runtime go once they're dropped
+pub(crate) fn released(rt: &mut Runtime) -> Released {
+    let isolate = rt.deno_runtime().v8_isolate();
+    isolate.get_slot::<Released>().cloned().unwrap_or_default()
+}
+
+/// The exception whose token `error` carries, an error only carries one when
+/// it's the exception's own
+fn take_raised(error: &RSError) -> Option<PyErr> {
+    let carried = match error {
+        RSError::JsError(e) => e
+            .additional_properties
+            .iter()
+            .find(|(key, _)| key == scripts::RAISED_PROPERTY)
+            .map(|(_, token)| token.clone()),
+        _ => None,
+    };
+    let message = carried.unwrap_or_else(|| error.to_string());
+    RAISED.with(|raised| {
+        let mut raised = raised.borrow_mut();
+        let i = raised
+            .iter()
+            .position(|(token, _)| message.contains(token.as_str()))?;
+        Some(raised.swap_remove(i).1)
+    })
+}
+
+/// Wraps a python callable so javascript can call into it through rustyscript's
+/// registered functions, arguments and results are carried over through serde.
+pub fn python_function(
+    func: Py<PyAny>,
+) -> impl Fn(&[serde_json::Value]) -> Result<serde_json::Value, RSError> {
+    move |args: &[serde_json::Value]| {
+        Python::with_gil(|py| {
+            let mut py_args = Vec::with_capacity(args.len());
+            for a in args {
+                match to_pyobject(py, a) {
+                    Ok(r) => py_args.push(r),
+                    Err(e) => return Err(RSError::Runtime(e.to_string())),
+                }
+            }
+            let py_args = PyTuple::new(py, py_args).map_err(|e| RSError::Runtime(e.to_string()))?;
+            let result = func.bind(py).call1(py_args).map_err(raised)?;
+            from_pyobject(result).map_err(|e| RSError::Runtime(e.to_string()))
+        })
+    }
+}
+
+/// Turns a rustyscript error into the python exception for it, timeouts get
+/// their own `TimeoutError` so callers can tell them apart from thrown errors.
+/// An exception raised by a registered function that nothing caught comes back
+/// as itself, traceback and all.
+pub fn js_error(error: RSError) -> PyErr {
+    named_js_error(error, None, None)
+}
+
+/// `js_error` with the name of the context it came from in front of the message.
+/// `verbose` picks between just the exception message and everything v8 knows
+/// about it, `None` keeps rustyscript's own formatting.
+fn named_js_error(error: RSError, name: Option<&str>, verbose: Option<bool>) -> PyErr {
+    if !matches!(error, RSError::Timeout(_))
+        && let Some(exception) = take_raised(&error)
+    {
+        return exception;
+    }
+    let prefix = name.map(|name| format!("[{name}] ")).unwrap_or_default();
+    let message = match (error, verbose) {
+        (RSError::Timeout(message), _) => {
+            return TimeoutError::new_err(format!("{prefix}{message}"));
+        }
+        (RSError::JsError(e), Some(verbose)) => describe_js_error(&e, verbose),
+        (error, _) => error.to_string(),
+    };
+    let Some((specifier, referrer)) = failed_import(&message) else {
+        return PyRuntimeError::new_err(format!("{prefix}{message}"));
+    };
+    Python::with_gil(|py| {
+        let error = ModuleResolutionError::new_err(format!("{prefix}{message}"));
+        let value = error.value(py);
+        if let Err(e) = value
+            .setattr("specifier", specifier)
+            .and_then(|_| value.setattr("referrer", referrer))
+        {
+            return e;
+        }
+        error
+    })
+}
+
+/// How the messages of imports that failed to resolve start, each is followed
+/// by the quoted specifier and possibly ` from ` and the quoted referrer
+const FAILED_IMPORTS: &[&str] = &[
+    "Cannot resolve module ",
+    "Relative import path ",
+    "Module not found ",
+];
+
+/// The specifier and referrer of the import an error `message` is about
+fn failed_import(message: &str) -> Option<(String, Option<String>)> {
+    fn quoted(text: &str) -> Option<(&str, &str)> {
+        let text = text.strip_prefix('"')?;
+        let end = text.find('"')?;
+        Some((&text[..end], &text[end + 1..]))
+    }
+    let rest = FAILED_IMPORTS
+        .iter()
+        .find_map(|start| message.find(start).map(|i| &message[i + start.len()..]))?;
+    let rest = rest.lines().next().unwrap_or(rest);
+    let (specifier, rest) = quoted(rest)?;
+    let referrer = rest
+        .find(" from ")
+        .and_then(|i| quoted(&rest[i + " from ".len()..]))
+        .map(|(referrer, _)| referrer.to_string());
+    Some((specifier.to_string(), referrer))
+}
+
+/// The message of a thrown error, verbosely followed by the line it was thrown
+/// from, its stack and the stacks of every error in its `cause` chain
+fn describe_js_error(error: &JsError, verbose: bool) -> String {
+    if !verbose {
+        return error.exception_message.clone();
+    }
+    let mut lines = vec![error.exception_message.clone()];
+    if let Some(source) = &error.source_line {
+        lines.push(format!("    {}", source.trim()));
+    }
+    if let Some(stack) = &error.stack {
+        lines.push(stack.clone());
+    }
+    let mut cause = error.cause.as_deref();
+    while let Some(error) = cause {
+        let stack = error.stack.as_deref().unwrap_or(&error.exception_message);
+        lines.push(format!("Caused by: {stack}"));
+        cause = error.cause.as_deref();
+    }
+    lines.join("\n")
+}
+
+/// Errors that are worth trying again, the ones scripts throw when something
+/// they race with isn't ready yet. Timeouts, anything the compiler rejected and
+/// exceptions python functions raised fail the same way every time.
+fn is_transient(error: &RSError) -> bool {
+    let RSError::JsError(e) = error else {
+        return false;
+    };
+    let raised = e
+        .additional_properties
+        .iter()
+        .any(|(key, _)| key == scripts::RAISED_PROPERTY);
+    !raised
+        && matches!(
+            e.name.as_deref(),
+            Some("Error" | "TypeError" | "ReferenceError")
+        )
+}
+
+/// Re-runs `attempt` on errors `retry_if` accepts with an exponential backoff starting
+/// at 10ms, the last error is handed back once the retries are used up.
+pub fn with_retries<T>(
+    retries: Option<u32>,
+    retry_if: impl Fn(&RSError) -> bool,
+    mut attempt: impl FnMut() -> Result<T, RSError>,
+) -> Result<T, RSError> {
+    let mut remaining = retries.unwrap_or(0);
+    let mut backoff = Duration::from_millis(10);
+    loop {
+        match attempt() {
+            Err(e) if remaining > 0 && retry_if(&e) => {
+                remaining -= 1;
+                Python::with_gil(|py| py.allow_threads(|| std::thread::sleep(backoff)));
+                backoff *= 2;
+            }
+            result => return result,
         }
     }
-    return Ok(s);
 }
 
 /// Inspired by asyncio.Future
@@ -84,53 +741,93 @@ pub fn python_args_to_serde(py_args: &Bound<'_, PyTuple>) -> PyResult<Vec<serde_
 /// and can the upper version can inherit the Runtime as a parent.
 #[pyclass]
 struct JSPromise {
-    fut: GIL<Promise<serde_json::Value>>,
+    fut: GIL<Promise<Value>>,
     result: Option<PyResult<Py<PyAny>>>,
+    /// The resolved value as json, kept for `result_json`
+    json: Option<PyResult<String>>,
+    context: Weak<()>,
+    /// Gone once `Context.abort_all` gave up on the promises made before it
+    epoch: Weak<()>,
 }
 
 impl JSPromise {
     /// Private static method in rust to attach a Promise to a python
     /// class object
-    pub fn new(fut: Promise<serde_json::Value>) -> Self {
+    pub fn new(fut: Promise<Value>, alive: &Arc<()>, epoch: &Arc<()>) -> Self {
         Self {
             fut: GIL::new(fut),
             result: None,
+            json: None,
+            context: Arc::downgrade(alive),
+            epoch: Arc::downgrade(epoch),
         }
     }
-}
 
-#[pymethods]
-impl JSPromise {
-    /// Returns true if Exception was thrown or a Result came back
-    /// from walking through the eventloop
-    #[getter]
-    pub fn is_done(&self) -> bool {
-        self.result.is_some()
+    /// Whether the promise is done without polling it, which it is once
+    /// `abort_all` gave up on it. Raises if `ctx` didn't make it.
+    fn settled_without(&mut self, ctx: &Context) -> PyResult<bool> {
+        // Polling against a runtime that didn't make the promise would mean
+        // touching handles from another isolate
+        match self.context.upgrade() {
+            Some(alive) if Arc::ptr_eq(&alive, &ctx.alive) => {}
+            _ => {
+                return Err(InvalidStateError::new_err(
+                    "Promise does not belong to this context's runtime.",
+                ));
+            }
+        }
+        if self.result.is_none() && self.epoch.strong_count() == 0 {
+            self.result
+                .replace(Err(CancelledError::new_err("Promise was aborted.")));
+            return Ok(true);
+        }
+        Ok(false)
     }
 
-    // Steps a single increment into the eventloop while also checking if the value
-    // is polled. it can't run poll_promise directly since we covered runtime in a
-    // mutex to prevent pyo3 from disallowing it to exist.
-    pub fn step(&mut self, ctx: &mut Context) -> PyResult<bool> {
+    /// Polls the promise against the runtime of `ctx`, already locked as `rt`
+    fn poll(&mut self, ctx: &Context, rt: &mut Runtime) -> PyResult<bool> {
         let f = self.fut.get()?;
-        let mut rt = ctx.runtime.get()?;
-        let result: Poll<Result<serde_json::Value, RSError>> = f.poll_promise(&mut rt);
+        let result: Poll<Result<Value, RSError>> = f.poll_promise(rt);
         match result {
             Poll::Pending => Ok(false),
             Poll::Ready(r) => {
                 match r {
                     Ok(value) => {
-                        self.result.replace(Ok(serde_to_python(value)?));
+                        self.json.replace(value_to_json(rt, &value));
+                        self.result
+                            .replace(Ok(value_to_python(rt, &value, &ctx.convert)?));
                     }
                     Err(e) => {
-                        self.result
-                            .replace(Err(PyRuntimeError::new_err(e.to_string())));
+                        let error = ctx.js_error(e);
+                        ctx.config.last_error.set(&error);
+                        self.result.replace(Err(error));
                     }
                 }
                 Ok(true)
             }
         }
     }
+}
+
+#[pymethods]
+impl JSPromise {
+    /// Returns true if Exception was thrown or a Result came back
+    /// from walking through the eventloop
+    #[getter]
+    pub fn is_done(&self) -> bool {
+        self.result.is_some()
+    }
+
+    // Steps a single increment into the eventloop while also checking if the value
+    // is polled. it can't run poll_promise directly since we covered runtime in a
+    // mutex to prevent pyo3 from disallowing it to exist.
+    pub fn step(&mut self, ctx: &mut Context) -> PyResult<bool> {
+        if self.settled_without(ctx)? {
+            return Ok(true);
+        }
+        let mut rt = ctx.runtime.lock(ctx.lock_timeout)?;
+        self.poll(ctx, &mut rt)
+    }
 
     pub fn result(&self) -> PyResult<Py<PyAny>> {
         match &self.result {
@@ -142,6 +839,28 @@ impl JSPromise {
         }
     }
 
+    /// Returns false once the context (or the recycled runtime) that made
+    /// this promise is gone, at which point it can never resolve.
+    pub fn context_alive(&self) -> bool {
+        self.context.strong_count() > 0
+    }
+
+    pub fn __repr__(&self) -> String {
+        match &self.result {
+            Some(Ok(res)) => Python::with_gil(|py| {
+                let res = res
+                    .bind(py)
+                    .repr()
+                    .map(|r| r.to_string())
+                    .unwrap_or_default();
+                format!("<JSPromise finished result={res}>")
+            }),
+            Some(Err(e)) => format!("<JSPromise finished exception={e}>"),
+            None if self.context_alive() => "<JSPromise pending>".to_string(),
+            None => "<JSPromise orphaned>".to_string(),
+        }
+    }
+
     pub fn exception(&self) -> PyResult<Option<PyErr>> {
         match &self.result {
             Some(x) => match x {
@@ -151,18 +870,64 @@ impl JSPromise {
             None => Err(InvalidStateError::new_err("Exception is not set.")),
         }
     }
+
+    /// Steps the promise if it's still pending and hands back the exception it
+    /// rejected with, `None` while pending or once resolved successfully.
+    pub fn poll_exception(&mut self, ctx: &mut Context) -> PyResult<Option<PyErr>> {
+        if self.result.is_none() && !self.step(ctx)? {
+            return Ok(None);
+        }
+        self.exception()
+    }
+
+    /// Obtains the result as a json string, `undefined` comes back as `null`
+    pub fn result_json(&self) -> PyResult<String> {
+        match (&self.result, &self.json) {
+            (Some(Err(e)), _) => Python::with_gil(|py| Err(e.clone_ref(py))),
+            (_, Some(Ok(json))) => Ok(json.clone()),
+            (_, Some(Err(e))) => Python::with_gil(|py| Err(e.clone_ref(py))),
+            _ => Err(InvalidStateError::new_err("Result is not ready.")),
+        }
+    }
+
+    /// A dict describing where the promise is at for logs, the `preview` of a
+    /// resolved value is its repr cut down to `PREVIEW_CHARS` characters.
+    pub fn debug_state<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let state = PyDict::new(py);
+        state.set_item("done", self.is_done())?;
+        state.set_item("has_result", matches!(self.result, Some(Ok(_))))?;
+        state.set_item("has_exception", matches!(self.result, Some(Err(_))))?;
+        if let Some(Ok(res)) = &self.result {
+            let repr = res.bind(py).repr()?.to_string();
+            let preview = match repr.char_indices().nth(PREVIEW_CHARS) {
+                Some((end, _)) => format!("{}...", &repr[..end]),
+                None => repr,
+            };
+            state.set_item("preview", preview)?;
+        }
+        Ok(state)
+    }
 }
 
+/// How much of a resolved value `JSPromise.debug_state` shows
+const PREVIEW_CHARS: usize = 80;
+
 // /// An Already loaded version of a Js Module Handle...
 #[pyclass]
 struct JsHandle {
     pub module: GIL<ModuleHandle>,
+    /// The specifier the module was resolved to when it was loaded
+    specifier: ModuleSpecifier,
+    /// Set by `unload`, the handle is useless from then on
+    unloaded: AtomicBool,
 }
 
 // /// An Unloaded version of a Js Module or ready to be prepared...
 #[pyclass]
 struct JsModule {
     module: GIL<Module>,
+    /// Filled in the first time `contents_hash` is asked for
+    digest: OnceLock<String>,
 }
 
 #[pymethods]
@@ -171,6 +936,7 @@ impl JsModule {
     pub fn new(filename: String, contents: String) -> Self {
         Self {
             module: GIL::new(Module::new(filename, contents)),
+            digest: OnceLock::new(),
         }
     }
 
@@ -179,13 +945,24 @@ impl JsModule {
         match Module::load(filename) {
             Ok(x) => Ok(Self {
                 module: GIL::new(x),
+                digest: OnceLock::new(),
             }),
             Err(e) => Err(PyFileNotFoundError::new_err(e.to_string())),
         }
     }
 
+    /// `extensions` picks which files get loaded, defaulting to `js` and `ts`
     #[staticmethod]
-    pub fn load_dir(directory: String) -> PyResult<Vec<Self>> {
+    #[pyo3(signature=(directory, extensions=None))]
+    pub fn load_dir(directory: String, extensions: Option<Vec<String>>) -> PyResult<Vec<Self>> {
+        let extensions: Vec<String> = match extensions {
+            // Both "mjs" and ".mjs" are taken
+            Some(extensions) => extensions
+                .iter()
+                .map(|e| e.trim_start_matches('.').to_string())
+                .collect(),
+            None => vec!["js".to_string(), "ts".to_string()],
+        };
         // Mirrors load_dir from Module::load_dir but for our python-made class object...
         let mut files: Vec<Self> = Vec::new();
         for file in read_dir(directory)? {
@@ -196,7 +973,7 @@ impl JsModule {
                     .extension()
                     .and_then(OsStr::to_str)
                     .unwrap_or_default();
-                if !["js", "ts"].contains(&extension) {
+                if !extensions.iter().any(|e| e == extension) {
                     continue;
                 }
 
@@ -215,14 +992,48 @@ impl JsModule {
     pub fn contents(&self) -> PyResult<String> {
         Ok(self.module.get()?.contents().to_string())
     }
+
+    /// Transpiles typescript or JSX to javascript without a runtime, the extension
+    /// of `filename` tells which it is. Javascript comes back untouched.
+    #[staticmethod]
+    pub fn transpile(source: String, filename: String) -> PyResult<String> {
+        let specifier = resolve_path(&filename, &std::env::current_dir()?)
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        transpile::transpile(&specifier, &source).map_err(PyValueError::new_err)
+    }
+
+    /// Hex sha256 of the contents, worked out once and then kept
+    pub fn contents_hash(&self) -> PyResult<String> {
+        if let Some(digest) = self.digest.get() {
+            return Ok(digest.clone());
+        }
+        let digest = format!(
+            "{:x}",
+            Sha256::digest(self.module.get()?.contents().as_bytes())
+        );
+        Ok(self.digest.get_or_init(|| digest).clone())
+    }
 }
 
 impl JsHandle {
-    pub fn new(handle: ModuleHandle) -> Self {
+    pub fn new(handle: ModuleHandle, specifier: ModuleSpecifier) -> Self {
         Self {
             module: GIL::new(handle),
+            specifier,
+            unloaded: AtomicBool::new(false),
         }
     }
+
+    /// The module's handle, raising `InvalidStateError` once it's been unloaded
+    #[track_caller]
+    fn handle(&self) -> PyResult<Guard<'_, ModuleHandle>> {
+        if self.unloaded.load(Ordering::Acquire) {
+            return Err(InvalidStateError::new_err(
+                "JsHandle's module has been unloaded.",
+            ));
+        }
+        self.module.get()
+    }
 }
 
 #[pymethods]
@@ -230,8 +1041,7 @@ impl JsHandle {
     #[getter]
     pub fn filename(&self) -> PyResult<String> {
         Ok(self
-            .module
-            .get()?
+            .handle()?
             .module()
             .filename()
             .to_string_lossy()
@@ -240,36 +1050,383 @@ impl JsHandle {
 
     #[getter]
     pub fn contents(&self) -> PyResult<String> {
-        Ok(self.module.get()?.module().contents().to_string())
+        Ok(self.handle()?.module().contents().to_string())
+    }
+
+    /// The absolute path the module was loaded from, or its url when it
+    /// didn't come from a file
+    pub fn resolved_path(&self) -> String {
+        match self.specifier.to_file_path() {
+            Ok(path) => path.to_string_lossy().to_string(),
+            Err(()) => self.specifier.to_string(),
+        }
+    }
+
+    /// The `typeof` of an export, found without converting it so functions
+    /// and other values that can't be serialized can be inspected too
+    pub fn describe_export(&self, ctx: &mut Context, name: &str) -> PyResult<String> {
+        let handle = self.handle()?;
+        let mut rt = ctx.runtime.lock(ctx.lock_timeout)?;
+        let js = rt.deno_runtime();
+        let namespace = match js.get_module_namespace(handle.id()) {
+            Ok(namespace) => namespace,
+            Err(e) => return Err(PyRuntimeError::new_err(e.to_string())),
+        };
+        let scope = &mut js.handle_scope();
+        let namespace = v8::Local::new(scope, namespace);
+        let Some(key) = v8::String::new(scope, name) else {
+            return Err(PyValueError::new_err("export name could not be encoded"));
+        };
+        let tc = &mut v8::TryCatch::new(scope);
+        if !namespace.has(tc, key.into()).unwrap_or(false) {
+            return Err(PyKeyError::new_err(name.to_string()));
+        }
+        // Reading an export before its module finished evaluating throws
+        match namespace.get(tc, key.into()) {
+            Some(value) => Ok(value.type_of(tc).to_rust_string_lossy(tc)),
+            None => Err(caught_exception(tc)),
+        }
+    }
+
+    /// Lets go of the module so its exports and entrypoint can be garbage
+    /// collected, loading the same file again evaluates it anew. V8 has no way
+    /// to forget a module, the compiled record stays in the module map until
+    /// the context is recycled. The handle raises `InvalidStateError` after.
+    pub fn unload(slf: PyRef<'_, Self>, ctx: &mut Context) -> PyResult<()> {
+        match ctx.loaded.get(slf.specifier.as_str()) {
+            Some((_, loaded)) if loaded.as_ptr() == slf.as_ptr() => {}
+            _ => {
+                return Err(PyValueError::new_err(
+                    "the module wasn't loaded by this context",
+                ));
+            }
+        }
+        let mut handle = slf.handle()?;
+        *handle = ModuleHandle::default();
+        slf.unloaded.store(true, Ordering::Release);
+        drop(handle);
+        ctx.loaded.remove(slf.specifier.as_str());
+        Ok(())
+    }
+}
+
+impl Context {
+    fn js_error(&self, error: RSError) -> PyErr {
+        named_js_error(error, Some(&self.name), self.verbose_errors)
+    }
+
+    /// Rejects sources over the byte budget so giant inputs never reach the compiler
+    fn check_source(&self, code: &str) -> PyResult<()> {
+        if let Some(limit) = self.max_source_bytes
+            && code.len() > limit
+        {
+            return Err(PyValueError::new_err(format!(
+                "source is {} bytes which exceeds max_source_bytes ({})",
+                code.len(),
+                limit
+            )));
+        }
+        Ok(())
+    }
+
+    /// Loads every javascript and typescript module in `directory` sorted by
+    /// filename, stopping at the first that fails
+    fn preload(&mut self, directory: String) -> PyResult<()> {
+        let extensions = vec!["js".to_string(), "ts".to_string()];
+        let mut modules = JsModule::load_dir(directory, Some(extensions))?;
+        modules.sort_by_cached_key(|m| m.filename().unwrap_or_default());
+        for module in &modules {
+            let handle = self.load_module(module, None)?;
+            self.modules.push(handle);
+        }
+        Ok(())
+    }
+
+    /// Evaluates `module` in place of the one `handle` was loaded from, the
+    /// handle points at the new module afterwards
+    fn replace_module(&self, handle: &JsHandle, module: &Module) -> PyResult<()> {
+        self.check_source(module.contents())?;
+        let resolution = loader::Resolution {
+            import_map: &self.config.import_map,
+            packages: self.config.packages.as_deref(),
+        };
+        let prepared = loader::prepare(module, resolution, &handle.specifier)?;
+        let mut rt = self.runtime.lock(self.lock_timeout)?;
+        loader::notify(&self.config.load_hook, handle.specifier.as_str());
+        let reloaded = match rt.load_module(prepared.as_ref().unwrap_or(module)) {
+            Ok(reloaded) => reloaded,
+            Err(e) => return Err(self.js_error(e)),
+        };
+        *handle.module.get()? = reloaded;
+        if self.config.shared_module_cache {
+            module_cache::share(&handle.specifier, module, resolution)?;
+            module_cache::fill(&mut rt)?;
+        }
+        Ok(())
+    }
+
+    /// Raises once the context is closed, the runtime's lock does the same for
+    /// the methods that take it
+    fn check_open(&self) -> PyResult<()> {
+        if self.closed {
+            return Err(PyRuntimeError::new_err("context closed"));
+        }
+        Ok(())
+    }
+
+    /// Raises whatever `console.error` was called with since the last check
+    /// Everything `run_until_complete` does after a tick of the event loop,
+    /// its result once the promise settled
+    fn tick(
+        &self,
+        rt: &mut Runtime,
+        promise: &mut JSPromise,
+        more: bool,
+        on_tick: Option<&Py<PyAny>>,
+        started: Instant,
+    ) -> PyResult<Option<Py<PyAny>>> {
+        self.check_console_errors()?;
+        if promise.settled_without(self)? || promise.poll(self, rt)? {
+            self.check_console_errors()?;
+            return promise.result().map(Some);
+        }
+        if !more {
+            return Err(PyRuntimeError::new_err(
+                "Event loop ran out of work before the promise settled.",
+            ));
+        }
+        if let Some(on_tick) = on_tick {
+            let elapsed = started.elapsed().as_secs_f64();
+            let cancelled = Python::with_gil(|py| {
+                let r = on_tick.bind(py).call1((elapsed,))?;
+                // Only an explicit False cancels so callbacks returning None keep going
+                Ok::<_, PyErr>(r.downcast::<PyBool>().is_ok_and(|b| !b.is_true()))
+            })?;
+            if cancelled {
+                return Err(CancelledError::new_err("on_tick returned False"));
+            }
+        }
+        Ok(None)
+    }
+
+    fn check_console_errors(&self) -> PyResult<()> {
+        match &self.console_errors {
+            Some(errors) => Python::with_gil(|py| errors.check(py)),
+            None => Ok(()),
+        }
     }
 }
 
 #[pymethods]
 impl Context {
     #[new]
-    #[pyo3(signature = (timeout=None, max_heap_size=None))]
-    pub fn new(timeout: Option<f64>, max_heap_size: Option<usize>) -> PyResult<Self> {
-        Ok(Self {
-            runtime: create_runtime(timeout, max_heap_size)?,
-        })
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (timeout=None, max_heap_size=None, symbol_keys=None, stack_trace_limit=None, convert_objects=None, timeout_ms=None, max_source_bytes=None, cache_results=None, memoryview=None, shared_pool=None, numeric_strings_as_decimal=None, shared_module_cache=None, regex_as_pattern=None, minimal=None, random_seed=None, preserve_surrogates=None, default_locale=None, max_result_bytes=None, lock_timeout=None, import_map=None, console_error_raises=None, mark_circular=None, module_dir=None, decimal_args_as_strings=None, name=None, verbose_errors=None, conditions=None, web_apis=None, integer_keys=None, max_concurrent_ops=None, env=None, env_writable=None, log_errors=None, pin_thread=None))]
+    pub fn new(
+        timeout: Option<f64>,
+        max_heap_size: Option<usize>,
+        symbol_keys: Option<bool>,
+        stack_trace_limit: Option<u32>,
+        convert_objects: Option<bool>,
+        timeout_ms: Option<u64>,
+        max_source_bytes: Option<usize>,
+        cache_results: Option<bool>,
+        memoryview: Option<bool>,
+        shared_pool: Option<bool>,
+        numeric_strings_as_decimal: Option<bool>,
+        shared_module_cache: Option<bool>,
+        regex_as_pattern: Option<bool>,
+        minimal: Option<bool>,
+        random_seed: Option<u64>,
+        preserve_surrogates: Option<bool>,
+        default_locale: Option<String>,
+        max_result_bytes: Option<usize>,
+        lock_timeout: Option<f64>,
+        import_map: Option<&Bound<'_, PyDict>>,
+        console_error_raises: Option<bool>,
+        mark_circular: Option<bool>,
+        module_dir: Option<String>,
+        decimal_args_as_strings: Option<bool>,
+        name: Option<String>,
+        verbose_errors: Option<bool>,
+        conditions: Option<Vec<String>>,
+        web_apis: Option<Vec<String>>,
+        integer_keys: Option<bool>,
+        max_concurrent_ops: Option<usize>,
+        env: Option<&Bound<'_, PyDict>>,
+        env_writable: Option<bool>,
+        log_errors: Option<bool>,
+        pin_thread: Option<bool>,
+    ) -> PyResult<Self> {
+        let timeout = either_timeout(timeout, timeout_ms)?;
+        let lock_timeout = match lock_timeout.map(Duration::try_from_secs_f64) {
+            Some(Ok(lock_timeout)) => Some(lock_timeout),
+            Some(Err(e)) => return Err(PyValueError::new_err(format!("lock_timeout: {e}"))),
+            None => None,
+        };
+        if max_concurrent_ops == Some(0) {
+            return Err(PyValueError::new_err(
+                "max_concurrent_ops has to be at least 1",
+            ));
+        }
+        if let Some(web_apis) = &web_apis {
+            if minimal == Some(true) {
+                return Err(PyValueError::new_err(
+                    "minimal and web_apis can't be used together",
+                ));
+            }
+            if let Some(unknown) = web_apis
+                .iter()
+                .find(|name| !scripts::WEB_API_GLOBALS.iter().any(|(api, _)| api == name))
+            {
+                let known: Vec<_> = scripts::WEB_API_GLOBALS
+                    .iter()
+                    .map(|(api, _)| *api)
+                    .collect();
+                return Err(PyValueError::new_err(format!(
+                    "unknown web api {unknown:?}, expected one of {}",
+                    known.join(", ")
+                )));
+            }
+        }
+        let env = match env {
+            Some(env) => {
+                let mut vars = serde_json::Map::with_capacity(env.len());
+                for (key, value) in env.iter() {
+                    let (Ok(key), Ok(value)) = (key.extract::<String>(), value.extract::<String>())
+                    else {
+                        return Err(PyValueError::new_err(format!(
+                            "env has to map strings to strings, got {key}: {value}"
+                        )));
+                    };
+                    vars.insert(key, value.into());
+                }
+                Some((vars, env_writable.unwrap_or(false)))
+            }
+            None => None,
+        };
+        // Relative entries are taken from where the process is, which is also
+        // where the runtime starts out
+        let import_map = match import_map {
+            Some(map) => ImportMap::from_dict(map, &std::env::current_dir()?)?,
+            None => ImportMap::default(),
+        };
+        let config = RuntimeConfig {
+            timeout,
+            max_heap_size,
+            stack_trace_limit,
+            shared_pool: shared_pool.unwrap_or(false),
+            shared_module_cache: shared_module_cache.unwrap_or(false),
+            minimal: minimal.unwrap_or(false),
+            random_seed,
+            default_locale,
+            load_hook: Default::default(),
+            import_map: Arc::new(import_map),
+            packages: conditions.map(|conditions| Arc::new(Packages::new(conditions))),
+            web_apis,
+            max_concurrent_ops,
+            env,
+            log_errors: log_errors.unwrap_or(false),
+            max_source_bytes,
+            last_error: LastError::default(),
+        };
+        let mut runtime = create_runtime(&config)?;
+        let console_errors = match console_error_raises {
+            Some(true) => {
+                let errors = ConsoleErrors::default();
+                errors.install(&mut runtime)?;
+                Some(errors)
+            }
+            _ => None,
+        };
+        let mut context = Self {
+            runtime: match pin_thread {
+                Some(true) => GIL::pinned(runtime),
+                _ => GIL::new(runtime),
+            },
+            config,
+            convert: ConvertOptions {
+                symbol_keys: symbol_keys.unwrap_or(false),
+                cache: cache_results.unwrap_or(false).then(ResultCache::default),
+                memoryview: memoryview.unwrap_or(false),
+                numeric_strings: numeric_strings_as_decimal.unwrap_or(false),
+                regex_patterns: regex_as_pattern.unwrap_or(false),
+                preserve_surrogates: preserve_surrogates.unwrap_or(false),
+                max_result_bytes,
+                mark_circular: mark_circular.unwrap_or(false),
+                integer_keys: integer_keys.unwrap_or(false),
+            },
+            args: ArgOptions {
+                objects: convert_objects.unwrap_or(false),
+                encoder: None,
+                decimals_as_strings: decimal_args_as_strings.unwrap_or(false),
+            },
+            functions: HashMap::new(),
+            async_functions: HashMap::new(),
+            alive: Arc::new(()),
+            epoch: Arc::new(()),
+            timers_cleared: 0,
+            max_source_bytes,
+            lock_timeout,
+            rejection_handler: None,
+            console: None,
+            console_errors,
+            shared_buffers: GIL::new(HashMap::new()),
+            mounts: Mounts::default(),
+            modules: Vec::new(),
+            exit_handler: None,
+            closed: false,
+            name: name.unwrap_or_else(|| {
+                format!("context-{}", CONTEXT_IDS.fetch_add(1, Ordering::Relaxed))
+            }),
+            verbose_errors,
+            mock_time: None,
+            loaded: HashMap::new(),
+        };
+        if let Some(module_dir) = module_dir {
+            context.preload(module_dir)?;
+        }
+        Ok(context)
     }
+    /// Still readable once the context is closed so it can be named in logs
+    #[getter]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Handles of the modules loaded from `module_dir`
+    #[getter]
+    pub fn modules(&self, py: Python<'_>) -> PyResult<Vec<Py<JsHandle>>> {
+        self.check_open()?;
+        Ok(self.modules.iter().map(|m| m.clone_ref(py)).collect())
+    }
+
     #[getter]
     pub fn timeout(&self) -> PyResult<f64> {
-        Ok(self.runtime.get()?.timeout().as_secs_f64())
+        Ok(self
+            .runtime
+            .lock(self.lock_timeout)?
+            .timeout()
+            .as_secs_f64())
     }
 
     #[getter]
     pub fn current_dir(&self) -> PyResult<String> {
         Ok(self
             .runtime
-            .get()?
+            .lock(self.lock_timeout)?
             .current_dir()
             .to_string_lossy()
             .to_string())
     }
 
-    pub fn set_current_dir(&mut self, path: String) -> PyResult<()> {
-        match self.runtime.get()?.set_current_dir(path) {
+    /// `create` makes the directory along with any missing parents first
+    #[pyo3(signature=(path, create=None))]
+    pub fn set_current_dir(&mut self, path: String, create: Option<bool>) -> PyResult<()> {
+        if create.unwrap_or(false) {
+            std::fs::create_dir_all(&path)?;
+        }
+        match self.runtime.lock(self.lock_timeout)?.set_current_dir(path) {
             Ok(_) => Ok(()),
             Err(e) => Err(PyNotADirectoryError::new_err(e.to_string())),
         }
@@ -292,7 +1449,7 @@ impl Context {
 
     //     match self.runtime.get()?.advance_event_loop_async(options).await {
     //         Ok(b) => {Ok(b)},
-    //         Err(e) => {Err(PyRuntimeError::new_err(e.to_string()))}
+    //         Err(e) => {Err(js_error(e))}
     //     }
     // }
 
@@ -313,31 +1470,487 @@ impl Context {
         if let Some(pump_v8_message_loop) = pump_v8_message_loop {
             options.pump_v8_message_loop = pump_v8_message_loop;
         }
-        match self.runtime.get()?.advance_event_loop(options) {
-            Ok(b) => Ok(b),
-            Err(e) => Err(PyRuntimeError::new_err(e.to_string())),
+        let more = match self
+            .runtime
+            .lock(self.lock_timeout)?
+            .advance_event_loop(options)
+        {
+            Ok(b) => b,
+            Err(e) => {
+                let error = self.js_error(e);
+                self.config.last_error.set(&error);
+                return Err(error);
+            }
+        };
+        self.check_console_errors()?;
+        Ok(more)
+    }
+
+    /// Runs every pending microtask, promise continuations included, without
+    /// going on to timers or async ops the way `advance` does
+    pub fn drain_microtasks(&mut self) -> PyResult<()> {
+        self.runtime
+            .lock(self.lock_timeout)?
+            .deno_runtime()
+            .v8_isolate()
+            .perform_microtask_checkpoint();
+        self.check_console_errors()
+    }
+
+    /// `retries` re-runs the code on transient errors with an exponential backoff.
+    /// Tripping `cancel_token` from another thread stops the code with a `CancelledError`,
+    /// the GIL is released while running so that thread gets the chance to.
+    /// `return_timing` returns `(result, timing)` with how long the call took.
+    /// `allow_top_level_await` runs the code in an async function and waits on
+    /// it, bounded by the runtime's timeout like everything else.
+    #[pyo3(signature=(code, retries=None, cancel_token=None, return_timing=None, allow_top_level_await=None))]
+    pub fn eval(
+        &mut self,
+        py: Python<'_>,
+        code: &str,
+        retries: Option<u32>,
+        cancel_token: Option<Py<CancelToken>>,
+        return_timing: Option<bool>,
+        allow_top_level_await: Option<bool>,
+    ) -> PyResult<Py<PyAny>> {
+        self.check_source(code)?;
+        let wrapped;
+        let code = if allow_top_level_await.unwrap_or(false) {
+            wrapped = format!(
+                "{}({})",
+                scripts::TOP_LEVEL_AWAIT,
+                serde_json::Value::String(code.to_string())
+            );
+            wrapped.as_str()
+        } else {
+            code
+        };
+        let mut timing = return_timing.unwrap_or(false).then(Timing::start);
+        let mut rt = self.runtime.lock(self.lock_timeout)?;
+        if let Some(timing) = &mut timing {
+            timing.locked();
+        }
+        let result = match cancel_token {
+            None => with_retries(retries, is_transient, || rt.eval::<Value>(code)),
+            Some(token) => {
+                let token = token.get();
+                token.attach(rt.deno_runtime().v8_isolate().thread_safe_handle())?;
+                let detached = Detached::new(&mut *rt);
+                let result = py
+                    .allow_threads(move || {
+                        let rt = detached.into_inner();
+                        let retry_if = |e: &RSError| is_transient(e) && !token.cancelled();
+                        Detached::new(with_retries(retries, retry_if, || rt.eval::<Value>(code)))
+                    })
+                    .into_inner();
+                token.detach()?;
+                if token.cancelled() {
+                    // Leaving the isolate terminated would break whatever runs next
+                    rt.deno_runtime().v8_isolate().cancel_terminate_execution();
+                    return Err(CancelledError::new_err("eval was cancelled"));
+                }
+                result
+            }
+        };
+        if let Some(timing) = &mut timing {
+            timing.finished();
+        }
+        let value = match result {
+            Ok(r) => value_to_python(&mut rt, &r, &self.convert)?,
+            Err(e) => return Err(self.js_error(e)),
+        };
+        drop(rt);
+        self.check_console_errors()?;
+        match timing {
+            Some(timing) => timing.attach(py, value),
+            None => Ok(value),
+        }
+    }
+
+    /// Evaluates code and describes the result the way the node REPL prints it,
+    /// `{ a: 1, b: [ 1, 2 ] }` rather than the value itself.
+    pub fn eval_display(&mut self, code: &str) -> PyResult<String> {
+        self.check_source(code)?;
+        let mut rt = self.runtime.lock(self.lock_timeout)?;
+        let value = match rt.eval::<Value>(code) {
+            Ok(value) => value,
+            Err(e) => return Err(self.js_error(e)),
+        };
+        let scope = &mut rt.deno_runtime().handle_scope();
+        let value = v8::Local::new(scope, value.as_v8());
+        let tc = &mut v8::TryCatch::new(scope);
+        let Some(source) = v8::String::new(tc, scripts::DISPLAY) else {
+            return Err(PyRuntimeError::new_err(
+                "display script could not be encoded",
+            ));
+        };
+        let display = v8::Script::compile(tc, source, None)
+            .and_then(|script| script.run(tc))
+            .and_then(|f| v8::Local::<v8::Function>::try_from(f).ok());
+        let Some(display) = display else {
+            return Err(caught_exception(tc));
+        };
+        let recv = v8::undefined(tc).into();
+        let text = match display.call(tc, recv, &[value]) {
+            Some(text) => text.to_rust_string_lossy(tc),
+            None => return Err(caught_exception(tc)),
+        };
+        self.check_console_errors()?;
+        Ok(text)
+    }
+
+    /// Evaluates code and binds the result to `globalThis[name]`, the value never
+    /// leaves javascript so it doesn't have to be something python can hold
+    pub fn eval_into(&mut self, name: &str, code: &str) -> PyResult<()> {
+        self.check_source(code)?;
+        let mut rt = self.runtime.lock(self.lock_timeout)?;
+        let value = match rt.eval::<Value>(code) {
+            Ok(value) => value,
+            Err(e) => return Err(self.js_error(e)),
+        };
+        let scope = &mut rt.deno_runtime().handle_scope();
+        let Some(key) = v8::String::new(scope, name) else {
+            return Err(PyValueError::new_err("name could not be encoded"));
+        };
+        let value = v8::Local::new(scope, value.as_v8());
+        let global = scope.get_current_context().global(scope);
+        let tc = &mut v8::TryCatch::new(scope);
+        match global.set(tc, key.into(), value) {
+            Some(_) => Ok(()),
+            None => Err(caught_exception(tc)),
+        }
+    }
+
+    /// Takes the most recent error the event loop ran into, whether `advance`,
+    /// `run_until_complete` or stepping a promise met it or it went to the
+    /// rejection handler or the error log
+    pub fn last_error(&mut self, py: Python<'_>) -> PyResult<Option<Py<PyAny>>> {
+        self.check_open()?;
+        Ok(self
+            .config
+            .last_error
+            .take()
+            .map(|error| error.into_value(py).into_any()))
+    }
+
+    /// Evaluates code and hands the result to `predicate`, raising `AssertionError`
+    /// with the result's repr when it returns something falsy
+    pub fn eval_assert(
+        &mut self,
+        py: Python<'_>,
+        code: &str,
+        predicate: Py<PyAny>,
+    ) -> PyResult<Py<PyAny>> {
+        let predicate = predicate.bind(py);
+        if !predicate.is_callable() {
+            return Err(PyValueError::new_err("predicate is not callable"));
+        }
+        let value = self.eval(py, code, None, None, None, None)?;
+        if predicate.call1((value.clone_ref(py),))?.is_truthy()? {
+            return Ok(value);
+        }
+        Err(PyAssertionError::new_err(format!(
+            "predicate rejected {}",
+            value.bind(py).repr()?
+        )))
+    }
+
+    /// Evaluates code and waits for the promise it evaluates to, if it is one,
+    /// returning what it resolves to. `timeout` in seconds or `timeout_ms` in
+    /// milliseconds replaces the runtime's own for the call, raising
+    /// `TimeoutError` once it's up.
+    #[pyo3(signature=(code, timeout=None, timeout_ms=None))]
+    pub fn eval_await(
+        &mut self,
+        code: &str,
+        timeout: Option<f64>,
+        timeout_ms: Option<u64>,
+    ) -> PyResult<Py<PyAny>> {
+        self.check_source(code)?;
+        let timeout = either_timeout(timeout, timeout_ms)?;
+        let mut rt = self.runtime.lock(self.lock_timeout)?;
+        let result = match timeout {
+            Some(limit) => bounded(&mut rt, limit, "eval_await", async |rt| {
+                rt.eval_async::<Value>(code).await
+            }),
+            None => rt.eval::<Value>(code),
+        };
+        let value = match result {
+            Ok(r) => value_to_python(&mut rt, &r, &self.convert)?,
+            Err(e) => return Err(self.js_error(e)),
+        };
+        drop(rt);
+        self.check_console_errors()?;
+        Ok(value)
+    }
+
+    /// Evaluates code with a temporary `__emit(chunk)` global that hands each chunk
+    /// to `on_chunk` as it's emitted, so big outputs reach python piece by piece
+    /// instead of as one value. The event loop runs until it's out of work and
+    /// what the code evaluated to is returned.
+    pub fn eval_stream(&mut self, code: &str, on_chunk: Py<PyAny>) -> PyResult<Py<PyAny>> {
+        self.check_source(code)?;
+        if !Python::with_gil(|py| on_chunk.bind(py).is_callable()) {
+            return Err(PyValueError::new_err("on_chunk is not callable"));
+        }
+        let mut rt = self.runtime.lock(self.lock_timeout)?;
+        install_functions(&mut rt, &[(EMIT.to_string(), on_chunk)])?;
+        let result = rt.eval::<Value>(code).and_then(|value| {
+            rt.block_on_event_loop(PollEventLoopOptions::default(), None)?;
+            Ok(value)
+        });
+        // A function of the same name registered before this gets its global back
+        let restored = match self.functions.get(EMIT) {
+            Some(func) => {
+                let func = Python::with_gil(|py| func.clone_ref(py));
+                install_functions(&mut rt, &[(EMIT.to_string(), func)])
+            }
+            None => remove_function(&mut rt, EMIT),
+        };
+        let value = match result {
+            Ok(r) => value_to_python(&mut rt, &r, &self.convert)?,
+            Err(e) => return Err(self.js_error(e)),
+        };
+        restored?;
+        drop(rt);
+        self.check_console_errors()?;
+        Ok(value)
+    }
+
+    /// Same as `eval` but failures come back as a `JsResult` instead of being raised
+    /// Only a runtime that's busy or gone is still raised.
+    pub fn eval_result(&mut self, code: &str) -> PyResult<JsResult> {
+        if let Err(e) = self.check_source(code) {
+            return Ok(JsResult::new(Err(e)));
         }
+        let mut rt = self.runtime.lock(self.lock_timeout)?;
+        let outcome = match rt.eval::<Value>(code) {
+            Ok(r) => value_to_python(&mut rt, &r, &self.convert),
+            Err(e) => Err(self.js_error(e)),
+        };
+        drop(rt);
+        let outcome = outcome.and_then(|value| self.check_console_errors().map(|_| value));
+        Ok(JsResult::new(outcome))
     }
 
-    pub fn eval(&mut self, code: &str) -> PyResult<Py<PyAny>> {
-        let result: Result<serde_json::Value, _> = self.runtime.get()?.eval(code);
+    /// Evaluates code under the v8 CPU profiler, returning the result along with a
+    /// `.cpuprofile` json string that Chrome DevTools can load.
+    #[cfg(feature = "profiler")]
+    pub fn profile(&mut self, code: &str) -> PyResult<(Py<PyAny>, String)> {
+        self.check_source(code)?;
+        let mut rt = self.runtime.lock(self.lock_timeout)?;
+        let (result, profile) = profile::profile(&mut rt, code)?;
         match result {
-            Ok(r) => Ok(serde_to_python(r)?),
-            Err(e) => Err(PyRuntimeError::new_err(e.to_string())),
+            Ok(r) => Ok((value_to_python(&mut rt, &r, &self.convert)?, profile)),
+            Err(e) => Err(self.js_error(e)),
         }
     }
 
-    #[pyo3(signature=(name, *py_args))]
-    pub fn call(&mut self, name: String, py_args: &Bound<'_, PyTuple>) -> PyResult<Py<PyAny>> {
-        let result: Result<serde_json::Value, _> = self.runtime.get()?.call_function_immediate(
-            None,
-            &name,
-            &python_args_to_serde(py_args)?,
-        );
+    /// Evaluates code that results in an `ArrayBuffer` or typed array and returns its bytes
+    pub fn eval_bytes(&mut self, code: &str) -> PyResult<Py<PyBytes>> {
+        self.check_source(code)?;
+        let mut rt = self.runtime.lock(self.lock_timeout)?;
+        let bytes = match rt.eval::<Value>(code) {
+            Ok(r) => value_to_bytes(&mut rt, &r)?,
+            Err(e) => return Err(self.js_error(e)),
+        };
+        drop(rt);
+        self.check_console_errors()?;
+        Ok(bytes)
+    }
+
+    /// Evaluates code and builds an instance of `py_type` out of the result,
+    /// objects are passed in as keyword arguments and anything else as the only argument.
+    pub fn eval_as(&mut self, code: &str, py_type: &Bound<'_, PyType>) -> PyResult<Py<PyAny>> {
+        let value = self.eval(py_type.py(), code, None, None, None, None)?;
+        let py = py_type.py();
+        let value = value.bind(py);
+        let instance = match value.downcast::<PyDict>() {
+            Ok(fields) => py_type.call((), Some(fields)),
+            Err(_) => py_type.call1((value,)),
+        };
+        match instance {
+            Ok(instance) => Ok(instance.unbind()),
+            Err(e) => Err(PyTypeError::new_err(format!(
+                "could not build {} from the result: {e}",
+                py_type.name()?
+            ))),
+        }
+    }
+
+    /// Evaluates code while recording anything written to the console,
+    /// the captured lines are returned alongside the result.
+    pub fn eval_capturing(&mut self, code: &str) -> PyResult<(Py<PyAny>, Vec<String>)> {
+        self.check_source(code)?;
+        let mut rt = self.runtime.lock(self.lock_timeout)?;
+        if let Err(e) = rt.eval::<Undefined>(scripts::CONSOLE_CAPTURE_START) {
+            return Err(self.js_error(e));
+        }
+        let result: Result<Value, _> = rt.eval(code);
+        let lines: Vec<String> = match rt.eval(scripts::CONSOLE_CAPTURE_STOP) {
+            Ok(lines) => lines,
+            Err(e) => return Err(self.js_error(e)),
+        };
         match result {
-            Ok(r) => Ok(serde_to_python(r)?),
-            Err(e) => Err(PyRuntimeError::new_err(e.to_string())),
+            Ok(r) => Ok((value_to_python(&mut rt, &r, &self.convert)?, lines)),
+            Err(e) => Err(self.js_error(e)),
+        }
+    }
+
+    /// `return_timing` returns `(result, timing)` the same as `eval` does
+    #[pyo3(signature=(name, *py_args, retries=None, return_timing=None))]
+    pub fn call(
+        &mut self,
+        py: Python<'_>,
+        name: String,
+        py_args: &Bound<'_, PyTuple>,
+        retries: Option<u32>,
+        return_timing: Option<bool>,
+    ) -> PyResult<Py<PyAny>> {
+        let args = python_args_to_serde(py_args, &self.args)?;
+        let mut timing = return_timing.unwrap_or(false).then(Timing::start);
+        let mut rt = self.runtime.lock(self.lock_timeout)?;
+        if let Some(timing) = &mut timing {
+            timing.locked();
+        }
+        let result: Result<Value, _> = with_retries(retries, is_transient, || {
+            rt.call_function_immediate(None, &name, &args)
+        });
+        if let Some(timing) = &mut timing {
+            timing.finished();
+        }
+        let value = match result {
+            Ok(r) => value_to_python(&mut rt, &r, &self.convert)?,
+            Err(e) => return Err(self.js_error(e)),
+        };
+        drop(rt);
+        self.check_console_errors()?;
+        match timing {
+            Some(timing) => timing.attach(py, value),
+            None => Ok(value),
+        }
+    }
+
+    /// Evaluates `expr` to a function and calls it like `call` does, for functions
+    /// that aren't globals such as `obj.handlers.onClick`. The function gets no
+    /// `this`, methods that need one have to be bound.
+    #[pyo3(signature=(expr, *py_args))]
+    pub fn call_value(&mut self, expr: &str, py_args: &Bound<'_, PyTuple>) -> PyResult<Py<PyAny>> {
+        self.check_source(expr)?;
+        let args = python_args_to_serde(py_args, &self.args)?;
+        let mut rt = self.runtime.lock(self.lock_timeout)?;
+        let value = match rt.eval::<Value>(expr) {
+            Ok(value) => value,
+            Err(e) => return Err(self.js_error(e)),
+        };
+        let function = {
+            let scope = &mut rt.deno_runtime().handle_scope();
+            let local = v8::Local::new(scope, value.as_v8());
+            if !local.is_function() {
+                let kind = local.type_of(scope).to_rust_string_lossy(scope);
+                return Err(PyTypeError::new_err(format!(
+                    "{expr} is not callable, it's {kind}"
+                )));
+            }
+            Function::try_from_v8(scope, value.into_v8())
+        };
+        let result =
+            function.and_then(|function| function.call_immediate::<Value>(&mut rt, None, &args));
+        let value = match result {
+            Ok(r) => value_to_python(&mut rt, &r, &self.convert)?,
+            Err(e) => return Err(self.js_error(e)),
+        };
+        drop(rt);
+        self.check_console_errors()?;
+        Ok(value)
+    }
+
+    /// Calls a global function `iterations` times with the sample arguments so v8
+    /// has optimized it before it's timed, the results are thrown away
+    #[pyo3(signature=(name, iterations, *sample_args))]
+    pub fn warmup(
+        &mut self,
+        name: &str,
+        iterations: u32,
+        sample_args: &Bound<'_, PyTuple>,
+    ) -> PyResult<()> {
+        let args = python_args_to_serde(sample_args, &self.args)?;
+        let mut rt = self.runtime.lock(self.lock_timeout)?;
+        for _ in 0..iterations {
+            if let Err(e) = rt.call_function_immediate::<Value>(None, name, &args) {
+                return Err(self.js_error(e));
+            }
+        }
+        Ok(())
+    }
+
+    /// Calls a global function with `args` followed by `kwargs` as a final options
+    /// object, the shape most javascript apis take
+    #[pyo3(signature=(name, args, kwargs=None))]
+    pub fn call_full(
+        &mut self,
+        name: &str,
+        args: &Bound<'_, PyAny>,
+        kwargs: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<Py<PyAny>> {
+        let args = PyTuple::new(args.py(), args.try_iter()?.collect::<PyResult<Vec<_>>>()?)?;
+        let mut args = python_args_to_serde(&args, &self.args)?;
+        if let Some(kwargs) = kwargs {
+            args.push(python_to_argument(kwargs.as_any(), &self.args)?);
         }
+        let mut rt = self.runtime.lock(self.lock_timeout)?;
+        let result: Result<Value, _> = rt.call_function_immediate(None, name, &args);
+        let value = match result {
+            Ok(r) => value_to_python(&mut rt, &r, &self.convert)?,
+            Err(e) => return Err(self.js_error(e)),
+        };
+        drop(rt);
+        self.check_console_errors()?;
+        Ok(value)
+    }
+
+    /// Calls a global function with arguments decoded straight from a serialized blob,
+    /// skipping the conversion of python objects. `format` is msgpack, cbor or json.
+    #[pyo3(signature=(name, blob, format="msgpack"))]
+    pub fn call_blob(&mut self, name: &str, blob: &[u8], format: &str) -> PyResult<Py<PyAny>> {
+        let args = blob::decode_args(blob, BlobFormat::parse(format)?)?;
+        let mut rt = self.runtime.lock(self.lock_timeout)?;
+        let result: Result<Value, _> = rt.call_function_immediate(None, name, &args);
+        let value = match result {
+            Ok(r) => value_to_python(&mut rt, &r, &self.convert)?,
+            Err(e) => return Err(self.js_error(e)),
+        };
+        drop(rt);
+        self.check_console_errors()?;
+        Ok(value)
+    }
+
+    /// Calls a global function with the contents of a json file as its one argument,
+    /// parsed as it's read so the file never has to fit in python memory as a string.
+    /// The GIL is released while parsing.
+    pub fn call_from_file(
+        &mut self,
+        py: Python<'_>,
+        name: &str,
+        json_file: &str,
+    ) -> PyResult<Py<PyAny>> {
+        let file = match File::open(json_file) {
+            Ok(file) => BufReader::new(file),
+            Err(e) => return Err(PyFileNotFoundError::new_err(format!("{json_file}: {e}"))),
+        };
+        let arg = match py.allow_threads(|| serde_json::from_reader::<_, serde_json::Value>(file)) {
+            Ok(arg) => arg,
+            Err(e) => return Err(PyValueError::new_err(format!("{json_file}: {e}"))),
+        };
+        let mut rt = self.runtime.lock(self.lock_timeout)?;
+        let result: Result<Value, _> = rt.call_function_immediate(None, name, &[arg]);
+        let value = match result {
+            Ok(r) => value_to_python(&mut rt, &r, &self.convert)?,
+            Err(e) => return Err(self.js_error(e)),
+        };
+        drop(rt);
+        self.check_console_errors()?;
+        Ok(value)
     }
 
     #[pyo3(signature=(module, name, *py_args))]
@@ -347,34 +1960,55 @@ impl Context {
         name: String,
         py_args: &Bound<'_, PyTuple>,
     ) -> PyResult<Py<PyAny>> {
-        let mc = module.module.get()?;
-        let result: Result<serde_json::Value, _> = self.runtime.get()?.call_function_immediate(
+        let mc = module.handle()?;
+        let mut rt = self.runtime.lock(self.lock_timeout)?;
+        let result: Result<Value, _> = rt.call_function_immediate(
             Some(&mc),
             &name,
-            &python_args_to_serde(py_args)?,
+            &python_args_to_serde(py_args, &self.args)?,
         );
-        match result {
-            Ok(r) => Ok(serde_to_python(r)?),
-            Err(e) => Err(PyRuntimeError::new_err(e.to_string())),
-        }
+        let value = match result {
+            Ok(r) => value_to_python(&mut rt, &r, &self.convert)?,
+            Err(e) => return Err(self.js_error(e)),
+        };
+        drop(rt);
+        self.check_console_errors()?;
+        Ok(value)
     }
 
     pub fn get_value(&mut self, name: String) -> PyResult<Py<PyAny>> {
-        let result: Result<serde_json::Value, _> =
-            self.runtime.get()?.get_value_immediate(None, &name);
-        match result {
-            Ok(r) => Ok(serde_to_python(r)?),
+        let mut rt = self.runtime.lock(self.lock_timeout)?;
+        let result: Result<Value, _> = rt.get_value_immediate(None, &name);
+        let value = match result {
+            Ok(r) => value_to_python(&mut rt, &r, &self.convert)?,
             Err(e) => match e {
-                RSError::ValueNotFound(s) => Err(PyKeyError::new_err(s)),
-                e => Err(PyRuntimeError::new_err(e.to_string())),
+                RSError::ValueNotFound(s) => return Err(PyKeyError::new_err(s)),
+                e => return Err(self.js_error(e)),
             },
-        }
+        };
+        drop(rt);
+        self.check_console_errors()?;
+        Ok(value)
+    }
+
+    /// Same as `get_value` but missing names come back as `None` instead of raising
+    pub fn try_get_value(&mut self, name: String) -> PyResult<Option<Py<PyAny>>> {
+        let mut rt = self.runtime.lock(self.lock_timeout)?;
+        let result: Result<Value, _> = rt.get_value_immediate(None, &name);
+        let value = match result {
+            Ok(r) => value_to_python(&mut rt, &r, &self.convert)?,
+            Err(RSError::ValueNotFound(_)) => return Ok(None),
+            Err(e) => return Err(self.js_error(e)),
+        };
+        drop(rt);
+        self.check_console_errors()?;
+        Ok(Some(value))
     }
 
     // Still having trouble getting the bigger one to work so I made a smaller version of it...
     // #[pyo3(signature=(module, modules=None))]
     // pub fn load_modules(&mut self, module:&JsModule, modules:Option<Vec<JsModule>>) -> PyResult<JsHandle> {
-    //     let mut rt = self.runtime.get()?;
+    //     let mut rt = self.runtime.lock(self.lock_timeout)?;
     //     let mut m = &module.module.get()?;
     //     let result = match modules {
     //         Some(mods) => {
@@ -390,18 +2024,598 @@ impl Context {
     //             Ok(JsHandle::new(handle))
     //         }
     //         Err(e) => {
-    //             Err(PyRuntimeError::new_err(e.to_string()))
+    //             Err(js_error(e))
     //         }
     //     }
     // }
 
-    /// Loads in a single module
-    pub fn load_module(&mut self, module: &JsModule) -> PyResult<JsHandle> {
+    /// Exposes a python callable to javascript as a global function
+    pub fn register_function(&mut self, name: String, func: Py<PyAny>) -> PyResult<()> {
+        let funcs = Python::with_gil(|py| {
+            let funcs = PyDict::new(py);
+            funcs.set_item(name, func)?;
+            Ok::<_, PyErr>(funcs.unbind())
+        })?;
+        Python::with_gil(|py| self.register_functions(funcs.bind(py)))
+    }
+
+    /// Exposes an async python callable as a global function returning a promise.
+    /// The coroutines run on `event_loop`, which has to be running in another
+    /// thread, or on a loop in a thread of its own when it's `None`.
+    #[pyo3(signature=(name, func, event_loop=None))]
+    pub fn register_async_function(
+        &mut self,
+        name: String,
+        func: Py<PyAny>,
+        event_loop: Option<Py<PyAny>>,
+    ) -> PyResult<()> {
+        if !Python::with_gil(|py| func.bind(py).is_callable()) {
+            return Err(PyValueError::new_err(format!("{name} is not callable")));
+        }
+        let function = AsyncFunction { func, event_loop };
+        let pair = Python::with_gil(|py| (name.clone(), function.clone_ref(py)));
+        let mut rt = self.runtime.lock(self.lock_timeout)?;
+        async_function::install(&mut rt, &[pair])?;
+        self.functions.remove(&name);
+        self.async_functions.insert(name, function);
+        Ok(())
+    }
+
+    /// Exposes a mapping of names to python callables as global functions,
+    /// the globals for all of them are bound in a single pass.
+    pub fn register_functions(&mut self, funcs: &Bound<'_, PyDict>) -> PyResult<()> {
+        let mut pairs = Vec::with_capacity(funcs.len());
+        for (name, func) in funcs.iter() {
+            let name: String = name.extract()?;
+            if !func.is_callable() {
+                return Err(PyValueError::new_err(format!("{name} is not callable")));
+            }
+            pairs.push((name, func.unbind()));
+        }
+        let mut rt = self.runtime.lock(self.lock_timeout)?;
+        install_functions(&mut rt, &pairs)?;
+        for (name, _) in &pairs {
+            self.async_functions.remove(name);
+        }
+        self.functions.extend(pairs);
+        Ok(())
+    }
+
+    /// Sets a callable that gets every argument serde can't serialize and returns
+    /// something it can, `None` removes it again.
+    pub fn set_arg_encoder(&mut self, encoder: Option<Py<PyAny>>) -> PyResult<()> {
+        self.check_open()?;
+        if let Some(encoder) = &encoder
+            && !Python::with_gil(|py| encoder.bind(py).is_callable())
+        {
+            return Err(PyValueError::new_err("encoder is not callable"));
+        }
+        self.args.encoder = encoder.map(Arc::new);
+        Ok(())
+    }
+
+    /// Calls `hook` with the specifier of every module as it's loaded, before it's
+    /// evaluated. `None` removes the hook.
+    pub fn set_module_load_hook(&mut self, hook: Option<Py<PyAny>>) -> PyResult<()> {
+        self.check_open()?;
+        if let Some(hook) = &hook
+            && !Python::with_gil(|py| hook.bind(py).is_callable())
+        {
+            return Err(PyValueError::new_err("hook is not callable"));
+        }
+        match self.config.load_hook.lock() {
+            Ok(mut current) => *current = hook,
+            Err(e) => return Err(PyRuntimeError::new_err(e.to_string())),
+        }
+        Ok(())
+    }
+
+    /// Names of the rustyscript extensions whose globals are present in this runtime
+    pub fn supported_features(&self) -> PyResult<Vec<String>> {
+        match self
+            .runtime
+            .lock(self.lock_timeout)?
+            .eval(scripts::SUPPORTED_FEATURES)
+        {
+            Ok(features) => Ok(features),
+            Err(e) => Err(self.js_error(e)),
+        }
+    }
+
+    /// The enumerable properties of `globalThis` that can be written as json,
+    /// converted to python. Functions and anything json can't hold are skipped.
+    pub fn dump_globals(&self, py: Python<'_>) -> PyResult<Py<PyDict>> {
+        let globals: serde_json::Value = match self
+            .runtime
+            .lock(self.lock_timeout)?
+            .eval(scripts::DUMP_GLOBALS)
+        {
+            Ok(globals) => globals,
+            Err(e) => return Err(self.js_error(e)),
+        };
+        Ok(to_pyobject(py, &globals)?
+            .downcast_into::<PyDict>()?
+            .unbind())
+    }
+
+    /// Names of the python functions currently exposed to javascript, sorted
+    pub fn registered_functions(&self) -> PyResult<Vec<String>> {
+        self.check_open()?;
+        let mut names: Vec<_> = self
+            .functions
+            .keys()
+            .chain(self.async_functions.keys())
+            .cloned()
+            .collect();
+        names.sort();
+        Ok(names)
+    }
+
+    /// Removes a function added with `register_function` so calling it from
+    /// javascript throws a `ReferenceError`.
+    pub fn unregister_function(&mut self, name: &str) -> PyResult<()> {
+        self.check_open()?;
+        let removed = self.functions.remove(name).is_some();
+        if !removed && self.async_functions.remove(name).is_none() {
+            return Err(PyKeyError::new_err(name.to_string()));
+        }
+        let mut rt = self.runtime.lock(self.lock_timeout)?;
+        remove_function(&mut rt, name)
+    }
+
+    /// Throws away the current runtime and builds a fresh one with the same options,
+    /// registered python functions are put back in place and the current directory is kept.
+    /// Previously loaded modules and promises do not carry over.
+    pub fn recycle(&mut self) -> PyResult<()> {
+        let mut rt = self.runtime.lock(self.lock_timeout)?;
+        let current_dir = rt.current_dir().to_path_buf();
+        *rt = create_runtime(&self.config)?;
+        self.alive = Arc::new(());
+        self.timers_cleared = 0;
+        self.loaded.clear();
+        if let Err(e) = rt.set_current_dir(current_dir) {
+            return Err(PyNotADirectoryError::new_err(e.to_string()));
+        }
+        let pairs: Vec<_> = Python::with_gil(|py| {
+            self.functions
+                .iter()
+                .map(|(name, func)| (name.clone(), func.clone_ref(py)))
+                .collect()
+        });
+        install_functions(&mut rt, &pairs)?;
+        let async_pairs: Vec<_> = Python::with_gil(|py| {
+            self.async_functions
+                .iter()
+                .map(|(name, function)| (name.clone(), function.clone_ref(py)))
+                .collect()
+        });
+        async_function::install(&mut rt, &async_pairs)?;
+        if let Some(console) = &self.console {
+            console.install(&mut rt)?;
+        }
+        if let Some(errors) = &self.console_errors {
+            errors.install(&mut rt)?;
+        }
+        for (name, store) in self.shared_buffers.get()?.iter() {
+            bind_shared_buffer(&mut rt, name, store)?;
+        }
+        if !self.mounts.is_empty() {
+            self.mounts.install(&mut rt)?;
+        }
+        if let Some(now) = self.mock_time {
+            install_mock_time(&mut rt, Some(now))?;
+        }
+        match &self.rejection_handler {
+            Some(func) => install_rejection_handler(
+                &mut rt,
+                Python::with_gil(|py| func.clone_ref(py)),
+                self.config.last_error.clone(),
+            ),
+            None => Ok(()),
+        }
+    }
+
+    /// Makes `Date.now()` and `new Date()` return `epoch_ms` milliseconds since
+    /// the epoch until it's moved with `advance_mock_time`, `None` goes back
+    /// to the real clock. Timers keep running on real time.
+    pub fn set_mock_time(&mut self, epoch_ms: Option<f64>) -> PyResult<()> {
+        if let Some(epoch_ms) = epoch_ms
+            && !epoch_ms.is_finite()
+        {
+            return Err(PyValueError::new_err("epoch_ms has to be a finite number"));
+        }
+        let mut rt = self.runtime.lock(self.lock_timeout)?;
+        install_mock_time(&mut rt, epoch_ms)?;
+        self.mock_time = epoch_ms;
+        Ok(())
+    }
+
+    /// Moves the time set with `set_mock_time` forward by `ms` milliseconds
+    pub fn advance_mock_time(&mut self, ms: f64) -> PyResult<()> {
+        let Some(now) = self.mock_time else {
+            return Err(PyRuntimeError::new_err(
+                "no mock time is set, call set_mock_time first",
+            ));
+        };
+        self.set_mock_time(Some(now + ms))
+    }
+
+    /// Gives up on in-flight async work without touching globals, pending promises
+    /// made by `call_async` raise `CancelledError` and every timer is cancelled.
+    /// Nothing is rejected inside of javascript, async ops that already started
+    /// still finish in the background and run their callbacks.
+    pub fn abort_all(&mut self) -> PyResult<()> {
+        self.epoch = Arc::new(());
+        let mut rt = self.runtime.lock(self.lock_timeout)?;
+        let code = format!("{}({})", scripts::ABORT_TIMERS, self.timers_cleared);
+        match rt.eval::<u64>(code) {
+            Ok(last) => {
+                self.timers_cleared = last;
+                Ok(())
+            }
+            Err(e) => Err(self.js_error(e)),
+        }
+    }
+
+    /// Registers code for `close` to run so scripts get to clean up after
+    /// themselves, it's stopped once `timeout` seconds are up.
+    #[pyo3(signature=(code, timeout=None))]
+    pub fn set_exit_handler(&mut self, code: String, timeout: Option<f64>) -> PyResult<()> {
+        self.check_open()?;
+        self.check_source(&code)?;
+        let timeout = match Duration::try_from_secs_f64(timeout.unwrap_or(1.0)) {
+            Ok(timeout) => timeout,
+            Err(e) => return Err(PyValueError::new_err(format!("timeout: {e}"))),
+        };
+        self.exit_handler = Some((code, timeout));
+        Ok(())
+    }
+
+    /// Runs the exit handler, gives up on in-flight async work and ends the
+    /// console streams, only the first call does anything. Errors in the
+    /// handler become warnings. Every other method raises from then on, `name`
+    /// stays readable.
+    pub fn close(&mut self, py: Python<'_>) -> PyResult<()> {
+        if self.closed {
+            return Ok(());
+        }
+        self.closed = true;
+        if let Some((code, timeout)) = self.exit_handler.take() {
+            let result = {
+                let mut rt = self.runtime.lock(self.lock_timeout)?;
+                bounded(&mut rt, timeout, "exit handler", async |rt| {
+                    rt.eval_async::<Undefined>(code).await
+                })
+            };
+            if let Err(e) = result {
+                let message = CString::new(format!("exit handler failed: {e}")).unwrap_or_default();
+                PyErr::warn(py, &py.get_type::<PyRuntimeWarning>(), &message, 1)?;
+            }
+        }
+        let aborted = self.abort_all();
+        if let Some(console) = &self.console {
+            console.close();
+        }
+        self.runtime.close();
+        aborted
+    }
+
+    pub fn __enter__(slf: PyRef<'_, Self>) -> PyResult<PyRef<'_, Self>> {
+        slf.check_open()?;
+        Ok(slf)
+    }
+
+    #[pyo3(signature=(*_exc_info))]
+    pub fn __exit__(&mut self, py: Python<'_>, _exc_info: &Bound<'_, PyTuple>) -> PyResult<bool> {
+        self.close(py)?;
+        Ok(false)
+    }
+
+    /// Returns an async iterator yielding console lines as they're written,
+    /// the event loop still has to be advanced for scripts to produce any.
+    pub fn console_stream(&mut self) -> PyResult<ConsoleStream> {
+        self.check_open()?;
+        let console = match &self.console {
+            Some(console) => console.clone(),
+            None => {
+                let console = ConsoleBroadcast::default();
+                let mut rt = self.runtime.lock(self.lock_timeout)?;
+                console.install(&mut rt)?;
+                self.console = Some(console.clone());
+                console
+            }
+        };
+        console.subscribe()
+    }
+
+    /// Exposes the host directory `host_path` to scripts under `js_path` through
+    /// the `fs` global, paths outside of every mount can't be reached.
+    #[pyo3(signature=(js_path, host_path, readonly=true))]
+    pub fn mount(&mut self, js_path: &str, host_path: &str, readonly: bool) -> PyResult<()> {
+        self.check_open()?;
+        let first = self.mounts.is_empty();
+        self.mounts.add(js_path, host_path, readonly)?;
+        if first {
+            let mut rt = self.runtime.lock(self.lock_timeout)?;
+            self.mounts.install(&mut rt)?;
+        }
+        Ok(())
+    }
+
+    /// Calls `func` with the reason of any promise that rejects without a handler,
+    /// rejections it sees are treated as handled.
+    pub fn set_unhandled_rejection_handler(&mut self, func: Py<PyAny>) -> PyResult<()> {
+        Python::with_gil(|py| {
+            if !func.bind(py).is_callable() {
+                return Err(PyValueError::new_err("handler is not callable"));
+            }
+            Ok(())
+        })?;
+        let mut rt = self.runtime.lock(self.lock_timeout)?;
+        install_rejection_handler(
+            &mut rt,
+            Python::with_gil(|py| func.clone_ref(py)),
+            self.config.last_error.clone(),
+        )?;
+        self.rejection_handler = Some(func);
+        Ok(())
+    }
+
+    /// Defines a global that scripts can read but never reassign, delete or mutate
+    pub fn define_constant(&mut self, name: &str, value: &Bound<'_, PyAny>) -> PyResult<()> {
+        let value = python_to_argument(value, &self.args)?;
+        let mut rt = self.runtime.lock(self.lock_timeout)?;
+        let result = rt
+            .eval::<Function>(scripts::DEFINE_CONSTANT)
+            .and_then(|define| rt.call_stored_function::<Undefined>(None, &define, &(name, value)));
+        match result {
+            Ok(_) => Ok(()),
+            Err(e) => Err(self.js_error(e)),
+        }
+    }
+
+    /// Binds `globalThis[name]` to an `ArrayBuffer` holding a copy of `data`, the bytes
+    /// go straight into javascript's memory instead of through an array of numbers
+    pub fn put_buffer(&mut self, name: &str, data: &[u8]) -> PyResult<()> {
+        let mut rt = self.runtime.lock(self.lock_timeout)?;
+        let scope = &mut rt.deno_runtime().handle_scope();
+        let Some(key) = v8::String::new(scope, name) else {
+            return Err(PyValueError::new_err("name could not be encoded"));
+        };
+        let store = v8::ArrayBuffer::new_backing_store_from_vec(data.to_vec()).make_shared();
+        let buffer = v8::ArrayBuffer::with_backing_store(scope, &store);
+        let global = scope.get_current_context().global(scope);
+        let tc = &mut v8::TryCatch::new(scope);
+        match global.set(tc, key.into(), buffer.into()) {
+            Some(_) => Ok(()),
+            None => Err(caught_exception(tc)),
+        }
+    }
+
+    /// Binds `globalThis[name]` to a zeroed `SharedArrayBuffer` of `size` bytes that
+    /// javascript can keep mutating in place between calls
+    pub fn create_shared_buffer(&mut self, name: &str, size: usize) -> PyResult<()> {
+        let mut rt = self.runtime.lock(self.lock_timeout)?;
+        let store = {
+            let isolate = rt.deno_runtime().v8_isolate();
+            v8::SharedArrayBuffer::new_backing_store(isolate, size).make_shared()
+        };
+        bind_shared_buffer(&mut rt, name, &store)?;
+        self.shared_buffers.get()?.insert(name.to_string(), store);
+        Ok(())
+    }
+
+    /// Copies out what a buffer from `create_shared_buffer` holds right now
+    pub fn read_shared_buffer(&mut self, py: Python<'_>, name: &str) -> PyResult<Py<PyBytes>> {
+        // Held so nothing runs and writes to the buffer while it's copied
+        let _rt = self.runtime.lock(self.lock_timeout)?;
+        let buffers = self.shared_buffers.get()?;
+        let Some(store) = buffers.get(name) else {
+            return Err(PyKeyError::new_err(name.to_string()));
+        };
+        let bytes = PyBytes::new_with(py, store.len(), |data| {
+            for (byte, cell) in data.iter_mut().zip(store.iter()) {
+                *byte = cell.get();
+            }
+            Ok(())
+        })?;
+        Ok(bytes.unbind())
+    }
+
+    /// Evaluates code to an object that stays in javascript, the returned proxy
+    /// converts a property only when it's looked up.
+    pub fn eval_lazy(slf: &Bound<'_, Self>, code: &str) -> PyResult<JsObjectProxy> {
+        let ctx = slf.try_borrow()?;
+        ctx.check_source(code)?;
+        let mut rt = ctx.runtime.lock(ctx.lock_timeout)?;
+        let value = match rt.eval::<Value>(code) {
+            Ok(value) => value,
+            Err(e) => return Err(ctx.js_error(e)),
+        };
+        let released = released(&mut rt);
+        let scope = &mut rt.deno_runtime().handle_scope();
+        JsObjectProxy::new(slf, scope, value.as_v8(), &released)
+    }
+
+    /// Creates a new realm in this runtime's isolate with its own globals,
+    /// realms are cheaper than a whole new `Context` since the heap is shared.
+    pub fn create_realm(slf: &Bound<'_, Self>) -> PyResult<Realm> {
+        Realm::new(slf)
+    }
+
+    /// Evaluates code in a realm made for just this call and thrown away right
+    /// after, nothing it defines is left behind for the next snippet.
+    pub fn eval_sandbox(slf: &Bound<'_, Self>, code: &str) -> PyResult<Py<PyAny>> {
+        Realm::new(slf)?.eval(slf.py(), code)
+    }
+
+    /// Compiles code without running any of it, returning the compile error if there is one.
+    /// With `module` set the imports are resolved against the current directory as well
+    /// but nothing is loaded or evaluated.
+    #[pyo3(signature=(code, module=None))]
+    pub fn check(&self, code: &str, module: Option<bool>) -> PyResult<Option<String>> {
+        self.check_source(code)?;
+        let mut rt = self.runtime.lock(self.lock_timeout)?;
+        // Imports are resolved relative to a file in the current directory
+        let referrer = match resolve_path("__check__.js", rt.current_dir()) {
+            Ok(referrer) => referrer,
+            Err(e) => return Err(PyNotADirectoryError::new_err(e.to_string())),
+        };
+        let js = rt.deno_runtime();
+        let scope = &mut js.handle_scope();
+        let tc = &mut v8::TryCatch::new(scope);
+        let Some(source) = v8::String::new(tc, code) else {
+            return Err(PyValueError::new_err("source could not be encoded"));
+        };
+        if !module.unwrap_or(false) {
+            if v8::Script::compile(tc, source, None).is_some() {
+                return Ok(None);
+            }
+            return Ok(Some(compile_error(tc)));
+        }
+        let imports = match graph::module_imports(tc, &referrer, code)? {
+            Ok(imports) => imports,
+            Err(e) => return Ok(Some(e)),
+        };
+        for specifier in imports {
+            if let Err(e) = resolve_import(&specifier, referrer.as_str()) {
+                return Ok(Some(e.to_string()));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Resolves every module the entry depends on without evaluating any of them,
+    /// mapping resolved specifiers to the sha256 of their source and their imports.
+    pub fn resolve_graph(&mut self, entry: &JsModule) -> PyResult<Py<PyDict>> {
+        let m = entry.module.get()?;
+        let mut rt = self.runtime.lock(self.lock_timeout)?;
+        graph::resolve_graph(&mut rt, &m)
+    }
+
+    /// Compiles WebAssembly from `bytes` and exposes the exports of
+    /// an instance of it as `globalThis[name]`.
+    pub fn instantiate_wasm(&mut self, name: &str, bytes: &[u8]) -> PyResult<()> {
+        let mut rt = self.runtime.lock(self.lock_timeout)?;
+        let js = rt.deno_runtime();
+        let scope = &mut js.handle_scope();
+        let tc = &mut v8::TryCatch::new(scope);
+        let Some(module) = v8::WasmModuleObject::compile(tc, bytes) else {
+            return Err(caught_exception(tc));
+        };
+        let (Some(source), Some(name)) = (
+            v8::String::new(tc, scripts::INSTANTIATE_WASM),
+            v8::String::new(tc, name),
+        ) else {
+            return Err(PyValueError::new_err("name could not be encoded"));
+        };
+        let installer = v8::Script::compile(tc, source, None)
+            .and_then(|script| script.run(tc))
+            .and_then(|f| v8::Local::<v8::Function>::try_from(f).ok());
+        let Some(installer) = installer else {
+            return Err(caught_exception(tc));
+        };
+        let recv = v8::undefined(tc).into();
+        match installer.call(tc, recv, &[name.into(), module.into()]) {
+            Some(_) => Ok(()),
+            None => Err(caught_exception(tc)),
+        }
+    }
+
+    /// Loads in a single module, a module whose specifier was already loaded into
+    /// this context from the same source isn't evaluated again and the same
+    /// handle comes back unless `force` is set.
+    #[pyo3(signature=(module, force=None))]
+    pub fn load_module(
+        &mut self,
+        module: &JsModule,
+        force: Option<bool>,
+    ) -> PyResult<Py<JsHandle>> {
+        let source = module.contents_hash()?;
         let m = module.module.get()?;
-        match self.runtime.get()?.load_module(&m) {
-            Ok(handle) => Ok(JsHandle::new(handle)),
-            Err(e) => Err(PyRuntimeError::new_err(e.to_string())),
+        self.check_source(m.contents())?;
+        let mut rt = self.runtime.lock(self.lock_timeout)?;
+        let specifier = match resolve_path(&m.filename().to_string_lossy(), rt.current_dir()) {
+            Ok(specifier) => specifier,
+            Err(e) => return Err(PyNotADirectoryError::new_err(e.to_string())),
+        };
+        if !force.unwrap_or(false)
+            && let Some((loaded, handle)) = self.loaded.get(specifier.as_str())
+            && *loaded == source
+        {
+            return Ok(Python::with_gil(|py| handle.clone_ref(py)));
+        }
+        let resolution = loader::Resolution {
+            import_map: &self.config.import_map,
+            packages: self.config.packages.as_deref(),
+        };
+        let prepared = loader::prepare(&m, resolution, &specifier)?;
+        loader::notify(&self.config.load_hook, specifier.as_str());
+        let handle = match rt.load_module(prepared.as_ref().unwrap_or(&*m)) {
+            Ok(handle) => JsHandle::new(handle, specifier.clone()),
+            Err(e) => return Err(self.js_error(e)),
+        };
+        if self.config.shared_module_cache {
+            module_cache::share(&specifier, &m, resolution)?;
+            module_cache::fill(&mut rt)?;
         }
+        let handle = Python::with_gil(|py| Py::new(py, handle))?;
+        let kept = Python::with_gil(|py| handle.clone_ref(py));
+        self.loaded.insert(specifier.to_string(), (source, kept));
+        Ok(handle)
+    }
+
+    /// Evaluates a loaded module again from `new_contents`, `handle` and every
+    /// other reference to it then point at the new module. Modules loaded with
+    /// `load_module` that import it, directly or through each other, are
+    /// evaluated again too so their imports bind to the new exports. Anything
+    /// else that imported it keeps the old module's bindings.
+    pub fn reload_module(
+        &mut self,
+        py: Python<'_>,
+        handle: PyRef<'_, JsHandle>,
+        new_contents: String,
+    ) -> PyResult<()> {
+        if !self.loaded.contains_key(handle.specifier.as_str()) {
+            return Err(PyValueError::new_err(
+                "the module wasn't loaded by this context",
+            ));
+        }
+        let filename = handle.handle()?.module().filename().to_path_buf();
+        let source = format!("{:x}", Sha256::digest(new_contents.as_bytes()));
+        self.replace_module(&handle, &Module::new(filename, new_contents))?;
+        if let Some((loaded, _)) = self.loaded.get_mut(handle.specifier.as_str()) {
+            *loaded = source;
+        }
+        let loaded: Vec<_> = self
+            .loaded
+            .values()
+            .map(|(_, importer)| importer.clone_ref(py))
+            .collect();
+        let mut reloaded = vec![handle.specifier.clone()];
+        let mut next = 0;
+        while let Some(target) = reloaded.get(next).cloned() {
+            next += 1;
+            for importer in &loaded {
+                let importer = importer.borrow(py);
+                if reloaded.contains(&importer.specifier) {
+                    continue;
+                }
+                let module = importer.module.get()?.module().clone();
+                let media_type = MediaType::from_path(module.filename());
+                let imports_target = import_map::specifiers(module.contents(), media_type)
+                    .iter()
+                    .any(|(_, specifier)| {
+                        resolve_import(specifier, importer.specifier.as_str())
+                            .is_ok_and(|resolved| resolved == target)
+                    });
+                if imports_target {
+                    self.replace_module(&importer, &module)?;
+                    reloaded.push(importer.specifier.clone());
+                }
+            }
+        }
+        Ok(())
     }
 
     /// Assuming the js function called is async, this will return a Promise to walk upon when the eventloop has the chance to use it...
@@ -411,13 +2625,13 @@ impl Context {
         name: String,
         py_args: &Bound<'_, PyTuple>,
     ) -> PyResult<JSPromise> {
-        let mut rt = self.runtime.get()?;
-        let args = python_args_to_serde(py_args)?;
-        let res: Result<Promise<serde_json::Value>, RSError> = rt.call_function(None, &name, &args);
+        let mut rt = self.runtime.lock(self.lock_timeout)?;
+        let args = python_args_to_serde(py_args, &self.args)?;
+        let res: Result<Promise<Value>, RSError> = rt.call_function(None, &name, &args);
 
         match res {
-            Ok(r) => Ok(JSPromise::new(r)),
-            Err(e) => Err(PyRuntimeError::new_err(e.to_string())),
+            Ok(r) => Ok(JSPromise::new(r, &self.alive, &self.epoch)),
+            Err(e) => Err(self.js_error(e)),
         }
     }
     #[pyo3(signature=(module, name, *py_args))]
@@ -427,25 +2641,140 @@ impl Context {
         name: String,
         py_args: &Bound<'_, PyTuple>,
     ) -> PyResult<JSPromise> {
-        let mut rt = self.runtime.get()?;
-        let mc = module.module.get()?;
-        let args = python_args_to_serde(py_args)?;
-        let res: Result<Promise<serde_json::Value>, RSError> =
-            rt.call_function(Some(&mc), &name, &args);
+        let mut rt = self.runtime.lock(self.lock_timeout)?;
+        let mc = module.handle()?;
+        let args = python_args_to_serde(py_args, &self.args)?;
+        let res: Result<Promise<Value>, RSError> = rt.call_function(Some(&mc), &name, &args);
 
         match res {
-            Ok(r) => Ok(JSPromise::new(r)),
-            Err(e) => Err(PyRuntimeError::new_err(e.to_string())),
+            Ok(r) => Ok(JSPromise::new(r, &self.alive, &self.epoch)),
+            Err(e) => Err(self.js_error(e)),
         }
     }
+
+    /// Drives the event loop until the promise settles and returns its result.
+    /// `on_tick` is called with the seconds elapsed after every tick,
+    /// returning `False` from it stops waiting with a `CancelledError`.
+    /// `total_timeout` in seconds caps the whole drive, whatever is running
+    /// when it passes is terminated and a `TimeoutError` raised.
+    #[pyo3(signature=(promise, on_tick=None, total_timeout=None))]
+    pub fn run_until_complete(
+        &mut self,
+        mut promise: PyRefMut<'_, JSPromise>,
+        on_tick: Option<Py<PyAny>>,
+        total_timeout: Option<f64>,
+    ) -> PyResult<Py<PyAny>> {
+        let total = match total_timeout.map(Duration::try_from_secs_f64) {
+            Some(Ok(total)) => Some(total),
+            Some(Err(e)) => return Err(PyValueError::new_err(format!("total_timeout: {e}"))),
+            None => None,
+        };
+        let started = Instant::now();
+        let this = &*self;
+        let mut rt = this.runtime.lock(this.lock_timeout)?;
+        let mut drive = async |rt: &mut Runtime| loop {
+            if let Some(total) = total
+                && started.elapsed() >= total
+            {
+                return Err(RSError::Timeout(String::new()));
+            }
+            let more = rt
+                .advance_event_loop_async(PollEventLoopOptions::default())
+                .await?;
+            if let Some(done) = this
+                .tick(rt, &mut promise, more, on_tick.as_ref(), started)
+                .transpose()
+            {
+                return Ok(done);
+            }
+        };
+        // A single tick can run forever too, so the deadline is kept from
+        // another thread as well
+        let result = match total {
+            Some(total) => bounded(&mut rt, total, "run_until_complete", drive),
+            None => rt.tokio_runtime().block_on(drive(&mut rt)),
+        };
+        drop(rt);
+        match result {
+            Ok(result) => result,
+            Err(e) => {
+                let error = self.js_error(e);
+                self.config.last_error.set(&error);
+                Err(error)
+            }
+        }
+    }
+}
+
+/// The version of v8 the runtime is built on
+#[pyfunction]
+pub fn v8_version() -> &'static str {
+    v8::V8::get_version()
+}
+
+/// The version of deno_core the runtime is built on
+#[pyfunction]
+pub fn deno_core_version() -> &'static str {
+    env!("PYRV8_DENO_CORE_VERSION")
+}
+
+/// The version of rustyscript the runtime is built on
+#[pyfunction]
+pub fn rustyscript_version() -> &'static str {
+    env!("PYRV8_RUSTYSCRIPT_VERSION")
+}
+
+/// Collects the results of promises that have already settled, raising the
+/// first exception among them unless `return_exceptions` puts them in the list
+#[pyfunction]
+#[pyo3(signature=(promises, return_exceptions=None))]
+fn gather_results(
+    py: Python<'_>,
+    promises: Vec<PyRef<'_, JSPromise>>,
+    return_exceptions: Option<bool>,
+) -> PyResult<Vec<Py<PyAny>>> {
+    let inline = return_exceptions.unwrap_or(false);
+    promises
+        .iter()
+        .map(|promise| match &promise.result {
+            Some(Ok(value)) => Ok(value.clone_ref(py)),
+            Some(Err(e)) if inline => Ok(e.clone_ref(py).into_value(py).into_any()),
+            Some(Err(e)) => Err(e.clone_ref(py)),
+            None => Err(InvalidStateError::new_err("Result is not ready.")),
+        })
+        .collect()
 }
 
 #[pymodule]
 pub fn pyrv8(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(pool::set_shared_runtime, module)?)?;
+    module.add_function(wrap_pyfunction!(locking::enable_lock_tracing, module)?)?;
+    module.add_function(wrap_pyfunction!(v8_version, module)?)?;
+    module.add_function(wrap_pyfunction!(deno_core_version, module)?)?;
+    module.add_function(wrap_pyfunction!(rustyscript_version, module)?)?;
+    module.add_function(wrap_pyfunction!(gather_results, module)?)?;
     module.add_class::<Context>()?;
     module.add_class::<JSPromise>()?;
     module.add_class::<JsModule>()?;
     module.add_class::<JsHandle>()?;
+    module.add_class::<JsSymbol>()?;
+    module.add_class::<JsRegExp>()?;
+    module.add_class::<JsResult>()?;
+    module.add_class::<JsBuffer>()?;
+    module.add_class::<Realm>()?;
+    module.add_class::<JsObjectProxy>()?;
+    module.add_class::<ConsoleStream>()?;
+    module.add_class::<CancelToken>()?;
+    module.add(
+        "InvalidStateError",
+        module.py().get_type::<InvalidStateError>(),
+    )?;
+    module.add("TimeoutError", module.py().get_type::<TimeoutError>())?;
+    module.add("JsConsoleError", module.py().get_type::<JsConsoleError>())?;
+    module.add(
+        "ModuleResolutionError",
+        module.py().get_type::<ModuleResolutionError>(),
+    )?;
 
     Ok(())
 }
@@ -0,0 +1,300 @@
+//! The import provider every context's module loader goes through, it lets
+//! modules import json files, applies the import map and package exports,
+//! reports loads to the load hook and lets the shared module cache attach
+//! code caches.
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use deno_ast::{swc::parser::token::Token, MediaType, TokenOrComment};
+use pyo3::{exceptions::PyValueError, prelude::*};
+use rustyscript::{
+    deno_core::{
+        error::ModuleLoaderError, ModuleSource, ModuleSourceCode, ModuleSpecifier, ModuleType,
+        RequestedModuleType, ResolutionKind,
+    },
+    module_loader::ImportProvider,
+    Module,
+};
+
+use crate::{
+    import_map::{rewrite_specifiers, ImportMap},
+    module_cache::SharedModuleCache,
+    packages::{in_node_modules, Packages},
+    transpile::transpile,
+};
+
+/// The callable from `set_module_load_hook`, shared between a context and its loader
+pub type LoadHook = Arc<Mutex<Option<Py<PyAny>>>>;
+
+/// Tells the load hook that `specifier` is about to be evaluated. The hook can't
+/// change what gets loaded so anything it raises goes to `sys.unraisablehook`.
+pub fn notify(hook: &LoadHook, specifier: &str) {
+    Python::with_gil(|py| {
+        // Cloned out so the hook is free to replace itself
+        let hook = match hook.lock() {
+            Ok(hook) => hook.as_ref().map(|hook| hook.clone_ref(py)),
+            Err(_) => None,
+        };
+        let Some(hook) = hook else {
+            return;
+        };
+        let hook = hook.bind(py);
+        if let Err(e) = hook.call1((specifier,)) {
+            e.write_unraisable(py, Some(hook));
+        }
+    });
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("json"))
+}
+
+/// Rewrites the deprecated `assert { type: 'json' }` clauses of imports and
+/// exports to `with`, v8 no longer parses `assert`. The keyword is padded so
+/// every position in the source stays where it was.
+pub fn rewrite_import_assertions(code: &str) -> Option<String> {
+    if !code.contains("assert") {
+        return None;
+    }
+    let tokens: Vec<_> = deno_ast::lex(code, MediaType::JavaScript)
+        .into_iter()
+        .filter(|item| matches!(item.inner, TokenOrComment::Token(_)))
+        .collect();
+    let mut rewritten: Option<String> = None;
+    for window in tokens.windows(3) {
+        let [source, keyword, brace] = window else {
+            continue;
+        };
+        // Only a module specifier followed by `assert {` on the same line is a clause
+        if !matches!(source.inner, TokenOrComment::Token(Token::Str { .. }))
+            || &code[keyword.range.clone()] != "assert"
+            || !matches!(brace.inner, TokenOrComment::Token(Token::LBrace))
+            || code[source.range.end..keyword.range.start].contains('\n')
+        {
+            continue;
+        }
+        rewritten
+            .get_or_insert_with(|| code.to_string())
+            .replace_range(keyword.range.clone(), "with  ");
+    }
+    rewritten
+}
+
+/// How the bare specifiers of a module are resolved, the import map is
+/// tried before packages
+#[derive(Clone, Copy)]
+pub struct Resolution<'a> {
+    pub import_map: &'a ImportMap,
+    pub packages: Option<&'a Packages>,
+}
+
+/// Applies both rewrites, `None` when neither changed anything. `referrer`
+/// is the module the code belongs to.
+fn rewrite(
+    code: &str,
+    media_type: MediaType,
+    resolution: Resolution<'_>,
+    referrer: &ModuleSpecifier,
+) -> Option<String> {
+    let asserted = rewrite_import_assertions(code);
+    let code = asserted.as_deref().unwrap_or(code);
+    let rewritten = match resolution.packages {
+        Some(packages) => rewrite_specifiers(code, media_type, |specifier| {
+            resolution
+                .import_map
+                .lookup(specifier)
+                .or_else(|| packages.resolve(specifier, referrer))
+        }),
+        None => resolution.import_map.rewrite(code, media_type),
+    };
+    rewritten.or(asserted)
+}
+
+/// The code `post_process` ends up with when `module` is imported rather than
+/// loaded from rust, `None` for json which gets no code cache
+pub fn imported_code(
+    module: &Module,
+    resolution: Resolution<'_>,
+    specifier: &ModuleSpecifier,
+) -> Option<String> {
+    if is_json(module.filename()) {
+        return None;
+    }
+    let code = transpile(specifier, module.contents()).ok()?;
+    Some(rewrite(&code, MediaType::JavaScript, resolution, specifier).unwrap_or(code))
+}
+
+/// Turns a module before it's loaded into whatever v8 should actually see,
+/// json files become a module with the parsed json as their default export.
+/// It's parsed by `JSON.parse` rather than written out as an object literal,
+/// which would turn a `__proto__` key into the object's prototype.
+pub fn prepare(
+    module: &Module,
+    resolution: Resolution<'_>,
+    specifier: &ModuleSpecifier,
+) -> PyResult<Option<Module>> {
+    if is_json(module.filename()) {
+        if let Err(e) = serde_json::from_str::<serde::de::IgnoredAny>(module.contents()) {
+            let message = format!("{}: {e}", module.filename().display());
+            return Err(PyValueError::new_err(message));
+        }
+        let text = serde_json::Value::String(module.contents().to_string());
+        let code = format!("export default JSON.parse({text});");
+        return Ok(Some(Module::new(module.filename(), code)));
+    }
+    let media_type = MediaType::from_path(module.filename());
+    Ok(
+        rewrite(module.contents(), media_type, resolution, specifier)
+            .map(|code| Module::new(module.filename(), code)),
+    )
+}
+
+pub struct Loader {
+    shared_cache: Option<SharedModuleCache>,
+    hook: LoadHook,
+    import_map: Arc<ImportMap>,
+    packages: Option<Arc<Packages>>,
+    /// Directories of the modules loaded from rust, json and packages are only
+    /// let through from inside them
+    roots: Vec<PathBuf>,
+    max_source_bytes: Option<usize>,
+}
+
+impl Loader {
+    pub fn new(
+        shared_module_cache: bool,
+        hook: LoadHook,
+        import_map: Arc<ImportMap>,
+        packages: Option<Arc<Packages>>,
+        max_source_bytes: Option<usize>,
+    ) -> Self {
+        Self {
+            shared_cache: shared_module_cache.then_some(SharedModuleCache),
+            hook,
+            import_map,
+            packages,
+            roots: Vec::new(),
+            max_source_bytes,
+        }
+    }
+
+    /// Whether `referrer` is a module inside the root `path` is in, `"."`
+    /// stands for rust loading `path` itself
+    fn beside_root(&self, path: &Path, referrer: &str) -> bool {
+        if referrer == "." {
+            return true;
+        }
+        let Some(referrer) = ModuleSpecifier::parse(referrer)
+            .ok()
+            .and_then(|referrer| referrer.to_file_path().ok())
+        else {
+            return false;
+        };
+        self.roots
+            .iter()
+            .any(|root| referrer.starts_with(root) && path.starts_with(root))
+    }
+
+    fn resolution(&self) -> Resolution<'_> {
+        Resolution {
+            import_map: &self.import_map,
+            packages: self.packages.as_deref(),
+        }
+    }
+}
+
+impl ImportProvider for Loader {
+    fn resolve(
+        &mut self,
+        specifier: &ModuleSpecifier,
+        referrer: &str,
+        _kind: ResolutionKind,
+    ) -> Option<Result<ModuleSpecifier, ModuleLoaderError>> {
+        let path = specifier.to_file_path().ok();
+        if referrer == "."
+            && let Some(root) = path.as_deref().and_then(Path::parent)
+            && !self.roots.iter().any(|r| r == root)
+        {
+            self.roots.push(root.to_path_buf());
+        }
+        // Json can't run anything so files next to the modules loaded from rust
+        // are let through, they still only load when imported `with { type: 'json' }`
+        if let Some(path) = &path
+            && is_json(path)
+            && self.beside_root(path, referrer)
+        {
+            return Some(Ok(specifier.clone()));
+        }
+        // Mapping a specifier is as good as loading what it maps to
+        if self.import_map.allows(specifier) {
+            return Some(Ok(specifier.clone()));
+        }
+        // Packages and whatever they import from inside their own directory
+        if self.packages.is_some()
+            && let Some(path) = &path
+            && in_node_modules(path, &self.roots)
+        {
+            return Some(Ok(specifier.clone()));
+        }
+        None
+    }
+
+    fn import(
+        &mut self,
+        specifier: &ModuleSpecifier,
+        referrer: Option<&ModuleSpecifier>,
+        _is_dyn_import: bool,
+        _requested_module_type: RequestedModuleType,
+    ) -> Option<Result<String, ModuleLoaderError>> {
+        // Said here since the filesystem error doesn't name the import
+        if let Ok(path) = specifier.to_file_path()
+            && !path.exists()
+        {
+            let message = match referrer {
+                Some(referrer) => format!("Module not found \"{specifier}\" from \"{referrer}\""),
+                None => format!("Module not found \"{specifier}\""),
+            };
+            return Some(Err(ModuleLoaderError::generic(message)));
+        }
+        // Checked before anything is read, the same limit eval holds code to
+        if let Some(limit) = self.max_source_bytes
+            && let Ok(path) = specifier.to_file_path()
+            && let Ok(size) = std::fs::metadata(&path).map(|m| m.len())
+            && size > limit as u64
+        {
+            let message =
+                format!("\"{specifier}\" is {size} bytes which exceeds max_source_bytes ({limit})");
+            return Some(Err(ModuleLoaderError::generic(message)));
+        }
+        None
+    }
+
+    fn post_process(
+        &mut self,
+        specifier: &ModuleSpecifier,
+        mut source: ModuleSource,
+    ) -> Result<ModuleSource, ModuleLoaderError> {
+        notify(&self.hook, specifier.as_str());
+        if source.module_type == ModuleType::Json {
+            return Ok(source);
+        }
+        // Already transpiled by now
+        if let ModuleSourceCode::String(code) = &source.code
+            && let Some(code) = rewrite(
+                code.as_str(),
+                MediaType::JavaScript,
+                self.resolution(),
+                specifier,
+            )
+        {
+            source.code = ModuleSourceCode::String(code.into());
+        }
+        match &mut self.shared_cache {
+            Some(cache) => cache.post_process(specifier, source),
+            None => Ok(source),
+        }
+    }
+}
@@ -0,0 +1,38 @@
+//! Pinning the locale `Intl` falls back on for a single runtime. ICU only has a
+//! process wide default, but v8 reads it once per isolate and keeps it, so it's
+//! swapped in just long enough for the new isolate to pick it up.
+use std::sync::Mutex;
+
+use pyo3::{exceptions::PyValueError, prelude::*};
+use rustyscript::{deno_core::v8, Error as RSError, Runtime, Undefined};
+
+use crate::scripts;
+
+/// Keeps other runtimes from being made while the ICU default is swapped out
+static ICU_DEFAULT: Mutex<()> = Mutex::new(());
+
+/// Creates a runtime with `create` whose `Intl` apis default to `locale`
+pub fn with_default_locale(
+    locale: &str,
+    create: impl FnOnce() -> PyResult<Runtime>,
+) -> PyResult<Runtime> {
+    if locale.contains('\0') {
+        return Err(PyValueError::new_err("default_locale can't contain a nul"));
+    }
+    let name = serde_json::to_string(locale).map_err(|e| PyValueError::new_err(e.to_string()))?;
+    let _guard = ICU_DEFAULT.lock().unwrap_or_else(|e| e.into_inner());
+    let previous = v8::icu::get_language_tag();
+    v8::icu::set_default_locale(locale);
+    let pinned = create().and_then(|mut runtime| {
+        match runtime.eval::<Undefined>(format!("{}({name})", scripts::PIN_LOCALE)) {
+            Ok(_) => Ok(runtime),
+            Err(RSError::JsError(e)) => Err(PyValueError::new_err(format!(
+                "invalid default_locale {locale}: {}",
+                e.exception_message
+            ))),
+            Err(e) => Err(crate::js_error(e)),
+        }
+    });
+    v8::icu::set_default_locale(&previous);
+    pinned
+}
@@ -1,26 +1,271 @@
-use pyo3::exceptions::PyRuntimeError;
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
 use pyo3::prelude::*;
 use pyo3::sync::MutexExt;
+use std::any::Any;
 use std::marker::{Send, Sync};
-use std::sync::{Mutex, MutexGuard};
+use std::mem::ManuallyDrop;
+use std::ops::{Deref, DerefMut};
+use std::panic::Location;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, TryLockError};
+use std::thread::{self, ThreadId};
+use std::time::{Duration, Instant};
+
+/// How long `get_timeout` sleeps between attempts at the lock
+const LOCK_POLL: Duration = Duration::from_millis(1);
+
+/// Checked first so locking costs nothing extra while tracing is off
+static TRACING: AtomicBool = AtomicBool::new(false);
+static TRACE_HOOK: Mutex<Option<Py<PyAny>>> = Mutex::new(None);
+
+/// Calls the tracing hook with what happened to a lock and where from, whatever
+/// the hook raises goes to `sys.unraisablehook`
+fn trace(py: Python<'_>, event: &str, site: &Location<'_>) {
+    let hook = match TRACE_HOOK.lock() {
+        Ok(hook) => hook.as_ref().map(|hook| hook.clone_ref(py)),
+        Err(_) => None,
+    };
+    let Some(hook) = hook else {
+        return;
+    };
+    let hook = hook.bind(py);
+    if let Err(e) = hook.call1((event, site.to_string())) {
+        e.write_unraisable(py, Some(hook));
+    }
+}
+
+/// Calls `callback(event, site)` every time one of pyrv8's locks is waited on,
+/// acquired or released, `None` turns tracing back off.
+#[pyfunction]
+#[pyo3(signature = (callback))]
+pub fn enable_lock_tracing(callback: Option<Bound<'_, PyAny>>) -> PyResult<()> {
+    if let Some(callback) = &callback
+        && !callback.is_callable()
+    {
+        return Err(PyValueError::new_err("callback is not callable"));
+    }
+    let mut hook = match TRACE_HOOK.lock() {
+        Ok(hook) => hook,
+        Err(e) => return Err(PyRuntimeError::new_err(e.to_string())),
+    };
+    TRACING.store(callback.is_some(), Ordering::Release);
+    *hook = callback.map(Bound::unbind);
+    Ok(())
+}
 
 /// GIL Locked System allowing pyo3 to accept
 /// the unsyncable types
 pub struct GIL<T> {
     mt: Mutex<T>,
+    /// The only thread allowed to take the lock, when pinned
+    owner: Option<ThreadId>,
+    /// Set by `close`, the lock can't be taken from then on
+    closed: AtomicBool,
 }
 
 impl<T> GIL<T> {
     pub fn new(t: T) -> Self {
-        Self { mt: Mutex::new(t) }
+        Self {
+            mt: Mutex::new(t),
+            owner: None,
+            closed: AtomicBool::new(false),
+        }
     }
-    pub fn get(&self) -> PyResult<MutexGuard<'_, T>> {
-        Python::with_gil(|py| match self.mt.lock_py_attached(py) {
-            Ok(r) => Ok(r),
-            Err(e) => Err(PyRuntimeError::new_err(e.to_string())),
+
+    /// A lock that raises when it's taken from any thread but the current one
+    pub fn pinned(t: T) -> Self {
+        Self {
+            mt: Mutex::new(t),
+            owner: Some(thread::current().id()),
+            closed: AtomicBool::new(false),
+        }
+    }
+
+    /// Turns every later attempt at the lock away with "context closed"
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::Release);
+    }
+
+    fn check(&self) -> PyResult<()> {
+        if self.closed.load(Ordering::Acquire) {
+            return Err(PyRuntimeError::new_err("context closed"));
+        }
+        match self.owner {
+            Some(owner) if owner != thread::current().id() => {
+                Err(PyRuntimeError::new_err(format!(
+                    "runtime is pinned to thread {owner:?} it was made on, it can't be used from {:?}",
+                    thread::current().id()
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    #[track_caller]
+    pub fn get(&self) -> PyResult<Guard<'_, T>> {
+        self.check()?;
+        let site = Location::caller();
+        Python::with_gil(|py| {
+            let traced = TRACING.load(Ordering::Acquire);
+            if traced {
+                trace(py, "waiting", site);
+            }
+            match self.mt.lock_py_attached(py) {
+                Ok(r) => Ok(Guard::new(py, r, traced.then_some(site))),
+                Err(e) => Err(PyRuntimeError::new_err(e.to_string())),
+            }
         })
     }
+
+    /// Like [`GIL::get`] but raises "runtime busy" once the lock has been held
+    /// by someone else for longer than `timeout`, `None` waits forever
+    #[track_caller]
+    pub fn get_timeout(&self, timeout: Option<Duration>) -> PyResult<Guard<'_, T>> {
+        let Some(timeout) = timeout else {
+            return self.get();
+        };
+        self.check()?;
+        let site = Location::caller();
+        let deadline = Instant::now() + timeout;
+        Python::with_gil(|py| {
+            let traced = TRACING.load(Ordering::Acquire);
+            if traced {
+                trace(py, "waiting", site);
+            }
+            loop {
+                match self.mt.try_lock() {
+                    Ok(r) => return Ok(Guard::new(py, r, traced.then_some(site))),
+                    Err(TryLockError::Poisoned(e)) => {
+                        return Err(PyRuntimeError::new_err(e.to_string()))
+                    }
+                    Err(TryLockError::WouldBlock) => {}
+                }
+                let now = Instant::now();
+                if now >= deadline {
+                    return Err(PyRuntimeError::new_err("runtime busy"));
+                }
+                // The holder may well need the GIL to finish up
+                let pause = LOCK_POLL.min(deadline - now);
+                py.allow_threads(|| std::thread::sleep(pause));
+            }
+        })
+    }
+}
+
+/// The lock held by a [`GIL::get`] call, reporting its release when tracing
+/// was on for the acquire
+pub struct Guard<'a, T> {
+    guard: ManuallyDrop<MutexGuard<'a, T>>,
+    site: Option<&'static Location<'static>>,
+}
+
+impl<'a, T> Guard<'a, T> {
+    fn new(
+        py: Python<'_>,
+        guard: MutexGuard<'a, T>,
+        site: Option<&'static Location<'static>>,
+    ) -> Self {
+        if let Some(site) = site {
+            trace(py, "acquired", site);
+        }
+        Self {
+            guard: ManuallyDrop::new(guard),
+            site,
+        }
+    }
+}
+
+impl<T> Deref for Guard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T> DerefMut for Guard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+impl<T> Drop for Guard<'_, T> {
+    fn drop(&mut self) {
+        // Released first so the hook never runs with the lock still held
+        unsafe { ManuallyDrop::drop(&mut self.guard) };
+        if let Some(site) = self.site {
+            Python::with_gil(|py| trace(py, "released", site));
+        }
+    }
 }
 
 unsafe impl<T> Sync for GIL<T> {}
 unsafe impl<T> Send for GIL<T> {}
+
+/// Carries a value into `Python::allow_threads`, only sound as long as
+/// the value is used on the thread that detached it.
+pub struct Detached<T>(T);
+
+impl<T> Detached<T> {
+    pub fn new(t: T) -> Self {
+        Self(t)
+    }
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+unsafe impl<T> Send for Detached<T> {}
+
+/// Values of a runtime let go of by whichever thread dropped them, they're kept
+/// until the runtime is next locked since dropping one touches its isolate.
+#[derive(Clone, Default)]
+pub struct Released(Arc<Mutex<Vec<Box<dyn Any>>>>);
+
+impl Released {
+    fn release<T: 'static>(&self, value: T) {
+        let mut values = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        values.push(Box::new(value));
+    }
+
+    /// Drops what was released, only to be called with the runtime locked
+    pub fn drain(&self) {
+        let values = std::mem::take(&mut *self.0.lock().unwrap_or_else(PoisonError::into_inner));
+        drop(values);
+    }
+}
+
+unsafe impl Send for Released {}
+unsafe impl Sync for Released {}
+
+/// A value of a runtime held from outside of it, only used with the runtime
+/// locked and handed to its [`Released`] once dropped.
+pub struct Held<T: 'static> {
+    value: ManuallyDrop<T>,
+    released: Released,
+}
+
+impl<T: 'static> Held<T> {
+    pub fn new(value: T, released: &Released) -> Self {
+        Self {
+            value: ManuallyDrop::new(value),
+            released: released.clone(),
+        }
+    }
+}
+
+impl<T: 'static> Deref for Held<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T: 'static> Drop for Held<T> {
+    fn drop(&mut self) {
+        let value = unsafe { ManuallyDrop::take(&mut self.value) };
+        self.released.release(value);
+    }
+}
+
+unsafe impl<T: 'static> Send for Held<T> {}
+unsafe impl<T: 'static> Sync for Held<T> {}
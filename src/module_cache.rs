@@ -0,0 +1,176 @@
+//! V8 code caches shared by every context in the process that asks for them.
+//! Compiled modules are tied to their isolate but code caches aren't, so a module
+//! compiled once lets the other contexts skip most of the work. Sources are always
+//! read by each context itself, a cache is only used for the exact text it was made from.
+use std::{
+    borrow::Cow,
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    sync::{Arc, LazyLock, Mutex},
+};
+
+use pyo3::{exceptions::PyRuntimeError, prelude::*};
+use rustyscript::{
+    deno_core::{
+        error::ModuleLoaderError, v8, ModuleSource, ModuleSourceCode, ModuleSpecifier,
+        SourceCodeCacheInfo,
+    },
+    module_loader::ImportProvider,
+    Module, Runtime,
+};
+
+use crate::loader::{self, Resolution};
+
+/// Past this many code caches the least recently used ones are let go
+const MODULE_CACHE_LIMIT: usize = 1024;
+
+/// The specifier of a module and a hash of the source its code cache was made from
+type CacheKey = (String, u64);
+
+/// Code caches along with when each was last used
+#[derive(Default)]
+struct CodeCaches {
+    entries: HashMap<CacheKey, (Arc<[u8]>, u64)>,
+    clock: u64,
+}
+
+impl CodeCaches {
+    fn get(&mut self, key: &CacheKey) -> Option<Arc<[u8]>> {
+        self.clock += 1;
+        let (data, used) = self.entries.get_mut(key)?;
+        *used = self.clock;
+        Some(data.clone())
+    }
+
+    fn insert(&mut self, key: CacheKey, data: Arc<[u8]>) {
+        self.clock += 1;
+        if !self.entries.contains_key(&key) && self.entries.len() >= MODULE_CACHE_LIMIT {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert(key, (data, self.clock));
+    }
+}
+
+static CODE_CACHES: LazyLock<Mutex<CodeCaches>> = LazyLock::new(Default::default);
+
+/// Modules that were loaded without a code cache, waiting for `fill` to make one
+static PENDING: Mutex<Vec<(String, u64, String)>> = Mutex::new(Vec::new());
+
+fn source_hash(code: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    code.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Hands the loader code caches another context already made for the same source.
+/// Only modules reached through imports go through the loader, the module
+/// passed to `load_module` itself is always compiled from scratch.
+pub struct SharedModuleCache;
+
+impl ImportProvider for SharedModuleCache {
+    fn post_process(
+        &mut self,
+        specifier: &ModuleSpecifier,
+        mut source: ModuleSource,
+    ) -> Result<ModuleSource, ModuleLoaderError> {
+        let ModuleSourceCode::String(code) = &source.code else {
+            return Ok(source);
+        };
+        let hash = source_hash(code.as_str());
+        let key = (specifier.to_string(), hash);
+        let cached = CODE_CACHES
+            .lock()
+            .ok()
+            .and_then(|mut caches| caches.get(&key));
+        match cached {
+            Some(data) => {
+                source.code_cache = Some(SourceCodeCacheInfo {
+                    hash,
+                    data: Some(Cow::Owned(data.to_vec())),
+                });
+            }
+            None => {
+                if let Ok(mut pending) = PENDING.lock() {
+                    pending.push((key.0, hash, code.as_str().to_string()));
+                }
+            }
+        }
+        Ok(source)
+    }
+}
+
+/// Queues a module loaded into a context for a code cache, made by the next `fill`.
+/// `module` is the one handed to `load_module`, before any rewriting.
+pub fn share(
+    specifier: &ModuleSpecifier,
+    module: &Module,
+    resolution: Resolution<'_>,
+) -> PyResult<()> {
+    // What the loader hashes when another context imports the same source
+    let Some(code) = loader::imported_code(module, resolution, specifier) else {
+        return Ok(());
+    };
+    let mut pending = match PENDING.lock() {
+        Ok(pending) => pending,
+        Err(e) => return Err(PyRuntimeError::new_err(e.to_string())),
+    };
+    pending.push((specifier.to_string(), source_hash(&code), code));
+    Ok(())
+}
+
+/// Makes code caches for the modules loaded without one since the last call
+pub fn fill(runtime: &mut Runtime) -> PyResult<()> {
+    let pending = match PENDING.lock() {
+        Ok(mut pending) => std::mem::take(&mut *pending),
+        Err(e) => return Err(PyRuntimeError::new_err(e.to_string())),
+    };
+    if pending.is_empty() {
+        return Ok(());
+    }
+    let scope = &mut runtime.deno_runtime().handle_scope();
+    let mut made = Vec::with_capacity(pending.len());
+    for (specifier, hash, code) in pending {
+        let tc = &mut v8::TryCatch::new(scope);
+        let (Some(source), Some(name)) =
+            (v8::String::new(tc, &code), v8::String::new(tc, &specifier))
+        else {
+            continue;
+        };
+        let origin = v8::ScriptOrigin::new(
+            tc,
+            name.into(),
+            0,
+            0,
+            false,
+            0,
+            None,
+            false,
+            false,
+            true,
+            None,
+        );
+        let mut source = v8::script_compiler::Source::new(source, Some(&origin));
+        // The runtime already reported anything that fails to compile
+        let Some(module) = v8::script_compiler::compile_module(tc, &mut source) else {
+            continue;
+        };
+        if let Some(data) = module.get_unbound_module_script(tc).create_code_cache() {
+            made.push(((specifier, hash), Arc::from(&data[..])));
+        }
+    }
+    let mut caches = match CODE_CACHES.lock() {
+        Ok(caches) => caches,
+        Err(e) => return Err(PyRuntimeError::new_err(e.to_string())),
+    };
+    for (key, data) in made {
+        caches.insert(key, data);
+    }
+    Ok(())
+}
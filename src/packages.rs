@@ -0,0 +1,142 @@
+//! Bare specifiers naming packages in `node_modules`, resolved through the
+//! `exports` of their `package.json` with a set of conditions such as `"node"`
+//! or `"browser"`. Like the import map these are rewritten into the source
+//! before it's compiled.
+use std::path::{Component, Path, PathBuf};
+
+use rustyscript::deno_core::ModuleSpecifier;
+use serde_json::Value;
+
+#[derive(Debug, Clone)]
+pub struct Packages {
+    /// Tried in the order `exports` lists its conditions, `default` always matches
+    conditions: Vec<String>,
+}
+
+/// Splits `name/sub/path` into the package name and the `./sub/path` it exports,
+/// scoped packages keep their scope
+fn split(specifier: &str) -> Option<(&str, String)> {
+    let end = if specifier.starts_with('@') {
+        let scope = specifier.find('/')?;
+        specifier[scope + 1..]
+            .find('/')
+            .map_or(specifier.len(), |i| scope + 1 + i)
+    } else {
+        specifier.find('/').unwrap_or(specifier.len())
+    };
+    let (name, rest) = specifier.split_at(end);
+    if name.is_empty() || name.ends_with('/') {
+        return None;
+    }
+    Some((name, format!(".{rest}")))
+}
+
+/// Whether `path` is in a `node_modules` directory that packages imported from
+/// one of `roots` can be found in, inside the root or above it
+pub fn in_node_modules(path: &Path, roots: &[PathBuf]) -> bool {
+    let node_modules = Component::Normal("node_modules".as_ref());
+    if !path.components().any(|c| c == node_modules) {
+        return false;
+    }
+    roots.iter().any(|root| {
+        path.starts_with(root)
+            || root
+                .ancestors()
+                .any(|directory| path.starts_with(directory.join("node_modules")))
+    })
+}
+
+impl Packages {
+    pub fn new(conditions: Vec<String>) -> Self {
+        Self { conditions }
+    }
+
+    /// Picks the first target of a conditional export whose condition is set
+    fn target(&self, target: &Value, star: Option<&str>) -> Option<String> {
+        match target {
+            Value::String(target) => Some(match star {
+                Some(star) => target.replace('*', star),
+                None => target.clone(),
+            }),
+            Value::Array(targets) => targets.iter().find_map(|t| self.target(t, star)),
+            Value::Object(conditions) => conditions.iter().find_map(|(condition, target)| {
+                if condition == "default" || self.conditions.contains(condition) {
+                    self.target(target, star)
+                } else {
+                    None
+                }
+            }),
+            _ => None,
+        }
+    }
+
+    /// Where `subpath` points in a package's `exports`, subpath patterns with a
+    /// single `*` are matched by their longest prefix
+    fn export(&self, exports: &Value, subpath: &str) -> Option<String> {
+        let sugar = match exports {
+            Value::Object(map) => !map.keys().any(|key| key.starts_with('.')),
+            _ => true,
+        };
+        if sugar {
+            return (subpath == ".")
+                .then(|| self.target(exports, None))
+                .flatten();
+        }
+        let map = exports.as_object()?;
+        if let Some(target) = map.get(subpath) {
+            return self.target(target, None);
+        }
+        let (key, star) = map
+            .keys()
+            .filter_map(|key| {
+                let (prefix, suffix) = key.split_once('*')?;
+                let star = subpath.strip_prefix(prefix)?.strip_suffix(suffix)?;
+                Some((key, star))
+            })
+            .max_by_key(|(key, _)| key.len())?;
+        self.target(&map[key.as_str()], Some(star))
+    }
+
+    /// The file a package subpath resolves to inside its directory
+    fn entry(&self, directory: &Path, subpath: &str) -> Option<PathBuf> {
+        let manifest = std::fs::read_to_string(directory.join("package.json")).ok()?;
+        let manifest: Value = serde_json::from_str(&manifest).ok()?;
+        let target = match manifest.get("exports") {
+            Some(exports) => self.export(exports, subpath)?,
+            None if subpath == "." => match manifest.get("main").and_then(Value::as_str) {
+                Some(main) => main.to_string(),
+                None => "./index.js".to_string(),
+            },
+            None => subpath.to_string(),
+        };
+        // Targets can't climb out of the package
+        let target = Path::new(target.strip_prefix("./").unwrap_or(&target));
+        if target
+            .components()
+            .any(|c| matches!(c, Component::ParentDir | Component::RootDir))
+        {
+            return None;
+        }
+        Some(directory.join(target))
+    }
+
+    /// The url of the file a bare `specifier` imported from `referrer` resolves
+    /// to, looking through every `node_modules` above the referrer
+    pub fn resolve(&self, specifier: &str, referrer: &ModuleSpecifier) -> Option<String> {
+        if specifier.starts_with(['.', '/']) || ModuleSpecifier::parse(specifier).is_ok() {
+            return None;
+        }
+        let (name, subpath) = split(specifier)?;
+        let referrer = referrer.to_file_path().ok()?;
+        referrer.ancestors().skip(1).find_map(|directory| {
+            let package = directory.join("node_modules").join(name);
+            if !package.is_dir() {
+                return None;
+            }
+            let entry = self.entry(&package, &subpath)?;
+            ModuleSpecifier::from_file_path(entry)
+                .ok()
+                .map(|url| url.to_string())
+        })
+    }
+}
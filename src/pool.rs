@@ -0,0 +1,85 @@
+//! A tokio runtime that contexts can share for their async ops instead
+//! of each one spinning up its own, and the ones they get otherwise.
+use std::{cell::Cell, ptr, rc::Rc, sync::OnceLock};
+
+use pyo3::{exceptions::PyRuntimeError, ffi, prelude::*};
+use rustyscript::tokio::runtime::{Builder, Handle, Runtime};
+
+use crate::async_function;
+
+static SHARED: OnceLock<Runtime> = OnceLock::new();
+
+fn build(threads: Option<usize>) -> PyResult<Runtime> {
+    let mut builder = Builder::new_multi_thread();
+    if let Some(threads) = threads {
+        builder.worker_threads(threads);
+    }
+    builder
+        .enable_all()
+        .thread_name("pyrv8-shared")
+        .build()
+        .map_err(|e| PyRuntimeError::new_err(e.to_string()))
+}
+
+/// Hands out the shared runtime, starting it with tokio's default
+/// thread count if `set_shared_runtime` was never called.
+pub fn shared_handle() -> PyResult<Handle> {
+    if let Some(runtime) = SHARED.get() {
+        return Ok(runtime.handle().clone());
+    }
+    // Losing a race here only means our runtime gets dropped for the other one
+    let _ = SHARED.set(build(None)?);
+    match SHARED.get() {
+        Some(runtime) => Ok(runtime.handle().clone()),
+        None => Err(PyRuntimeError::new_err("shared runtime failed to start")),
+    }
+}
+
+/// Starts the runtime shared by contexts made with `shared_pool=True` using
+/// `threads` worker threads, this has to happen before any of them are made.
+#[pyfunction]
+pub fn set_shared_runtime(threads: usize) -> PyResult<()> {
+    if SHARED.get().is_some() || SHARED.set(build(Some(threads))?).is_err() {
+        return Err(PyRuntimeError::new_err(
+            "the shared runtime is already running",
+        ));
+    }
+    Ok(())
+}
+
+thread_local! {
+    /// The thread state put aside by [`release_gil`]
+    static PARKED: Cell<*mut ffi::PyThreadState> = const { Cell::new(ptr::null_mut()) };
+}
+
+fn release_gil() {
+    // Python code only has to get the GIL in the meantime while an async call runs
+    if !async_function::pending() {
+        return;
+    }
+    // Nothing runs on this thread until it's unparked
+    unsafe {
+        if ffi::PyGILState_Check() == 1 {
+            PARKED.set(ffi::PyEval_SaveThread());
+        }
+    }
+}
+
+fn reacquire_gil() {
+    let state = PARKED.replace(ptr::null_mut());
+    if !state.is_null() {
+        unsafe { ffi::PyEval_RestoreThread(state) };
+    }
+}
+
+/// A runtime for a single context, it lets go of the GIL whenever it sits idle
+/// waiting on an async python function so that function's loop gets to run.
+pub fn owned() -> PyResult<Rc<Runtime>> {
+    Builder::new_current_thread()
+        .enable_all()
+        .on_thread_park(release_gil)
+        .on_thread_unpark(reacquire_gil)
+        .build()
+        .map(Rc::new)
+        .map_err(|e| PyRuntimeError::new_err(e.to_string()))
+}
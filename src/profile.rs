@@ -0,0 +1,49 @@
+//! CPU profiles through a local inspector session, only built with the `profiler` feature.
+use pyo3::{exceptions::PyRuntimeError, prelude::*};
+use rustyscript::{
+    deno_core::{
+        InspectorSessionKind, InspectorSessionOptions, LocalInspectorSession, PollEventLoopOptions,
+    },
+    js_value::Value,
+    Error as RSError, Runtime,
+};
+
+/// Sends a message to the profiler and drives the event loop until it answers
+fn post(
+    runtime: &mut Runtime,
+    session: &mut LocalInspectorSession,
+    method: &str,
+) -> PyResult<serde_json::Value> {
+    let tokio = runtime.tokio_runtime();
+    let js = runtime.deno_runtime();
+    let reply = tokio.block_on(js.with_event_loop_future(
+        Box::pin(session.post_message::<()>(method, None)),
+        PollEventLoopOptions::default(),
+    ));
+    reply.map_err(|e| PyRuntimeError::new_err(format!("{method} failed: {e}")))
+}
+
+/// Runs `code` under the v8 CPU profiler, returning what it evaluated to along
+/// with the profile in the `.cpuprofile` format devtools loads.
+pub fn profile(runtime: &mut Runtime, code: &str) -> PyResult<(Result<Value, RSError>, String)> {
+    let js = runtime.deno_runtime();
+    js.maybe_init_inspector();
+    let mut session = js
+        .inspector()
+        .borrow()
+        .create_local_session(InspectorSessionOptions {
+            kind: InspectorSessionKind::NonBlocking {
+                wait_for_disconnect: false,
+            },
+        });
+    post(runtime, &mut session, "Profiler.enable")?;
+    post(runtime, &mut session, "Profiler.start")?;
+    let result = runtime.eval::<Value>(code);
+    let stopped = post(runtime, &mut session, "Profiler.stop");
+    post(runtime, &mut session, "Profiler.disable")?;
+    let profile = match stopped?.get("profile") {
+        Some(profile) => profile.to_string(),
+        None => return Err(PyRuntimeError::new_err("the profiler returned no profile")),
+    };
+    Ok((result, profile))
+}
@@ -0,0 +1,155 @@
+//! Objects left in javascript and read from python a property at a time, only
+//! what's accessed is ever converted.
+use std::sync::{Arc, Weak};
+
+use pyo3::{
+    exceptions::{PyKeyError, PyRuntimeError, PyTypeError},
+    prelude::*,
+};
+use rustyscript::deno_core::v8::{self, GetPropertyNamesArgsBuilder, KeyConversionMode};
+
+use crate::convert::v8_to_python;
+use crate::locking::{Held, Released};
+use crate::{caught_exception, Context, InvalidStateError};
+
+/// A javascript object kept in the runtime of the `Context` that made it
+#[pyclass]
+pub struct JsObjectProxy {
+    context: Py<Context>,
+    object: Held<v8::Global<v8::Object>>,
+    alive: Weak<()>,
+}
+
+impl JsObjectProxy {
+    pub(crate) fn new(
+        context: &Bound<'_, Context>,
+        scope: &mut v8::HandleScope<'_>,
+        value: &v8::Global<v8::Value>,
+        released: &Released,
+    ) -> PyResult<Self> {
+        let local = v8::Local::new(scope, value);
+        let Ok(object) = v8::Local::<v8::Object>::try_from(local) else {
+            let kind = local.type_of(scope).to_rust_string_lossy(scope);
+            return Err(PyTypeError::new_err(format!(
+                "eval_lazy needs an object, got {kind}"
+            )));
+        };
+        Ok(Self {
+            context: context.clone().unbind(),
+            object: Held::new(v8::Global::new(scope, object), released),
+            alive: Arc::downgrade(&context.try_borrow()?.alive),
+        })
+    }
+
+    /// Runs `f` with the object, anything thrown is turned into a `RuntimeError`
+    fn enter<F, T>(&self, py: Python<'_>, f: F) -> PyResult<T>
+    where
+        F: for<'t, 's> FnOnce(
+            &mut v8::TryCatch<'t, v8::HandleScope<'s>>,
+            &Context,
+            v8::Local<'s, v8::Object>,
+        ) -> PyResult<T>,
+    {
+        let ctx = self.context.try_borrow(py)?;
+        // Handles made in an isolate that got recycled are long gone
+        match self.alive.upgrade() {
+            Some(alive) if Arc::ptr_eq(&alive, &ctx.alive) => {}
+            _ => {
+                return Err(InvalidStateError::new_err(
+                    "JsObjectProxy belongs to a runtime that has been recycled.",
+                ));
+            }
+        }
+        let mut rt = ctx.runtime.lock(ctx.lock_timeout)?;
+        let scope = &mut rt.deno_runtime().handle_scope();
+        let local = v8::Local::new(scope, &*self.object);
+        let tc = &mut v8::TryCatch::new(scope);
+        f(tc, &ctx, local)
+    }
+}
+
+/// A python `str` or `int` as a property key
+fn property_key<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    key: &Bound<'_, PyAny>,
+) -> PyResult<v8::Local<'s, v8::Value>> {
+    if let Ok(key) = key.extract::<&str>() {
+        return match v8::String::new(scope, key) {
+            Some(key) => Ok(key.into()),
+            None => Err(PyRuntimeError::new_err("key could not be encoded")),
+        };
+    }
+    if let Ok(index) = key.extract::<i64>() {
+        return Ok(v8::Number::new(scope, index as f64).into());
+    }
+    Err(PyTypeError::new_err(format!(
+        "keys have to be str or int, not {}",
+        key.get_type().name()?
+    )))
+}
+
+#[pymethods]
+impl JsObjectProxy {
+    /// Converts the property and everything under it, missing ones raise `KeyError`
+    pub fn __getitem__(&self, py: Python<'_>, key: &Bound<'_, PyAny>) -> PyResult<Py<PyAny>> {
+        self.enter(py, |tc, ctx, object| {
+            let name = property_key(tc, key)?;
+            match object.has(tc, name) {
+                Some(true) => {}
+                Some(false) => return Err(PyKeyError::new_err(key.clone().unbind())),
+                None => return Err(caught_exception(tc)),
+            }
+            let Some(value) = object.get(tc, name) else {
+                return Err(caught_exception(tc));
+            };
+            v8_to_python(py, tc, value, &ctx.convert)
+        })
+    }
+
+    pub fn __contains__(&self, py: Python<'_>, key: &Bound<'_, PyAny>) -> PyResult<bool> {
+        self.enter(py, |tc, _, object| {
+            let name = property_key(tc, key)?;
+            match object.has(tc, name) {
+                Some(has) => Ok(has),
+                None => Err(caught_exception(tc)),
+            }
+        })
+    }
+
+    /// The object's own enumerable string keys, in the order javascript lists them
+    pub fn keys(&self, py: Python<'_>) -> PyResult<Vec<String>> {
+        self.enter(py, |tc, _, object| {
+            let args = GetPropertyNamesArgsBuilder::new()
+                .key_conversion(KeyConversionMode::ConvertToString)
+                .build();
+            let Some(keys) = object.get_own_property_names(tc, args) else {
+                return Err(caught_exception(tc));
+            };
+            let mut names = Vec::with_capacity(keys.length() as usize);
+            for i in 0..keys.length() {
+                if let Some(key) = keys.get_index(tc, i) {
+                    names.push(key.to_rust_string_lossy(tc));
+                }
+            }
+            Ok(names)
+        })
+    }
+
+    pub fn __len__(&self, py: Python<'_>) -> PyResult<usize> {
+        Ok(self.keys(py)?.len())
+    }
+
+    /// Converts the whole object the way `eval` would have
+    pub fn to_python(&self, py: Python<'_>) -> PyResult<Py<PyAny>> {
+        self.enter(py, |tc, ctx, object| {
+            v8_to_python(py, tc, object.into(), &ctx.convert)
+        })
+    }
+
+    pub fn __repr__(&self, py: Python<'_>) -> String {
+        match self.keys(py) {
+            Ok(keys) => format!("<JsObjectProxy with {} keys>", keys.len()),
+            Err(_) => "<JsObjectProxy orphaned>".to_string(),
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use std::sync::{Arc, Weak};
+
+use pyo3::{exceptions::PyRuntimeError, prelude::*, types::PyTuple};
+use rustyscript::deno_core::{serde_v8, v8, PollEventLoopOptions};
+use rustyscript::Runtime;
+
+use crate::convert::v8_to_python;
+use crate::locking::Held;
+use crate::{
+    bounded, caught_exception, python_args_to_serde, raised, released, Context, InvalidStateError,
+};
+
+/// A separate v8 context living in the same isolate as the `Context` that made it.
+/// Realms share the heap but get their own set of globals, only the javascript
+/// builtins are present so there is no `Deno`, `console` or registered functions.
+#[pyclass]
+pub struct Realm {
+    context: Py<Context>,
+    realm: Held<v8::Global<v8::Context>>,
+    alive: Weak<()>,
+}
+
+impl Realm {
+    pub(crate) fn new(context: &Bound<'_, Context>) -> PyResult<Self> {
+        let ctx = context.try_borrow()?;
+        let mut rt = ctx.runtime.lock(ctx.lock_timeout)?;
+        let realm = {
+            let scope = &mut rt.deno_runtime().handle_scope();
+            let local = v8::Context::new(scope, Default::default());
+            v8::Global::new(scope, local)
+        };
+        Ok(Self {
+            context: context.clone().unbind(),
+            realm: Held::new(realm, &released(&mut rt)),
+            alive: Arc::downgrade(&ctx.alive),
+        })
+    }
+
+    /// Runs `f` inside of the realm and waits for the promise it comes to, if it
+    /// is one, within the context's timeout. Thrown errors are turned into a
+    /// `RuntimeError` and running out of time into a `TimeoutError`.
+    fn enter<F>(&self, py: Python<'_>, f: F) -> PyResult<Py<PyAny>>
+    where
+        F: for<'t, 's> FnOnce(
+            &mut v8::TryCatch<'t, v8::HandleScope<'s>>,
+            &Context,
+        ) -> PyResult<Option<v8::Local<'s, v8::Value>>>,
+    {
+        let ctx = self.context.try_borrow(py)?;
+        // Handles made in an isolate that got recycled are long gone
+        match self.alive.upgrade() {
+            Some(alive) if Arc::ptr_eq(&alive, &ctx.alive) => {}
+            _ => {
+                return Err(InvalidStateError::new_err(
+                    "Realm belongs to a runtime that has been recycled.",
+                ));
+            }
+        }
+        let mut rt = ctx.runtime.lock(ctx.lock_timeout)?;
+        let limit = rt.timeout();
+        let result = bounded(&mut rt, limit, "realm", async |rt: &mut Runtime| {
+            let value = {
+                let scope = &mut rt.deno_runtime().handle_scope();
+                let local = v8::Local::new(scope, &*self.realm);
+                let scope = &mut v8::ContextScope::new(scope, local);
+                let tc = &mut v8::TryCatch::new(scope);
+                match f(tc, &ctx).map_err(raised)? {
+                    Some(value) => v8::Global::new(tc, value),
+                    None => return Err(raised(caught_exception(tc))),
+                }
+            };
+            let js = rt.deno_runtime();
+            let resolved = js.resolve(value);
+            Ok(js
+                .with_event_loop_promise(resolved, PollEventLoopOptions::default())
+                .await?)
+        });
+        let value = match result {
+            Ok(value) => value,
+            Err(e) => return Err(ctx.js_error(e)),
+        };
+        let scope = &mut rt.deno_runtime().handle_scope();
+        let local = v8::Local::new(scope, &*self.realm);
+        let scope = &mut v8::ContextScope::new(scope, local);
+        let tc = &mut v8::TryCatch::new(scope);
+        let value = v8::Local::new(tc, value);
+        v8_to_python(py, tc, value, &ctx.convert)
+    }
+}
+
+#[pymethods]
+impl Realm {
+    pub fn eval(&self, py: Python<'_>, code: &str) -> PyResult<Py<PyAny>> {
+        self.enter(py, |tc, ctx| {
+            ctx.check_source(code)?;
+            let Some(source) = v8::String::new(tc, code) else {
+                return Err(PyRuntimeError::new_err("source could not be encoded"));
+            };
+            Ok(v8::Script::compile(tc, source, None).and_then(|script| script.run(tc)))
+        })
+    }
+
+    #[pyo3(signature=(name, *py_args))]
+    pub fn call(
+        &self,
+        py: Python<'_>,
+        name: &str,
+        py_args: &Bound<'_, PyTuple>,
+    ) -> PyResult<Py<PyAny>> {
+        self.enter(py, |tc, ctx| {
+            let args = python_args_to_serde(py_args, &ctx.args)?;
+            let global = tc.get_current_context().global(tc);
+            let Some(key) = v8::String::new(tc, name) else {
+                return Err(PyRuntimeError::new_err("name could not be encoded"));
+            };
+            let value = global.get(tc, key.into());
+            let Some(func) = value.and_then(|v| v8::Local::<v8::Function>::try_from(v).ok()) else {
+                return Err(PyRuntimeError::new_err(format!("{name} is not a function")));
+            };
+            let mut v8_args = Vec::with_capacity(args.len());
+            for a in &args {
+                match serde_v8::to_v8(tc, a) {
+                    Ok(v) => v8_args.push(v),
+                    Err(e) => return Err(PyRuntimeError::new_err(e.to_string())),
+                }
+            }
+            Ok(func.call(tc, global.into(), &v8_args))
+        })
+    }
+}
@@ -0,0 +1,53 @@
+//! Outcomes handed back instead of raised, for loops that can't afford a
+//! try/except around every item.
+use pyo3::{exceptions::PyBaseException, prelude::*};
+
+/// Either the value some javascript produced or the exception it would have raised
+#[pyclass(frozen)]
+pub struct JsResult {
+    outcome: Result<Py<PyAny>, PyErr>,
+}
+
+impl JsResult {
+    pub fn new(outcome: PyResult<Py<PyAny>>) -> Self {
+        Self { outcome }
+    }
+}
+
+#[pymethods]
+impl JsResult {
+    pub fn is_ok(&self) -> bool {
+        self.outcome.is_ok()
+    }
+
+    pub fn is_err(&self) -> bool {
+        self.outcome.is_err()
+    }
+
+    /// The value, raising the exception when there is none
+    pub fn unwrap(&self, py: Python<'_>) -> PyResult<Py<PyAny>> {
+        match &self.outcome {
+            Ok(value) => Ok(value.clone_ref(py)),
+            Err(e) => Err(e.clone_ref(py)),
+        }
+    }
+
+    /// The exception, `None` when the code succeeded
+    pub fn err(&self, py: Python<'_>) -> Option<Py<PyBaseException>> {
+        match &self.outcome {
+            Ok(_) => None,
+            Err(e) => Some(e.value(py).clone().unbind()),
+        }
+    }
+
+    pub fn __bool__(&self) -> bool {
+        self.outcome.is_ok()
+    }
+
+    pub fn __repr__(&self, py: Python<'_>) -> PyResult<String> {
+        match &self.outcome {
+            Ok(value) => Ok(format!("Ok({})", value.bind(py).repr()?)),
+            Err(e) => Ok(format!("Err({})", e.value(py).repr()?)),
+        }
+    }
+}
@@ -0,0 +1,564 @@
+//! Javascript snippets that get evaluated inside of the runtime on our behalf.
+
+/// Swaps `console` for one that records formatted lines instead of printing them.
+pub const CONSOLE_CAPTURE_START: &str = r#"(() => {
+    const original = globalThis.console;
+    const lines = [];
+    const format = (args) =>
+        args.map((a) => (typeof a === "string" ? a : Deno.inspect(a))).join(" ");
+    const capture = Object.create(original);
+    for (const level of ["log", "info", "warn", "error", "debug", "trace"]) {
+        capture[level] = (...args) => { lines.push(format(args)); };
+    }
+    globalThis.console = capture;
+    globalThis[Symbol.for("pyrv8.console_capture")] = { original, lines };
+})()"#;
+
+/// Puts the original `console` back and hands over the lines recorded since
+/// [`CONSOLE_CAPTURE_START`].
+pub const CONSOLE_CAPTURE_STOP: &str = r#"(() => {
+    const key = Symbol.for("pyrv8.console_capture");
+    const capture = globalThis[key];
+    delete globalThis[key];
+    globalThis.console = capture.original;
+    return capture.lines;
+})()"#;
+
+/// The property [`INSTALL_RAISED`] keeps the token of a python exception under
+pub const RAISED_PROPERTY: &str = "pyrv8Raised";
+
+/// Moves the token at the end of the message of an exception a registered
+/// function raised onto a hidden property, so javascript sees the exception's
+/// own message. The property is listed among the error's additional ones
+/// which deno carries over when the error is thrown out of javascript.
+/// Installs `__pyrv8_raised` to wrap the bound functions with.
+pub const INSTALL_RAISED: &str = r#"(() => {
+    const token = / (\[python exception \d+\])$/;
+    const keys = Symbol.for("errorAdditionalPropertyKeys");
+    const adopt = (e) => {
+        const found = e instanceof Error && !(keys in e) && token.exec(e.message);
+        if (found) {
+            if (typeof e.stack === "string") e.stack = e.stack.replace(found[0], "");
+            e.message = e.message.slice(0, found.index);
+            Object.defineProperty(e, "pyrv8Raised", { value: found[1] });
+            Object.defineProperty(e, keys, { value: ["pyrv8Raised"] });
+        }
+        throw e;
+    };
+    const raised = (f) => (...args) => {
+        try {
+            const result = f(...args);
+            return result instanceof Promise ? result.catch(adopt) : result;
+        } catch (e) {
+            adopt(e);
+        }
+    };
+    Object.defineProperty(globalThis, "__pyrv8_raised", { value: raised });
+})()"#;
+
+/// Binds registered rustyscript functions to globals of the same name,
+/// evaluated as `BIND_FUNCTIONS(["name", ...])`.
+pub const BIND_FUNCTIONS: &str = r#"((names) => {
+    for (const name of names) {
+        globalThis[name] = __pyrv8_raised((...args) => rustyscript.functions[name](...args));
+    }
+})"#;
+
+/// Binds registered async functions to globals returning promises,
+/// evaluated as `BIND_ASYNC_FUNCTIONS(["name", ...])`.
+/// Calls wait for a free slot first when [`LIMIT_OPS`] is in place.
+pub const BIND_ASYNC_FUNCTIONS: &str = r#"((names) => {
+    const limited = globalThis.__pyrv8_limited ?? ((f) => f);
+    for (const name of names) {
+        globalThis[name] = limited(
+            __pyrv8_raised((...args) => rustyscript.async_functions[name](...args)),
+        );
+    }
+})"#;
+
+/// Removes a global bound by [`BIND_FUNCTIONS`] or [`BIND_ASYNC_FUNCTIONS`], evaluated as `UNBIND_FUNCTION("name")`.
+pub const UNBIND_FUNCTION: &str = r#"((name) => {
+    delete globalThis[name];
+})"#;
+
+/// Routes rejections nobody handled to the python callback registered under
+/// [`REJECTION_HANDLER`], errors are passed along as their string form since
+/// they don't survive serialization.
+pub const INSTALL_REJECTION_HANDLER: &str = r#"(() => {
+    const handler = rustyscript.functions["__pyrv8_unhandled_rejection"];
+    Deno.core.setUnhandledPromiseRejectionHandler((promise, reason) => {
+        const simple = reason instanceof Error || typeof reason === "symbol";
+        handler(simple ? String(reason) : reason);
+        return true;
+    });
+})()"#;
+
+/// Name the unhandled rejection callback is registered under
+pub const REJECTION_HANDLER: &str = "__pyrv8_unhandled_rejection";
+
+/// Name the function writing errors to `sys.stderr` is registered under
+pub const ERROR_LOG: &str = "__pyrv8_log_error";
+
+/// Sends exceptions thrown by timers and microtasks and rejections nobody
+/// handled to [`ERROR_LOG`] with their stack instead of the event loop.
+pub const INSTALL_ERROR_LOG: &str = r#"(() => {
+    const log = rustyscript.functions["__pyrv8_log_error"];
+    const describe = (error) =>
+        error instanceof Error && error.stack ? error.stack : String(error);
+    Deno.core.setReportExceptionCallback((error) => log(`Uncaught ${describe(error)}`));
+    Deno.core.setUnhandledPromiseRejectionHandler((promise, reason) => {
+        log(`Uncaught (in promise) ${describe(reason)}`);
+        return true;
+    });
+})()"#;
+
+/// Instantiates a compiled `WebAssembly.Module` without imports and binds
+/// its exports to a global, called as `INSTANTIATE_WASM(name, module)`.
+pub const INSTANTIATE_WASM: &str = r#"((name, module) => {
+    globalThis[name] = new WebAssembly.Instance(module, {}).exports;
+})"#;
+
+/// Defines a read only global, objects are frozen all the way down so their
+/// contents can't be changed either. Called as `DEFINE_CONSTANT(name, value)`.
+pub const DEFINE_CONSTANT: &str = r#"((name, value) => {
+    const freeze = (v) => {
+        if (typeof v === "object" && v !== null) {
+            Object.values(v).forEach(freeze);
+            Object.freeze(v);
+        }
+        return v;
+    };
+    Object.defineProperty(globalThis, name, {
+        value: freeze(value),
+        writable: false,
+        configurable: false,
+        enumerable: true,
+    });
+})"#;
+
+/// Name the console stream handler is registered under
+pub const CONSOLE_STREAM_HANDLER: &str = "__pyrv8_console_stream";
+
+/// Wraps the methods of `console` so each line is also handed to
+/// [`CONSOLE_STREAM_HANDLER`] while still being printed as usual.
+pub const CONSOLE_STREAM: &str = r#"(() => {
+    const send = rustyscript.functions["__pyrv8_console_stream"];
+    const format = (args) =>
+        args.map((a) => (typeof a === "string" ? a : Deno.inspect(a))).join(" ");
+    for (const level of ["log", "info", "warn", "error", "debug", "trace"]) {
+        const original = console[level];
+        console[level] = (...args) => {
+            send(format(args));
+            return original.apply(console, args);
+        };
+    }
+})()"#;
+
+/// Name the `console.error` handler is registered under
+pub const CONSOLE_ERROR_HANDLER: &str = "__pyrv8_console_error";
+
+/// Wraps `console.error` so its arguments are also handed to
+/// [`CONSOLE_ERROR_HANDLER`], anything json can't carry goes as its inspected text.
+pub const CONSOLE_ERROR_HOOK: &str = r#"(() => {
+    const report = rustyscript.functions["__pyrv8_console_error"];
+    const plain = (a) => {
+        if (a === null) return null;
+        try {
+            return JSON.parse(JSON.stringify(a));
+        } catch {
+            return Deno.inspect(a);
+        }
+    };
+    const original = console.error;
+    console.error = (...args) => {
+        report(args.map(plain));
+        return original.apply(console, args);
+    };
+})()"#;
+
+/// Names the functions behind [`FS_SHIM`] are registered under
+pub const FS_READ: &str = "__pyrv8_fs_read";
+pub const FS_READ_TEXT: &str = "__pyrv8_fs_read_text";
+pub const FS_WRITE: &str = "__pyrv8_fs_write";
+pub const FS_READ_DIR: &str = "__pyrv8_fs_read_dir";
+
+/// Defines a node style `fs` global over the mounted directories,
+/// binary data crosses over as base64.
+pub const FS_SHIM: &str = r#"(() => {
+    const fns = rustyscript.functions;
+    const decode = (data) => Uint8Array.from(atob(data), (c) => c.charCodeAt(0));
+    const encode = (bytes) => {
+        let s = "";
+        for (const b of bytes) s += String.fromCharCode(b);
+        return btoa(s);
+    };
+    const isText = (options) => {
+        const encoding = typeof options === "string" ? options : options?.encoding;
+        return encoding === "utf8" || encoding === "utf-8";
+    };
+    const readFileSync = (path, options) =>
+        isText(options)
+            ? fns["__pyrv8_fs_read_text"](String(path))
+            : decode(fns["__pyrv8_fs_read"](String(path)));
+    const writeFileSync = (path, data) => {
+        const bytes = typeof data === "string"
+            ? new TextEncoder().encode(data)
+            : new Uint8Array(data.buffer ?? data, data.byteOffset ?? 0, data.byteLength);
+        fns["__pyrv8_fs_write"](String(path), encode(bytes));
+    };
+    const readdirSync = (path) => fns["__pyrv8_fs_read_dir"](String(path));
+    globalThis.fs = {
+        readFileSync,
+        writeFileSync,
+        readdirSync,
+        readFile: async (...args) => readFileSync(...args),
+        writeFile: async (...args) => writeFileSync(...args),
+        readdir: async (...args) => readdirSync(...args),
+    };
+})()"#;
+
+/// Formats a value the way the node REPL prints it, evaluated as `DISPLAY(value)`.
+/// Contexts made with `minimal` have no `Deno.inspect` so they fall back to `String`.
+pub const DISPLAY: &str = r#"((value) => {
+    if (typeof Deno === "undefined") {
+        if (typeof value === "string") return JSON.stringify(value);
+        try {
+            return String(value);
+        } catch {
+            return Object.prototype.toString.call(value);
+        }
+    }
+    // Strings handed to inspect directly come back without their quotes
+    if (typeof value === "string") return Deno.inspect([value]).slice(2, -2);
+    return Deno.inspect(value, { colors: false, depth: 2 });
+})"#;
+
+/// Strips the globals extensions added so only the ECMAScript builtins and
+/// `WebAssembly` are left. `rustyscript` is swapped for a copy that doesn't
+/// reach for `Deno` when called since that goes too.
+pub const MINIMAL_GLOBALS: &str = r#"(() => {
+    const ops = Deno.core.ops;
+    const calls = (op) => new Proxy({}, { get: (_target, name) => (...args) => op(name, args) });
+    const internals = Object.freeze({
+        register_entrypoint: (f) => ops.op_register_entrypoint(f),
+        bail: (msg) => { throw new Error(msg); },
+        functions: calls(ops.call_registered_function),
+        async_functions: calls(ops.call_registered_function_async),
+    });
+    const builtins = new Set([
+        "globalThis", "Infinity", "NaN", "undefined", "eval", "isFinite", "isNaN",
+        "parseFloat", "parseInt", "decodeURI", "decodeURIComponent", "encodeURI",
+        "encodeURIComponent", "escape", "unescape", "AggregateError", "Array",
+        "ArrayBuffer", "AsyncDisposableStack", "Atomics", "BigInt", "BigInt64Array",
+        "BigUint64Array", "Boolean", "DataView", "Date", "DisposableStack", "Error",
+        "EvalError", "FinalizationRegistry", "Float16Array", "Float32Array",
+        "Float64Array", "Function", "Int8Array", "Int16Array", "Int32Array", "Intl",
+        "Iterator", "JSON", "Map", "Math", "Number", "Object", "Promise", "Proxy",
+        "RangeError", "ReferenceError", "Reflect", "RegExp", "Set", "SharedArrayBuffer",
+        "String", "SuppressedError", "Symbol", "SyntaxError", "TypeError", "Uint8Array",
+        "Uint8ClampedArray", "Uint16Array", "Uint32Array", "URIError", "WeakMap",
+        "WeakRef", "WeakSet", "WebAssembly",
+    ]);
+    for (const name of Object.getOwnPropertyNames(globalThis)) {
+        if (!builtins.has(name)) {
+            delete globalThis[name];
+        }
+    }
+    Object.defineProperty(globalThis, "rustyscript", { value: internals });
+})()"#;
+
+/// The globals behind each of the names `web_apis` takes, the ones a build
+/// doesn't have are simply never there to begin with
+pub const WEB_API_GLOBALS: &[(&str, &[&str])] = &[
+    ("console", &["console"]),
+    ("url", &["URL", "URLSearchParams", "URLPattern"]),
+    (
+        "encoding",
+        &[
+            "TextEncoder",
+            "TextDecoder",
+            "TextEncoderStream",
+            "TextDecoderStream",
+            "atob",
+            "btoa",
+        ],
+    ),
+    ("crypto", &["crypto", "Crypto", "CryptoKey", "SubtleCrypto"]),
+    (
+        "timers",
+        &[
+            "setTimeout",
+            "clearTimeout",
+            "setInterval",
+            "clearInterval",
+            "setImmediate",
+            "refTimer",
+            "unrefTimer",
+        ],
+    ),
+    ("blob", &["Blob", "File", "FileReader"]),
+    (
+        "fetch",
+        &[
+            "fetch",
+            "Request",
+            "Response",
+            "Headers",
+            "FormData",
+            "EventSource",
+        ],
+    ),
+    (
+        "streams",
+        &[
+            "ReadableStream",
+            "WritableStream",
+            "TransformStream",
+            "ByteLengthQueuingStrategy",
+            "CountQueuingStrategy",
+            "CompressionStream",
+            "DecompressionStream",
+        ],
+    ),
+    (
+        "events",
+        &[
+            "Event",
+            "EventTarget",
+            "CustomEvent",
+            "AbortController",
+            "AbortSignal",
+        ],
+    ),
+    ("websocket", &["WebSocket", "WebSocketStream"]),
+    ("webstorage", &["localStorage", "sessionStorage", "Storage"]),
+];
+
+/// Deletes globals by name, evaluated as `REMOVE_GLOBALS(["name", ...])`
+pub const REMOVE_GLOBALS: &str = r#"((names) => {
+    for (const name of names) {
+        delete globalThis[name];
+    }
+})"#;
+
+/// Replaces `Math.random` and `crypto.getRandomValues` with an sfc32 generator,
+/// evaluated as `SEED_RANDOM(low, high)` with the two halves of the seed.
+pub const SEED_RANDOM: &str = r#"((low, high) => {
+    let a = low >>> 0, b = high >>> 0, c = 0x9e3779b9, d = 1;
+    const next = () => {
+        const t = (((a + b) | 0) + d) | 0;
+        d = (d + 1) | 0;
+        a = b ^ (b >>> 9);
+        b = (c + (c << 3)) | 0;
+        c = (c << 21) | (c >>> 11);
+        c = (c + t) | 0;
+        return t >>> 0;
+    };
+    // The first outputs still look a lot like the seed
+    for (let i = 0; i < 15; i++) next();
+    Math.random = () => next() / 4294967296;
+    if (globalThis.crypto?.getRandomValues) {
+        globalThis.crypto.getRandomValues = (array) => {
+            const bytes = new Uint8Array(array.buffer, array.byteOffset, array.byteLength);
+            for (let i = 0; i < bytes.length; i++) bytes[i] = next() & 0xff;
+            return array;
+        };
+    }
+})"#;
+
+/// Lists the rustyscript extensions that left their globals behind
+pub const SUPPORTED_FEATURES: &str = r#"(() => {
+    const probes = {
+        console: () => typeof console?.log === "function",
+        url: () => typeof URL === "function",
+        crypto: () => typeof crypto?.getRandomValues === "function",
+        web_stub: () => typeof setTimeout === "function" && typeof fetch !== "function",
+        web: () => typeof fetch === "function",
+        webstorage: () => typeof localStorage === "object",
+        websocket: () => typeof WebSocket === "function",
+        broadcast_channel: () => typeof BroadcastChannel === "function",
+        cache: () => typeof caches === "object",
+        webgpu: () => typeof navigator?.gpu === "object",
+        fs: () => typeof Deno?.readFile === "function",
+        kv: () => typeof Deno?.openKv === "function",
+        cron: () => typeof Deno?.cron === "function",
+        ffi: () => typeof Deno?.dlopen === "function",
+        webassembly: () => typeof WebAssembly === "object",
+    };
+    return Object.keys(probes).filter((name) => probes[name]());
+})()"#;
+
+/// Copies the enumerable globals that survive a round trip through json,
+/// functions and anything `JSON.stringify` rejects or drops are left out
+pub const DUMP_GLOBALS: &str = r#"(() => {
+    const globals = {};
+    for (const name of Object.keys(globalThis)) {
+        try {
+            const value = globalThis[name];
+            if (typeof value === "function" || typeof value === "symbol") continue;
+            const text = JSON.stringify(value);
+            if (text !== undefined) globals[name] = JSON.parse(text);
+        } catch {
+            // Getters that throw, bigints and cycles
+        }
+    }
+    return globals;
+})()"#;
+
+/// Freezes the clock `Date` reads at `now` milliseconds since the epoch, evaluated
+/// as `MOCK_TIME(now)`. `MOCK_TIME(null)` puts the real `Date` back.
+pub const MOCK_TIME: &str = r#"((now) => {
+    let real = globalThis.__pyrv8_real_date;
+    if (real === undefined) {
+        real = Date;
+        Object.defineProperty(globalThis, "__pyrv8_real_date", { value: real });
+    }
+    if (now === null) {
+        globalThis.Date = real;
+        return;
+    }
+    function Date(...args) {
+        if (!new.target) return new real(now).toString();
+        return args.length ? new real(...args) : new real(now);
+    }
+    Object.setPrototypeOf(Date, real);
+    Date.prototype = real.prototype;
+    Date.now = () => now;
+    globalThis.Date = Date;
+})"#;
+
+/// Runs `code` as the body of an async function so it can `await` at the top
+/// level, evaluated as `TOP_LEVEL_AWAIT(code)`. A single expression is returned
+/// as it is, statements have to `return` what they evaluate to.
+pub const TOP_LEVEL_AWAIT: &str = r#"((code) => {
+    const AsyncFunction = (async () => {}).constructor;
+    let run;
+    try {
+        run = new AsyncFunction(`return (${code}\n)`);
+    } catch {
+        run = new AsyncFunction(code);
+    }
+    return run();
+})"#;
+
+/// Lets at most `limit` async ops be in flight at once, evaluated as
+/// `LIMIT_OPS(limit)`. `fetch` and async python functions wait for a slot
+/// before they start and `setTimeout` timers before they're scheduled, the
+/// slot is given back once they settle or fire. Intervals aren't limited
+/// since they never give theirs back.
+pub const LIMIT_OPS: &str = r#"((limit) => {
+    let active = 0;
+    const waiting = [];
+    const take = (run) => {
+        if (active < limit) {
+            active++;
+            run();
+        } else {
+            waiting.push(run);
+        }
+    };
+    // A waiting op gets the slot directly so nothing can sneak in between
+    const release = () => {
+        const next = waiting.shift();
+        if (next) next();
+        else active = Math.max(0, active - 1);
+    };
+    const limited = (f) => async (...args) => {
+        await new Promise(take);
+        try {
+            return await f(...args);
+        } finally {
+            release();
+        }
+    };
+    Object.defineProperty(globalThis, "__pyrv8_limited", { value: limited });
+    if (typeof fetch === "function") globalThis.fetch = limited(fetch);
+    if (typeof setTimeout !== "function") return;
+    const realSetTimeout = setTimeout, realClearTimeout = clearTimeout;
+    const timers = new Map();
+    let nextId = 1;
+    globalThis.setTimeout = (callback, delay, ...args) => {
+        const id = nextId++;
+        const timer = { real: null };
+        timers.set(id, timer);
+        take(() => {
+            // Cleared while it was waiting
+            if (!timers.has(id)) return release();
+            timer.real = realSetTimeout(() => {
+                timers.delete(id);
+                release();
+                if (typeof callback === "function") callback(...args);
+            }, delay);
+        });
+        return id;
+    };
+    globalThis.clearTimeout = (id) => {
+        const timer = timers.get(id);
+        if (!timer) return realClearTimeout(id);
+        timers.delete(id);
+        if (timer.real !== null) {
+            realClearTimeout(timer.real);
+            release();
+        }
+    };
+    // `abort_all` cancels the real timers behind these
+    Object.defineProperty(globalThis, "__pyrv8_reset_ops", {
+        value: () => {
+            timers.clear();
+            waiting.length = 0;
+            active = 0;
+        },
+    });
+})"#;
+
+/// Exposes string variables as `process.env` and through `Deno.env` when the
+/// runtime has no env of its own, evaluated as `INSTALL_ENV(vars, writable)`.
+/// Read only ones are frozen so assigning to them does nothing, or throws in
+/// strict code.
+pub const INSTALL_ENV: &str = r#"((vars, writable) => {
+    const env = Object.assign(Object.create(null), vars);
+    if (!writable) Object.freeze(env);
+    const process = globalThis.process ?? {};
+    Object.defineProperty(process, "env", { value: env, enumerable: true, writable });
+    if (globalThis.process === undefined) {
+        Object.defineProperty(globalThis, "process", { value: process, writable: true, configurable: true });
+    }
+    if (typeof Deno === "object" && Deno.env === undefined) {
+        const readOnly = () => {
+            throw new TypeError("the environment is read only");
+        };
+        const denoEnv = Object.freeze({
+            get: (key) => env[key],
+            has: (key) => Object.hasOwn(env, key),
+            toObject: () => ({ ...env }),
+            set: writable ? (key, value) => { env[key] = String(value); } : readOnly,
+            delete: writable ? (key) => { delete env[key]; } : readOnly,
+        });
+        try {
+            Object.defineProperty(Deno, "env", { value: denoEnv, enumerable: true });
+        } catch {
+            // Deno is frozen in some builds, process.env still works
+        }
+    }
+})"#;
+
+/// Cancels every timer made since the last call, evaluated as `ABORT_TIMERS(last)`
+/// and returning the newest id. Timer ids count up so a throwaway timer tells
+/// how far to go.
+pub const ABORT_TIMERS: &str = r#"((last) => {
+    const core = globalThis.Deno?.core;
+    if (!core) return last;
+    const newest = core.queueUserTimer(0, false, 0, () => {});
+    for (let id = last + 1; id <= newest; id++) {
+        core.cancelTimer(id);
+    }
+    globalThis.__pyrv8_reset_ops?.();
+    return newest;
+})"#;
+
+/// Rejects locales that aren't well formed and makes v8 read the default
+/// locale so the isolate keeps it, evaluated as `PIN_LOCALE(locale)`.
+pub const PIN_LOCALE: &str = r#"((locale) => {
+    Intl.getCanonicalLocales(locale);
+    new Intl.NumberFormat().resolvedOptions();
+})"#;
@@ -0,0 +1,75 @@
+//! Wall-clock and CPU time spent on a single call, for `return_timing`.
+use std::time::{Duration, Instant};
+
+use pyo3::{prelude::*, types::PyDict};
+
+/// CPU time used by the calling thread, the runtime runs on the thread that called it
+#[cfg(unix)]
+fn thread_cpu_time() -> Option<Duration> {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: clock_gettime only writes into the timespec it's given
+    let ok = unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut ts) } == 0;
+    ok.then(|| Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32))
+}
+
+#[cfg(not(unix))]
+fn thread_cpu_time() -> Option<Duration> {
+    None
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// Started before the runtime lock is taken so waiting on it is measured apart
+/// from the call itself
+pub struct Timing {
+    started: Instant,
+    lock_wait: Duration,
+    cpu_started: Option<Duration>,
+    wall: Duration,
+    cpu: Option<Duration>,
+}
+
+impl Timing {
+    pub fn start() -> Self {
+        Self {
+            started: Instant::now(),
+            lock_wait: Duration::ZERO,
+            cpu_started: None,
+            wall: Duration::ZERO,
+            cpu: None,
+        }
+    }
+
+    /// The runtime lock was acquired, everything from here on is the call
+    pub fn locked(&mut self) {
+        self.lock_wait = self.started.elapsed();
+        self.started = Instant::now();
+        self.cpu_started = thread_cpu_time();
+    }
+
+    /// The javascript returned, converting the result isn't counted
+    pub fn finished(&mut self) {
+        self.wall = self.started.elapsed();
+        self.cpu = self
+            .cpu_started
+            .zip(thread_cpu_time())
+            .map(|(start, end)| end.saturating_sub(start));
+    }
+
+    /// Pairs `result` with a dict of `wall_ms`, `lock_ms` and `cpu_ms` where the
+    /// platform can tell
+    pub fn attach(self, py: Python<'_>, result: Py<PyAny>) -> PyResult<Py<PyAny>> {
+        let timing = PyDict::new(py);
+        timing.set_item("wall_ms", millis(self.wall))?;
+        timing.set_item("lock_ms", millis(self.lock_wait))?;
+        if let Some(cpu) = self.cpu {
+            timing.set_item("cpu_ms", millis(cpu))?;
+        }
+        Ok((result, timing).into_pyobject(py)?.into_any().unbind())
+    }
+}
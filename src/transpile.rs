@@ -0,0 +1,44 @@
+//! Typescript and JSX to javascript without typechecking, the same way
+//! rustyscript does it when loading modules.
+use deno_ast::{EmitOptions, MediaType, ParseParams, TranspileModuleOptions, TranspileOptions};
+use rustyscript::deno_core::ModuleSpecifier;
+
+/// True for the media types that have to be transpiled before v8 can run them
+pub fn needs_transpiling(specifier: &ModuleSpecifier) -> bool {
+    matches!(
+        MediaType::from_specifier(specifier),
+        MediaType::Jsx
+            | MediaType::TypeScript
+            | MediaType::Mts
+            | MediaType::Cts
+            | MediaType::Dts
+            | MediaType::Dmts
+            | MediaType::Dcts
+            | MediaType::Tsx
+    )
+}
+
+/// Turns the source into plain javascript, sources that already are
+/// javascript are handed back untouched.
+pub fn transpile(specifier: &ModuleSpecifier, code: &str) -> Result<String, String> {
+    if !needs_transpiling(specifier) {
+        return Ok(code.to_string());
+    }
+    let parsed = deno_ast::parse_module(ParseParams {
+        specifier: specifier.clone(),
+        text: code.into(),
+        media_type: MediaType::from_specifier(specifier),
+        capture_tokens: false,
+        scope_analysis: false,
+        maybe_syntax: None,
+    })
+    .map_err(|e| e.to_string())?;
+    let emitted = parsed
+        .transpile(
+            &TranspileOptions::default(),
+            &TranspileModuleOptions::default(),
+            &EmitOptions::default(),
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(emitted.into_source().text)
+}
@@ -1,4 +1,18 @@
-from pyrv8 import Context
+import asyncio
+import builtins
+import json
+import math
+import re
+import threading
+import time
+from dataclasses import dataclass
+from decimal import Decimal
+from pathlib import Path
+
+import pytest
+
+import pyrv8
+from pyrv8 import CancelToken, Context, InvalidStateError, JsConsoleError, JsModule, JsRegExp, JsResult, JsSymbol, TimeoutError
 
 
 
@@ -18,3 +32,1511 @@ def test_context_eval_with_function_call() -> None:
 
     assert context.call("add", 1, 2) == 3
 
+
+def test_context_eval_symbol() -> None:
+    context = Context()
+    symbol = context.eval("Symbol('token')")
+    assert isinstance(symbol, JsSymbol)
+    assert symbol.description == "token"
+    assert not symbol.is_well_known
+    assert context.eval("Symbol.iterator").is_well_known
+
+
+@pytest.mark.parametrize(
+    "code, expected",
+    [
+        ("new Date(0)", {}),
+        ("new Set([1])", {}),
+        ("new Number(1)", {}),
+        ("(function f() {})", {}),
+        ("[1, , 3]", [1, None, 3]),
+        ("[undefined, null]", [None, None]),
+        ("({a: undefined, b: null})", {"b": None}),
+        ("({get x() { return 1; }})", {"x": 1}),
+        ("Object.create({inherited: 1}, {own: {value: 2, enumerable: true}, hidden: {value: 3}})", {"own": 2}),
+        ("({1: 'a', b: 2})", {"1": "a", "b": 2}),
+        ("new Map([['a', 1]])", {"a": 1}),
+        ("2 ** 31", 2**31),
+        ("2 ** 32", float(2**32)),
+        ("-(2 ** 31)", -(2**31)),
+        ("1.5", 1.5),
+    ],
+)
+def test_context_eval_serde_parity(code, expected) -> None:
+    # What serde_v8 produced before values were walked by hand
+    result = Context().eval(code)
+    assert result == expected
+    assert type(result) is type(expected)
+
+
+def test_context_eval_serde_differences() -> None:
+    context = Context()
+    # serde_v8 refused these or, for symbols and cycles, brought the process down
+    assert context.eval("new Uint8Array([1, 2])") == b"\x01\x02"
+    assert context.eval("12345678901234567890n") == 12345678901234567890
+    assert isinstance(context.eval("Symbol()"), JsSymbol)
+    with pytest.raises(ValueError, match="circular reference"):
+        context.eval("const o = {}; o.o = o; o")
+    # serde_json had no room for these and made them None
+    assert math.isnan(context.eval("NaN"))
+    assert context.eval("-Infinity") == -math.inf
+
+
+def test_context_symbol_keys() -> None:
+    context = Context(symbol_keys=True)
+    result = context.eval("({a: 1, [Symbol.iterator]: 2})")
+    assert result["a"] == 1
+    [(key, value)] = result["__symbols__"].items()
+    assert key.description == "Symbol.iterator"
+    assert value == 2
+
+
+def test_context_eval_capturing() -> None:
+    context = Context()
+    result, lines = context.eval_capturing("console.log('hello', 1); console.error({a: 1}); 5")
+    assert result == 5
+    assert lines == ["hello 1", "{ a: 1 }"]
+
+
+def test_context_register_functions() -> None:
+    context = Context()
+    context.register_functions({"double": lambda x: x * 2, "greet": lambda n: f"hi {n}"})
+    assert context.eval("double(21)") == 42
+    assert context.eval("greet('js')") == "hi js"
+
+
+def test_context_try_get_value() -> None:
+    context = Context()
+    context.eval("globalThis.answer = 42")
+    assert context.try_get_value("answer") == 42
+    assert context.try_get_value("missing") is None
+
+
+@dataclass
+class Point:
+    x: int
+    y: int
+
+
+def test_context_convert_objects() -> None:
+    context = Context(convert_objects=True)
+    context.eval("function norm(p) { return p.x * p.x + p.y * p.y; }")
+    assert context.call("norm", Point(3, 4)) == 25
+    context.eval("function first(ps) { return ps.points[0].y; }")
+    assert context.call("first", {"points": [Point(1, 2)]}) == 2
+
+
+def test_context_stack_trace_limit() -> None:
+    deep = "function dive(n) { if (n === 0) throw new Error('deep'); dive(n - 1); } dive(30)"
+    frames = lambda error: sum(line.strip().startswith("at ") for line in str(error).splitlines())
+    with pytest.raises(RuntimeError) as default:
+        Context(verbose_errors=True).eval(deep)
+    assert frames(default.value) > 3
+    context = Context(stack_trace_limit=3, verbose_errors=True)
+    assert context.eval("Error.stackTraceLimit") == 3
+    with pytest.raises(RuntimeError) as limited:
+        context.eval(deep)
+    assert frames(limited.value) == 3
+    deeper = Context(stack_trace_limit=50)
+    assert deeper.eval(f"try {{ {deep} }} catch (e) {{ e.stack.split('\\n').length }}") > 31
+
+
+def test_context_recycle_keeps_functions() -> None:
+    context = Context()
+    context.register_function("double", lambda x: x * 2)
+    context.eval("globalThis.leftover = 1")
+    context.recycle()
+    assert context.try_get_value("leftover") is None
+    assert context.eval("double(4)") == 8
+
+
+def test_context_max_source_bytes() -> None:
+    context = Context(max_source_bytes=16)
+    assert context.eval("1 + 1") == 2
+    with pytest.raises(ValueError):
+        context.eval("1 + " * 16 + "1")
+
+
+def test_context_max_source_bytes_imports(tmp_path) -> None:
+    (tmp_path / "big.js").write_text("export const x = 1;" + " " * 64)
+    (tmp_path / "small.js").write_text("export const x = 1;")
+    context = Context(max_source_bytes=64)
+    entry = JsModule(str(tmp_path / "main.js"), "import { x } from './big.js'; export const y = x;")
+    with pytest.raises(RuntimeError, match="max_source_bytes"):
+        context.load_module(entry)
+    entry = JsModule(str(tmp_path / "other.js"), "import { x } from './small.js'; export const y = x;")
+    context.load_module(entry)
+
+
+def test_context_eval_retries() -> None:
+    context = Context()
+    context.eval("globalThis.attempts = 0")
+    code = "if (++attempts < 3) throw new Error('not ready'); attempts"
+    assert context.eval(code, retries=3) == 3
+    with pytest.raises(RuntimeError):
+        context.eval("(", retries=3)
+    slow = Context(timeout=0.05)
+    slow.eval("globalThis.attempts = 0")
+    with pytest.raises(TimeoutError):
+        slow.eval("++attempts, new Promise((r) => setTimeout(r, 10000))", retries=3)
+    assert slow.eval("attempts") == 1
+    context.eval("globalThis.attempts = 0")
+    calls = []
+    context.register_function("flaky", lambda: calls.append(1) or 1 / 0)
+    with pytest.raises(ZeroDivisionError):
+        context.eval("flaky()", retries=3)
+    assert calls == [1]
+    with pytest.raises(RuntimeError):
+        context.eval("++attempts; throw new RangeError('no')", retries=3)
+    assert context.eval("attempts") == 1
+
+
+def test_context_unhandled_rejection_handler() -> None:
+    context = Context()
+    reasons = []
+    context.set_unhandled_rejection_handler(reasons.append)
+    context.eval("Promise.reject(new Error('lost')); undefined")
+    context.advance()
+    assert reasons == ["Error: lost"]
+
+
+def test_context_log_errors(capsys: pytest.CaptureFixture[str]) -> None:
+    context = Context(log_errors=True)
+    context.eval("Promise.reject(new Error('lost')); setTimeout(() => { throw new TypeError('late'); }); undefined")
+    while context.advance():
+        pass
+    err = capsys.readouterr().err
+    assert "Uncaught (in promise) Error: lost" in err
+    assert "Uncaught TypeError: late" in err
+
+
+@pytest.mark.parametrize("value", [math.nan, math.inf, -math.inf])
+def test_context_call_non_finite_args(value: float) -> None:
+    context = Context()
+    context.eval("function describe(x) { return [String(x), typeof x]; }")
+    context.eval("function nested(o) { return String(o.list[1]); }")
+    assert context.call("describe", value) == [str(value).replace("nan", "NaN").replace("inf", "Infinity"), "number"]
+    assert context.call("nested", {"list": [None, value]}) == context.call("describe", value)[0]
+    assert context.call("describe", None) == ["null", "object"]
+
+
+def test_context_call_non_finite_lookalike_strings() -> None:
+    context = Context()
+    context.eval("function describe(x) { return [x, typeof x]; }")
+    for text in ["\x01pyrv8 non-finite NaN", "NaN", "Infinity"]:
+        assert context.call("describe", text) == [text, "string"]
+        assert context.call("describe", [text, float("nan")])[0][0] == text
+
+
+@dataclass
+class Reading:
+    value: float
+
+
+def test_context_call_non_finite_fields_and_decimals() -> None:
+    context = Context(convert_objects=True)
+    context.eval("function describe(x) { return String(x.value ?? x); }")
+    context.eval("function nested(o) { return String(o.r[0].value); }")
+    assert context.call("describe", Reading(math.nan)) == "NaN"
+    assert context.call("nested", {"r": [Reading(-math.inf)]}) == "-Infinity"
+    assert context.call("describe", Decimal("NaN")) == "NaN"
+    assert context.call("describe", Decimal("-Infinity")) == "-Infinity"
+    calls = []
+    context.set_arg_encoder(lambda v: calls.append(v) or math.inf)
+    assert context.call("describe", 1j) == "Infinity"
+    assert calls == [1j]
+
+
+def test_context_module_resolution_error(tmp_path) -> None:
+    entry = tmp_path / "entry.js"
+    entry.write_text("import { x } from './missing.js';\nexport default x;")
+    context = Context()
+    with pytest.raises(pyrv8.ModuleResolutionError) as info:
+        context.load_module(JsModule.load(str(entry)))
+    assert info.value.specifier == (tmp_path / "missing.js").as_uri()
+    assert info.value.referrer == entry.as_uri()
+
+
+def test_context_drain_microtasks() -> None:
+    context = Context()
+    context.eval("globalThis.log = []; setTimeout(() => log.push('timer'), 20); Promise.resolve().then(() => log.push('then')).then(() => log.push('again')); undefined")
+    context.drain_microtasks()
+    assert context.eval("log") == ["then", "again"]
+    while context.advance():
+        pass
+    assert context.eval("log") == ["then", "again", "timer"]
+
+
+def test_context_call_from_file(tmp_path) -> None:
+    path = tmp_path / "args.json"
+    path.write_text(json.dumps({"items": list(range(1000))}))
+    context = Context()
+    context.eval("function total(payload) { return payload.items.reduce((a, b) => a + b, 0); }")
+    assert context.call_from_file("total", str(path)) == sum(range(1000))
+    path.write_text("{not json")
+    with pytest.raises(ValueError):
+        context.call_from_file("total", str(path))
+    with pytest.raises(FileNotFoundError):
+        context.call_from_file("total", str(tmp_path / "missing.json"))
+
+
+def test_context_eval_sandbox() -> None:
+    context = Context()
+    assert context.eval_sandbox("globalThis.leak = 1; leak + 1") == 2
+    assert context.eval_sandbox("typeof leak") == "undefined"
+    assert context.eval("typeof leak") == "undefined"
+    with pytest.raises(RuntimeError):
+        context.eval_sandbox("throw new Error('boom')")
+
+
+def test_js_handle_unload() -> None:
+    context = Context()
+    handle = context.load_module(JsModule("plugin.js", "export function run() { return 1; }"))
+    assert context.call_module(handle, "run") == 1
+    handle.unload(context)
+    with pytest.raises(InvalidStateError):
+        context.call_module(handle, "run")
+    with pytest.raises(ValueError):
+        handle.unload(context)
+    again = context.load_module(JsModule("plugin.js", "export function run() { return 2; }"))
+    assert context.call_module(again, "run") == 2
+
+
+def test_context_call_value() -> None:
+    context = Context()
+    context.eval("globalThis.obj = {handlers: {onClick: (a, b) => a + b}}")
+    assert context.call_value("obj.handlers.onClick", 1, 2) == 3
+    assert context.call_value("(x) => x * 2", 4) == 8
+    with pytest.raises(TypeError):
+        context.call_value("obj.handlers", 1)
+
+
+def test_context_pin_thread() -> None:
+    context = Context(pin_thread=True)
+    assert context.eval("1 + 1") == 2
+    errors = []
+
+    def use() -> None:
+        try:
+            context.eval("1")
+        except RuntimeError as e:
+            errors.append(str(e))
+
+    thread = threading.Thread(target=use)
+    thread.start()
+    thread.join()
+    assert len(errors) == 1 and "pinned" in errors[0]
+
+
+def test_context_eval_as() -> None:
+    context = Context()
+    assert context.eval_as("({x: 1, y: 2})", Point) == Point(1, 2)
+    with pytest.raises(TypeError):
+        context.eval_as("({z: 1})", Point)
+
+
+def test_context_realm_globals_are_isolated() -> None:
+    context = Context()
+    context.eval("globalThis.shared = 1")
+    realm = context.create_realm()
+    realm.eval("globalThis.shared = 2; function add(a, b) { return a + b; }")
+    assert realm.call("add", 2, 3) == 5
+    assert context.eval("shared") == 1
+    assert realm.eval("typeof Deno") == "undefined"
+
+
+def test_context_realm_awaits_within_timeout() -> None:
+    context = Context(timeout_ms=200)
+    realm = context.create_realm()
+    assert realm.eval("Promise.resolve(2).then((n) => n * 3)") == 6
+    realm.eval("async function later(n) { return n + 1; }")
+    assert realm.call("later", 1) == 2
+    with pytest.raises(TimeoutError):
+        realm.eval("while (true) {}")
+    assert realm.eval("1 + 1") == 2
+
+
+def test_context_realm_used_from_a_callback() -> None:
+    context = Context()
+    realm = context.create_realm()
+
+    def nested():
+        try:
+            return realm.eval("1")
+        except RuntimeError as e:
+            return str(e)
+
+    context.register_function("nested", nested)
+    assert "borrowed" in context.eval("nested()")
+    handles = [context.create_realm(), context.eval_lazy("({a: 1})")]
+    dropper = threading.Thread(target=handles.clear)
+    dropper.start()
+    dropper.join()
+    assert context.eval("1 + 1") == 2 and realm.eval("2") == 2
+
+
+def test_context_cache_results() -> None:
+    context = Context(cache_results=True)
+    context.eval("function config() { return {name: 'dash', sizes: [1, 2]}; }")
+    first = context.call("config")
+    assert context.call("config") is first
+    assert context.eval("({name: 'other', sizes: [1, 2]})") is not first
+    # Nesting that writes out the same keys and values in the same order
+    assert context.eval("({x: {a: 1, b: 2}})") != context.eval("({x: {a: 1}, b: 2})")
+
+
+def test_context_cache_results_hit_skips_conversion() -> None:
+    context = Context(cache_results=True)
+    context.eval("globalThis.reads = 0; globalThis.make = () => ({get n() { reads++; return 1; }})")
+    first = context.eval("make()")
+    # Fingerprinting reads the getter once and converting it a second time
+    assert context.eval("reads") == 2
+    assert context.eval("make()") is first
+    assert context.eval("reads") == 3
+
+
+def test_context_set_current_dir_create(tmp_path) -> None:
+    context = Context()
+    target = tmp_path / "work" / "nested"
+    with pytest.raises(NotADirectoryError):
+        context.set_current_dir(str(target))
+    context.set_current_dir(str(target), create=True)
+    assert target.is_dir()
+
+
+def test_context_run_until_complete_on_tick() -> None:
+    context = Context()
+    context.eval("async function later() { await new Promise((r) => setTimeout(r, 20)); return 7; }")
+    ticks = []
+    assert context.run_until_complete(context.call_async("later"), ticks.append) == 7
+    assert ticks and ticks == sorted(ticks)
+    with pytest.raises(asyncio.CancelledError):
+        context.run_until_complete(context.call_async("later"), lambda elapsed: False)
+
+
+def test_context_run_until_complete_total_timeout() -> None:
+    context = Context()
+    context.eval("async function spin() { while (true) await new Promise((r) => setTimeout(r, 1)); }")
+    with pytest.raises(TimeoutError):
+        context.run_until_complete(context.call_async("spin"), total_timeout=0.2)
+    context.eval("async function later() { return 7; }")
+    assert context.run_until_complete(context.call_async("later"), total_timeout=1) == 7
+
+
+# (module (func (export "add") (param i32 i32) (result i32)
+#   local.get 0 local.get 1 i32.add))
+ADD_WASM = bytes.fromhex(
+    "0061736d0100000001070160027f7f017f030201000707010361646400000a09010700200020016a0b"
+)
+
+
+def test_context_instantiate_wasm() -> None:
+    context = Context()
+    context.instantiate_wasm("kernels", ADD_WASM)
+    assert context.eval("kernels.add(2, 3)") == 5
+    with pytest.raises(RuntimeError):
+        context.instantiate_wasm("broken", b"not wasm")
+
+
+def test_context_check() -> None:
+    context = Context()
+    assert context.check("globalThis.ran = true") is None
+    assert context.try_get_value("ran") is None
+    assert "SyntaxError" in context.check("function (")
+    assert context.check("import { a } from './a.js'; export default a;", module=True) is None
+    assert context.check("import a from 'bare';", module=True) is not None
+
+
+def test_context_define_constant() -> None:
+    context = Context()
+    context.define_constant("API", {"version": 3})
+    context.eval("API = null; API.version = 4")
+    assert context.eval("API.version") == 3
+    with pytest.raises(RuntimeError):
+        context.eval("'use strict'; API = null")
+
+
+def test_context_console_stream() -> None:
+    context = Context()
+    stream = context.console_stream()
+
+    async def collect() -> list[str]:
+        context.eval("setTimeout(() => console.log('tick', {n: 1}), 0); undefined")
+        lines = []
+        while not lines:
+            context.advance()
+            try:
+                lines.append(await asyncio.wait_for(stream.next_line(), 0.01))
+            except asyncio.TimeoutError:
+                pass
+        stream.close()
+        async for line in stream:
+            lines.append(line)
+        return lines
+
+    assert asyncio.run(collect()) == ["tick { n: 1 }"]
+
+
+def test_context_non_finite_numbers() -> None:
+    context = Context()
+    assert math.isnan(context.eval("NaN"))
+    assert context.eval("Infinity") == math.inf
+    assert context.eval("-Infinity") == -math.inf
+    assert context.eval("[1 / 0, { x: -1 / 0 }]") == [math.inf, {"x": -math.inf}]
+
+
+@pytest.mark.parametrize("value", [math.nan, math.inf, -math.inf])
+def test_context_non_finite_round_trip(value: float) -> None:
+    def same(result: object) -> bool:
+        return math.isnan(result) if math.isnan(value) else result == value
+
+    context = Context()
+    context.eval("function echo(x) { return x; }")
+    assert same(context.call("echo", value))
+    nested = context.call("echo", [value, {"n": value}])
+    assert same(nested[0]) and same(nested[1]["n"])
+    handle = context.load_module(JsModule("echo.js", "export const echo = (x) => x;"))
+    assert same(context.call_module(handle, "echo", value))
+    realm = context.create_realm()
+    realm.eval("function echo(x) { return x; }")
+    assert same(realm.call("echo", value))
+    context.define_constant("LIMIT", {"n": value})
+    assert context.eval("typeof LIMIT.n") == "number"
+    assert same(context.eval("LIMIT.n"))
+
+
+def test_context_eval_cancel_token() -> None:
+    context = Context()
+    token = CancelToken()
+    threading.Timer(0.05, token.cancel).start()
+    with pytest.raises(asyncio.CancelledError):
+        context.eval("while (true) {}", cancel_token=token)
+    assert token.cancelled
+    assert context.eval("1 + 1") == 2
+    with pytest.raises(asyncio.CancelledError):
+        context.eval("1 + 1", cancel_token=token)
+
+
+def test_context_resolve_graph(tmp_path) -> None:
+    (tmp_path / "dep.ts").write_text("export const n: number = 1;")
+    entry = tmp_path / "main.js"
+    entry.write_text("import { n } from './dep.ts'; export default n;")
+    context = Context()
+    graph = context.resolve_graph(JsModule.load(str(entry)))
+    main, dep = entry.as_uri(), (tmp_path / "dep.ts").as_uri()
+    assert graph[main]["imports"] == [dep]
+    assert graph[dep]["imports"] == []
+    assert len(graph[dep]["sha256"]) == 64
+
+
+def test_js_module_load_dir_extensions(tmp_path) -> None:
+    for name in ("a.js", "b.mjs", "c.txt"):
+        (tmp_path / name).write_text("export default 1;")
+    default = JsModule.load_dir(str(tmp_path))
+    assert [Path(m.filename).name for m in default] == ["a.js"]
+    chosen = JsModule.load_dir(str(tmp_path), [".mjs", "js"])
+    assert sorted(Path(m.filename).name for m in chosen) == ["a.js", "b.mjs"]
+
+
+def test_context_memoryview() -> None:
+    context = Context(memoryview=True)
+    view = context.eval("new Uint8Array([1, 2, 3, 4]).subarray(1)")
+    assert isinstance(view, memoryview) and view.readonly
+    assert bytes(view) == b"\x02\x03\x04"
+    shared = context.eval("new ArrayBuffer(2, { maxByteLength: 8 })")
+    assert shared == b"\x00\x00"
+
+
+def test_context_shared_pool() -> None:
+    contexts = [Context(shared_pool=True) for _ in range(2)]
+    for i, context in enumerate(contexts):
+        context.eval("async function later(n) { await new Promise((r) => setTimeout(r, 5)); return n; }")
+        assert context.run_until_complete(context.call_async("later", i)) == i
+
+
+def test_promise_result_json() -> None:
+    context = Context()
+    context.eval("async function body() { return {ok: true, items: [1, 2]}; }")
+    promise = context.call_async("body")
+    with pytest.raises(InvalidStateError):
+        promise.result_json()
+    context.run_until_complete(promise)
+    assert promise.result_json() == '{"ok":true,"items":[1,2]}'
+
+
+def test_context_mount(tmp_path: Path) -> None:
+    (tmp_path / "x.txt").write_text("hello")
+    context = Context()
+    context.mount("/data", str(tmp_path))
+    assert context.eval("fs.readFileSync('/data/x.txt', 'utf8')") == "hello"
+    assert context.eval("fs.readdirSync('/data')") == ["x.txt"]
+    with pytest.raises(RuntimeError, match="EROFS"):
+        context.eval("fs.writeFileSync('/data/y.txt', 'no')")
+    with pytest.raises(RuntimeError, match="EACCES"):
+        context.eval("fs.readFileSync('/data/../etc/passwd', 'utf8')")
+    context.mount("/out", str(tmp_path), readonly=False)
+    context.eval("fs.writeFileSync('/out/y.txt', 'yes')")
+    assert (tmp_path / "y.txt").read_text() == "yes"
+    outside = tmp_path.parent / f"{tmp_path.name}-outside.txt"
+    (tmp_path / "link.txt").symlink_to(outside)
+    with pytest.raises(RuntimeError):
+        context.eval("fs.writeFileSync('/out/link.txt', 'escaped')")
+    assert not outside.exists()
+
+
+def test_context_numeric_strings_as_decimal() -> None:
+    context = Context(numeric_strings_as_decimal=True)
+    assert context.eval("'1.10'") == Decimal("1.10")
+    assert str(context.eval("'1.10'")) == "1.10"
+    assert context.eval("({total: '-2.5e3', name: 'x1'})") == {"total": Decimal("-2.5e3"), "name": "x1"}
+    assert context.eval("'NaN'") == "NaN"
+    assert Context().eval("'1.10'") == "1.10"
+
+
+def test_context_timeout_error() -> None:
+    context = Context(timeout_ms=50)
+    module = JsModule("slow.js", "await new Promise((r) => setTimeout(r, 2000));")
+    with pytest.raises(TimeoutError) as info:
+        context.load_module(module)
+    assert isinstance(info.value, builtins.TimeoutError)
+    with pytest.raises(RuntimeError) as info:
+        context.eval("throw new Error('boom')")
+    assert not isinstance(info.value, builtins.TimeoutError)
+
+
+def test_context_call_blob() -> None:
+    context = Context()
+    context.eval("function pack(...args) { return args; }")
+    # [1, "a", {"k": true}] as msgpack
+    assert context.call_blob("pack", b"\x93\x01\xa1a\x81\xa1k\xc3") == [1, "a", {"k": True}]
+    # [-2, "a"] as cbor
+    assert context.call_blob("pack", b"\x82\x21\x61a", format="cbor") == [-2, "a"]
+    assert context.call_blob("pack", b'{"x": 1}', format="json") == [{"x": 1}]
+    with pytest.raises(ValueError):
+        context.call_blob("pack", b"\x93\x01", format="msgpack")
+    # 1 behind the cbor self-describe tag
+    assert context.call_blob("pack", b"\xd9\xd9\xf7\x01", format="cbor") == [1]
+    # [1, 2] as an indefinite length cbor array, 1 as a cbor bignum
+    assert context.call_blob("pack", b"\x9f\x01\x02\xff", format="cbor") == [1, 2]
+    assert context.call_blob("pack", b"\xc2\x41\x01", format="cbor") == [1]
+    context.eval(
+        "function kinds(...args) {"
+        " return args.map((a) => a instanceof Uint8Array ? [...a] : Number.isNaN(a) ? 'NaN' : a); }"
+    )
+    # ["NaN", b"ab"] as msgpack and as cbor with a half precision NaN
+    assert context.call_blob("kinds", b"\x92\xcb\x7f\xf8\x00\x00\x00\x00\x00\x00\xc4\x02ab") == [
+        "NaN",
+        [97, 98],
+    ]
+    assert context.call_blob("kinds", b"\x82\xf9\x7e\x00\x42ab", format="cbor") == ["NaN", [97, 98]]
+    assert context.call_blob("kinds", b"\xfa\xff\x80\x00\x00", format="cbor") == [float("-inf")]
+    unsupported = [
+        (b"\xd6\xff\x00\x00\x00\x00", "msgpack"),  # timestamp extension
+        (b"\xd8\x18\x41\x01", "cbor"),  # embedded cbor tag
+        (b"\x3b\xff\xff\xff\xff\xff\xff\xff\xff", "cbor"),  # below i64
+    ]
+    for blob, format in unsupported:
+        with pytest.raises(ValueError):
+            context.call_blob("pack", blob, format=format)
+
+
+def test_context_unregister_function() -> None:
+    context = Context()
+    context.register_functions({"b": lambda: 2, "a": lambda: 1})
+    assert context.registered_functions() == ["a", "b"]
+    context.unregister_function("a")
+    assert context.registered_functions() == ["b"]
+    assert context.eval("(() => { try { a(); } catch (e) { return e.name; } })()") == "ReferenceError"
+    assert context.eval("b()") == 2
+    with pytest.raises(KeyError):
+        context.unregister_function("a")
+
+
+def test_context_shared_module_cache(tmp_path) -> None:
+    lib = tmp_path / "shared_lib.js"
+    lib.write_text("export const double = (n) => n * 2;")
+    entry = tmp_path / "main.js"
+    entry.write_text("import { double } from './shared_lib.js'; export const run = (n) => double(n);")
+    Context(shared_module_cache=True).load_module(JsModule.load(str(lib)))
+    for n in range(3):
+        context = Context(shared_module_cache=True)
+        handle = context.load_module(JsModule.load(str(entry)))
+        assert context.call_module(handle, "run", n) == n * 2
+
+
+def test_context_shared_module_cache_reads_the_file(tmp_path) -> None:
+    lib = tmp_path / "lib.js"
+    lib.write_text("export const where = 'disk';")
+    entry = tmp_path / "main.js"
+    entry.write_text("import { where } from './lib.js'; export const run = () => where;")
+    Context(shared_module_cache=True).load_module(JsModule(str(lib), "export const where = 'memory';"))
+    context = Context(shared_module_cache=True)
+    assert context.call_module(context.load_module(JsModule.load(str(entry))), "run") == "disk"
+    lib.write_text("export const where = 'changed';")
+    context = Context(shared_module_cache=True)
+    assert context.call_module(context.load_module(JsModule.load(str(entry))), "run") == "changed"
+
+
+def test_context_regexp() -> None:
+    regexp = Context().eval("/^(?<year>\\d{4})-\\k<year>$/gi")
+    assert isinstance(regexp, JsRegExp)
+    assert (regexp.source, regexp.flags) == ("^(?<year>\\d{4})-\\k<year>$", "gi")
+    pattern = Context(regex_as_pattern=True).eval("/^(?<year>\\d{4})-\\k<year>$/gi")
+    assert isinstance(pattern, re.Pattern)
+    assert pattern.flags & re.IGNORECASE
+    assert pattern.match("2024-2024").group("year") == "2024"
+
+
+def test_promise_poll_exception() -> None:
+    context = Context()
+    context.eval("async function fail() { await null; throw new Error('nope'); }")
+    promise = context.call_async("fail")
+    exception = None
+    for _ in range(100):
+        exception = promise.poll_exception(context)
+        if exception is not None:
+            break
+        context.advance()
+    assert isinstance(exception, RuntimeError) and "nope" in str(exception)
+    context.eval("async function ok() { return 1; }")
+    assert context.run_until_complete(context.call_async("ok")) == 1
+    done = context.call_async("ok")
+    context.run_until_complete(done)
+    assert done.poll_exception(context) is None
+
+
+def test_promise_context_alive_and_repr() -> None:
+    context = Context()
+    context.eval("async function later() { await null; return 7; }")
+    context.eval("async function fail() { throw new Error('nope'); }")
+    pending = context.call_async("later")
+    assert pending.context_alive() and repr(pending) == "<JSPromise pending>"
+    context.run_until_complete(pending)
+    assert pending.context_alive() and repr(pending) == "<JSPromise finished result=7>"
+    failed = context.call_async("fail")
+    assert failed.step(context)
+    assert repr(failed).startswith("<JSPromise finished exception=") and "nope" in repr(failed)
+    orphaned = context.call_async("later")
+    context.recycle()
+    assert not orphaned.context_alive() and repr(orphaned) == "<JSPromise orphaned>"
+    # A finished promise keeps its result after the runtime that made it is gone
+    assert not pending.context_alive() and repr(pending) == "<JSPromise finished result=7>"
+
+
+def test_promise_debug_state() -> None:
+    context = Context()
+    context.eval("async function big() { await null; return 'x'.repeat(500); }")
+    promise = context.call_async("big")
+    assert promise.debug_state() == {"done": False, "has_result": False, "has_exception": False}
+    context.run_until_complete(promise)
+    state = promise.debug_state()
+    assert state["done"] and state["has_result"] and not state["has_exception"]
+    assert state["preview"].startswith("'xxx") and len(state["preview"]) == 83
+
+
+def test_context_console_error_raises() -> None:
+    context = Context(console_error_raises=True)
+    context.eval("console.log('fine')")
+    with pytest.raises(JsConsoleError) as info:
+        context.eval("console.error('failed', 42, {code: 'E1'})")
+    assert info.value.args == ("failed", 42, {"code": "E1"})
+    assert context.eval("1") == 1
+    context.eval("setTimeout(() => console.error('late'), 0)")
+    with pytest.raises(JsConsoleError, match="late"):
+        while context.advance():
+            pass
+    assert Context().eval("console.error('ignored'); 2") == 2
+
+
+def test_context_console_error_raises_everywhere(tmp_path) -> None:
+    context = Context(console_error_raises=True)
+    context.eval(
+        "function shout(...args) { console.error('shout'); return args.length; }"
+        "Object.defineProperty(globalThis, 'loud', { get() { console.error('get'); return 1; } });"
+    )
+    arguments = tmp_path / "args.json"
+    arguments.write_text("[1]")
+    module = tmp_path / "loud.js"
+    module.write_text("export const shout = () => { console.error('module'); return 1; };")
+    handle = context.load_module(JsModule.load(str(module)))
+    calls = [
+        lambda: context.call_full("shout", [1], {"k": 2}),
+        lambda: context.call_blob("shout", b"[1]", format="json"),
+        lambda: context.call_from_file("shout", str(arguments)),
+        lambda: context.call_module(handle, "shout"),
+        lambda: context.get_value("loud"),
+        lambda: context.try_get_value("loud"),
+        lambda: context.eval_display("shout()"),
+        lambda: context.eval_bytes("shout(), new Uint8Array(1)"),
+    ]
+    for call in calls:
+        with pytest.raises(JsConsoleError):
+            call()
+    assert isinstance(context.eval_result("shout()").err(), JsConsoleError)
+    assert context.eval("1") == 1
+
+
+def test_context_minimal() -> None:
+    context = Context(minimal=True)
+    for name in ("console", "setTimeout", "fetch", "crypto", "Deno"):
+        assert context.eval(f"typeof {name}") == "undefined"
+    assert context.eval("[1, 2, 3].map((n) => n * 2)") == [2, 4, 6]
+    context.register_function("add", lambda a, b: a + b)
+    assert context.eval("add(1, 2)") == 3
+
+
+def test_context_web_apis() -> None:
+    context = Context(web_apis=["url", "encoding"])
+    assert context.eval("new URL('https://a.test/x').pathname") == "/x"
+    assert context.eval("btoa('hi')") == "aGk="
+    for name in ("console", "setTimeout", "fetch", "crypto"):
+        assert context.eval(f"typeof {name}") == "undefined"
+    assert context.eval("typeof Deno") == "object"
+    with pytest.raises(ValueError):
+        Context(web_apis=["url", "nope"])
+    with pytest.raises(ValueError):
+        Context(web_apis=["url"], minimal=True)
+
+
+def test_context_eval_bytes() -> None:
+    context = Context()
+    assert context.eval_bytes("new Uint8Array([137, 80, 78, 71]).subarray(1)") == b"PNG"
+    assert context.eval_bytes("new ArrayBuffer(2)") == b"\x00\x00"
+    with pytest.raises(TypeError):
+        context.eval_bytes("'not binary'")
+
+
+def test_context_random_seed() -> None:
+    draw = "Array.from({length: 5}, () => Math.random())"
+    first = Context(random_seed=42).eval(draw)
+    assert first == Context(random_seed=42).eval(draw)
+    assert first != Context(random_seed=7).eval(draw)
+    assert all(0 <= n < 1 for n in first)
+
+
+def test_versions_and_features() -> None:
+    assert pyrv8.v8_version().split(".")[0].isdigit()
+    assert pyrv8.deno_core_version().startswith("0.")
+    assert {"console", "url", "crypto"} <= set(Context().supported_features())
+    assert Context(minimal=True).supported_features() == ["webassembly"]
+
+
+def test_context_set_arg_encoder() -> None:
+    context = Context()
+    context.eval("function echo(v) { return v; }")
+    with pytest.raises(ValueError):
+        context.call("echo", 1 + 2j)
+    context.set_arg_encoder(lambda v: {"re": v.real, "im": v.imag})
+    assert context.call("echo", 1 + 2j) == {"re": 1.0, "im": 2.0}
+    assert context.call("echo", [1, 3j]) == [1, {"re": 0.0, "im": 3.0}]
+    context.set_arg_encoder(lambda v: v)
+    with pytest.raises(ValueError):
+        context.call("echo", 1j)
+
+
+def test_context_decimal_args() -> None:
+    from fractions import Fraction
+
+    context = Context()
+    context.eval("function echo(v) { return v; } function kind(v) { return typeof v; }")
+    assert context.call("echo", Decimal("1.5")) == 1.5
+    assert context.call("kind", Decimal("1.5")) == "number"
+    assert context.call("echo", {"price": [Fraction(3, 2)]}) == {"price": [1.5]}
+    with pytest.warns(RuntimeWarning):
+        assert context.call("echo", Decimal("0.1")) == 0.1
+    exact = Context(decimal_args_as_strings=True)
+    exact.eval("function echo(v) { return v; }")
+    assert exact.call("echo", Decimal("1.10")) == "1.10"
+    assert exact.call("echo", Fraction(1, 3)) == "1/3"
+
+
+def test_context_exit_handler() -> None:
+    flushed = []
+    with Context() as context:
+        context.register_function("flush", flushed.append)
+        context.eval("globalThis.buffer = ['a', 'b']")
+        context.set_exit_handler("flush(buffer.join(''))")
+    assert flushed == ["ab"]
+    context.close()
+    assert flushed == ["ab"]
+
+    failing = Context()
+    failing.set_exit_handler("throw new Error('cleanup broke')")
+    with pytest.warns(RuntimeWarning, match="cleanup broke"):
+        failing.close()
+
+    stuck = Context()
+    stuck.set_exit_handler("while (true) {}", timeout=0.05)
+    with pytest.warns(RuntimeWarning):
+        stuck.close()
+
+
+def test_context_use_after_close() -> None:
+    context = Context()
+    stream = context.console_stream()
+    context.close()
+    for use in (
+        lambda: context.eval("1 + 1"),
+        lambda: context.call("String", 1),
+        lambda: context.register_function("f", print),
+        lambda: context.registered_functions(),
+        lambda: context.console_stream(),
+        lambda: context.__enter__(),
+    ):
+        with pytest.raises(RuntimeError, match="context closed"):
+            use()
+    assert context.name.startswith("context-")
+
+    async def rest() -> list[str]:
+        return [line async for line in stream]
+
+    assert asyncio.run(rest()) == []
+
+
+def test_context_abort_all() -> None:
+    context = Context()
+    context.eval("globalThis.fired = false; setTimeout(() => { fired = true; }, 10);")
+    context.eval("async function slow() { await new Promise((r) => setTimeout(r, 50)); return 1; }")
+    promise = context.call_async("slow")
+    context.abort_all()
+    with pytest.raises(asyncio.CancelledError):
+        context.run_until_complete(promise)
+    assert context.run_until_complete(context.call_async("slow")) == 1
+    assert context.eval("fired") is False
+
+
+def test_context_nested_binary() -> None:
+    context = Context()
+    result = context.eval("({name: 'x', thumbnail: new Uint8Array([1, 2]), parts: [new ArrayBuffer(1)]})")
+    assert result == {"name": "x", "thumbnail": b"\x01\x02", "parts": [b"\x00"]}
+    views = Context(memoryview=True).eval("({thumbnail: new Uint8Array([1, 2])})")
+    assert isinstance(views["thumbnail"], memoryview)
+
+
+@pytest.mark.skipif(not hasattr(Context, "profile"), reason="built without the profiler feature")
+def test_context_profile() -> None:
+    result, profile = Context().profile("let n = 0; for (let i = 0; i < 1e5; i++) n += i; n")
+    assert result == sum(range(100000))
+    assert "nodes" in json.loads(profile)
+
+
+def test_context_json_modules(tmp_path) -> None:
+    (tmp_path / "config.json").write_text('{"name": "demo", "sizes": [1, 2, 3]}')
+    entry = tmp_path / "main.js"
+    entry.write_text(
+        "import config from './config.json' assert { type: 'json' };\n"
+        "export const describe = () => `${config.name}:${config.sizes.length}`;"
+    )
+    context = Context()
+    handle = context.load_module(JsModule.load(str(entry)))
+    assert context.call_module(handle, "describe") == "demo:3"
+    loaded = sorted(Path(m.filename).name for m in JsModule.load_dir(str(tmp_path), ["js", "json"]))
+    assert loaded == ["config.json", "main.js"]
+    (tmp_path / "proto.json").write_text('{"__proto__": {"polluted": true}}')
+    check = tmp_path / "check.js"
+    check.write_text(
+        "import p from './proto.json' with { type: 'json' };\n"
+        "export const own = () => [Object.hasOwn(p, '__proto__'), p.polluted ?? null];"
+    )
+    # Loaded first so the import gets the module made from it
+    context.load_module(JsModule.load(str(tmp_path / "proto.json")))
+    handle = context.load_module(JsModule.load(str(check)))
+    assert context.call_module(handle, "own") == [True, None]
+
+
+def test_context_json_modules_whitelist(tmp_path) -> None:
+    secret = tmp_path / "secret.json"
+    secret.write_text('{"token": "x"}')
+    context = Context()
+    with pytest.raises(RuntimeError):
+        context.eval(f"import({json.dumps(secret.as_uri())}, {{ with: {{ type: 'json' }} }})")
+    app = tmp_path / "app"
+    app.mkdir()
+    (app / "main.js").write_text("import secret from '../secret.json' with { type: 'json' }; export default secret;")
+    with pytest.raises(RuntimeError):
+        context.load_module(JsModule.load(str(app / "main.js")))
+
+
+def test_context_return_timing() -> None:
+    context = Context()
+    result, timing = context.eval("let n = 0; for (let i = 0; i < 1e5; i++) n += i; n", return_timing=True)
+    assert result == sum(range(100000))
+    assert timing["wall_ms"] >= 0 and timing["lock_ms"] >= 0
+    context.eval("function add(a, b) { return a + b; }")
+    result, timing = context.call("add", 1, 2, return_timing=True)
+    assert result == 3 and "wall_ms" in timing
+
+
+def test_context_preserve_surrogates() -> None:
+    code = "'ok \\ud83d\\ude00 bad \\ud83d'"
+    units = Context().eval(f"(s => Array.from({{length: s.length}}, (_, i) => s.charCodeAt(i)))({code})")
+    assert Context().eval(code) == "ok \U0001f600 bad �"
+    context = Context(preserve_surrogates=True)
+    text = context.eval(code)
+    assert text == "ok \U0001f600 bad \ud83d"
+    encoded = text.encode("utf-16-le", "surrogatepass")
+    assert list(memoryview(encoded).cast("H")) == units
+    assert context.eval(f"({{[{code}]: 1}})") == {text: 1}
+
+
+def test_context_call_full() -> None:
+    context = Context()
+    context.eval("function request(url, method, options) { return [url, method, options]; }")
+    result = context.call_full("request", ["/api", "GET"], {"timeout": 5, "retry": True})
+    assert result == ["/api", "GET", {"timeout": 5, "retry": True}]
+    assert context.call_full("request", ("/api",)) == ["/api", None, None]
+
+
+def test_js_handle_resolved_path(tmp_path, monkeypatch) -> None:
+    (tmp_path / "plugin.js").write_text("export const n = 1;")
+    monkeypatch.chdir(tmp_path)
+    context = Context()
+    handle = context.load_module(JsModule.load("./plugin.js"))
+    assert handle.filename == "./plugin.js"
+    assert handle.resolved_path() == str(tmp_path.resolve() / "plugin.js")
+
+
+def test_context_load_module_dedup(tmp_path) -> None:
+    context = Context()
+    context.eval("globalThis.runs = 0")
+    module = JsModule("counter.js", "globalThis.runs++; export const n = 1;")
+    first = context.load_module(module)
+    assert context.load_module(JsModule("./counter.js", "globalThis.runs++; export const n = 1;")) is first
+    assert context.eval("runs") == 1
+    assert context.load_module(module, force=True) is not first
+    assert context.eval("runs") == 2
+    path = tmp_path / "plugin.js"
+    path.write_text("export const version = () => 1;")
+    old = context.load_module(JsModule.load(str(path)))
+    path.write_text("export const version = () => 2;")
+    new = context.load_module(JsModule.load(str(path)))
+    assert new is not old
+    assert context.call_module(new, "version") == 2
+    assert context.load_module(JsModule.load(str(path))) is new
+
+
+def test_js_handle_describe_export() -> None:
+    context = Context()
+    handle = context.load_module(
+        JsModule("exports.js", "export const n = 1; export const o = {}; export default function f() {}")
+    )
+    assert handle.describe_export(context, "n") == "number"
+    assert handle.describe_export(context, "o") == "object"
+    assert handle.describe_export(context, "default") == "function"
+    with pytest.raises(KeyError):
+        handle.describe_export(context, "missing")
+
+
+def test_context_eval_into() -> None:
+    context = Context()
+    assert context.eval_into("counter", "(() => { let n = 0; return { next: () => ++n }; })()") is None
+    context.eval("counter.next()")
+    assert context.eval("counter.next()") == 2
+    context.eval_into("big", "new Map([[1, {a: [1, 2]}]])")
+    assert context.eval("big instanceof Map && big.get(1).a[1]") == 2
+    with pytest.raises(RuntimeError):
+        context.eval_into("broken", "throw new Error('no')")
+    assert context.eval("typeof broken") == "undefined"
+
+
+def test_context_verbose_errors() -> None:
+    code = "function fail() { throw new Error('outer', {cause: new Error('inner')}); }\nfail()"
+    with pytest.raises(RuntimeError) as terse:
+        Context(name="t", verbose_errors=False).eval(code)
+    assert str(terse.value) == "[t] Uncaught Error: outer"
+    with pytest.raises(RuntimeError) as verbose:
+        Context(verbose_errors=True).eval(code)
+    message = str(verbose.value)
+    assert "at fail" in message and "Caused by: Error: inner" in message
+    assert "throw new Error" in message
+
+
+def test_context_integer_keys() -> None:
+    code = "({0: 'a', 1: 'b', '-2': 'c', '01': 'd', x: 'e'})"
+    assert Context(integer_keys=True).eval(code) == {0: "a", 1: "b", -2: "c", "01": "d", "x": "e"}
+    assert Context().eval(code) == {"0": "a", "1": "b", "-2": "c", "01": "d", "x": "e"}
+
+
+def test_gather_results() -> None:
+    context = Context()
+    context.eval("var ok = async (v) => v; var fail = async () => { throw new Error('bad'); }")
+    promises = [context.call_async("ok", 1), context.call_async("fail"), context.call_async("ok", 3)]
+    with pytest.raises(InvalidStateError):
+        pyrv8.gather_results(promises)
+    while not all(promise.step(context) for promise in promises):
+        context.advance()
+    with pytest.raises(RuntimeError, match="bad"):
+        pyrv8.gather_results(promises)
+    first, error, last = pyrv8.gather_results(promises, return_exceptions=True)
+    assert (first, last) == (1, 3) and isinstance(error, RuntimeError)
+
+
+def test_context_last_error() -> None:
+    context = Context()
+    assert context.last_error() is None
+    context.eval("setTimeout(() => { throw new Error('late'); }, 1)")
+    for _ in range(100):
+        try:
+            if not context.advance():
+                break
+        except RuntimeError:
+            break
+    error = context.last_error()
+    assert isinstance(error, RuntimeError) and "late" in str(error)
+    assert context.last_error() is None
+
+    context.eval("async function fail() { throw new Error('awaited'); }")
+    with pytest.raises(RuntimeError):
+        context.run_until_complete(context.call_async("fail"))
+    assert "awaited" in str(context.last_error())
+    # Rejected as soon as it's called so there's no need to advance
+    assert context.call_async("fail").step(context)
+    assert "awaited" in str(context.last_error())
+
+    context.set_unhandled_rejection_handler(lambda reason: None)
+    context.eval("Promise.reject(new Error('handled')); undefined")
+    context.advance()
+    assert "handled" in str(context.last_error())
+
+    logged = Context(log_errors=True)
+    logged.eval("setTimeout(() => { throw new TypeError('logged'); }); undefined")
+    while logged.advance():
+        pass
+    assert "logged" in str(logged.last_error())
+
+
+def test_context_eval_assert() -> None:
+    context = Context()
+    assert context.eval_assert("[1, 2, 3]", lambda r: len(r) == 3) == [1, 2, 3]
+    with pytest.raises(AssertionError, match=r"\[1, 2\]"):
+        context.eval_assert("[1, 2]", lambda r: len(r) == 3)
+    with pytest.raises(ValueError):
+        context.eval_assert("1", None)
+
+
+def test_context_eval_top_level_await() -> None:
+    context = Context(timeout=1)
+    assert context.eval("await Promise.resolve(2) * 3", allow_top_level_await=True) == 6
+    code = "const a = await Promise.resolve(1); return a + 1;"
+    assert context.eval(code, allow_top_level_await=True) == 2
+    with pytest.raises(pyrv8.TimeoutError):
+        context.eval("await new Promise(() => {})", allow_top_level_await=True)
+
+
+def test_context_eval_lazy() -> None:
+    context = Context()
+    proxy = context.eval_lazy("({a: 1, b: {c: [1, 2]}, 3: 'x'})")
+    assert proxy["a"] == 1 and proxy["b"] == {"c": [1, 2]} and proxy[3] == "x"
+    assert "b" in proxy and "z" not in proxy
+    assert sorted(proxy.keys()) == ["3", "a", "b"] and len(proxy) == 3
+    with pytest.raises(KeyError):
+        proxy["z"]
+    with pytest.raises(TypeError):
+        context.eval_lazy("1")
+    context.recycle()
+    with pytest.raises(pyrv8.InvalidStateError):
+        proxy["a"]
+
+
+def test_context_mock_time() -> None:
+    context = Context()
+    with pytest.raises(RuntimeError):
+        context.advance_mock_time(1)
+    context.set_mock_time(1_700_000_000_000)
+    assert context.eval("Date.now()") == 1_700_000_000_000
+    context.advance_mock_time(250)
+    assert context.eval("new Date().getTime()") == 1_700_000_000_250
+    assert context.eval("new Date(0).getTime() === 0 && new Date() instanceof Date") is True
+    context.recycle()
+    assert context.eval("Date.now()") == 1_700_000_000_250
+    context.set_mock_time(None)
+    assert context.eval("Date.now()") > 1_700_000_000_250
+
+
+def test_context_eval_await() -> None:
+    context = Context()
+    context.eval("var later = (v, ms) => new Promise((resolve) => setTimeout(() => resolve(v), ms))")
+    assert context.eval_await("later(5, 10)") == 5
+    assert context.eval_await("1 + 1") == 2
+    with pytest.raises(RuntimeError, match="nope"):
+        context.eval_await("Promise.reject(new Error('nope'))")
+    with pytest.raises(TimeoutError):
+        context.eval_await("later(1, 5000)", timeout=0.05)
+    assert context.eval_await("later('again', 1)", timeout=1) == "again"
+    with pytest.raises(TimeoutError):
+        context.eval_await("while (true) {}", timeout=0.1)
+    assert context.eval_await("later('after', 1)", timeout=1) == "after"
+
+
+def test_context_eval_await_timeout_ms() -> None:
+    context = Context()
+    context.eval("var later = (v, ms) => new Promise((resolve) => setTimeout(() => resolve(v), ms))")
+    with pytest.raises(TimeoutError):
+        context.eval_await("later(1, 5000)", timeout_ms=50)
+    assert context.eval_await("later('ms', 1)", timeout_ms=1000) == "ms"
+    with pytest.raises(ValueError):
+        context.eval_await("1", timeout=1, timeout_ms=1000)
+    with pytest.raises(ValueError):
+        context.eval_await("1", timeout=-1)
+    seconds = Context(timeout=0.05)
+    seconds.eval("var later = (v, ms) => new Promise((resolve) => setTimeout(() => resolve(v), ms))")
+    with pytest.raises(TimeoutError):
+        seconds.eval_await("later(1, 5000)")
+    # The override replaces the context's own timeout rather than adding to it
+    assert seconds.eval_await("later(2, 100)", timeout_ms=1000) == 2
+
+
+def test_context_eval_stream() -> None:
+    context = Context()
+    chunks = []
+    code = "for (let i = 0; i < 3; i++) __emit(`line ${i}`); setTimeout(() => __emit({done: true}), 1); 3"
+    assert context.eval_stream(code, chunks.append) == 3
+    assert chunks == ["line 0", "line 1", "line 2", {"done": True}]
+    assert context.eval("typeof __emit") == "undefined"
+    with pytest.raises(ValueError):
+        context.eval_stream("1", 1)
+
+
+def test_context_dump_globals() -> None:
+    context = Context()
+    context.eval("var count = 2; globalThis.config = {debug: true, tags: ['a']}; var big = 1n; var f = () => 1")
+    globals = context.dump_globals()
+    assert globals["count"] == 2 and globals["config"] == {"debug": True, "tags": ["a"]}
+    assert "big" not in globals and "f" not in globals
+
+
+def test_context_name() -> None:
+    context = Context(name="worker-1")
+    assert context.name == "worker-1"
+    with pytest.raises(RuntimeError, match=r"^\[worker-1\] "):
+        context.eval("throw new Error('no')")
+    first, second = Context(), Context()
+    assert first.name.startswith("context-") and first.name != second.name
+
+
+def test_context_eval_result() -> None:
+    context = Context()
+    results = [context.eval_result(code) for code in ("1 + 1", "throw new Error('boom')")]
+    assert all(isinstance(r, JsResult) for r in results)
+    ok, failed = results
+    assert ok.is_ok() and ok.unwrap() == 2 and ok.err() is None
+    assert failed.is_err() and not failed
+    assert "boom" in str(failed.err())
+    with pytest.raises(RuntimeError, match="boom"):
+        failed.unwrap()
+
+
+def test_context_eval_display() -> None:
+    context = Context()
+    assert context.eval_display("({a: 1, b: [1, 2]})") == "{ a: 1, b: [ 1, 2 ] }"
+    assert context.eval_display("'hi'") == '"hi"'
+    assert context.eval_display("undefined") == "undefined"
+    assert context.eval_display("new Map([[1, 2]])") == "Map(1) { 1 => 2 }"
+    assert Context(minimal=True).eval_display("[1, 2]") == "1,2"
+
+
+def test_context_circular_results() -> None:
+    code = "const node = {name: 'a', children: []}; node.children.push(node); node"
+    with pytest.raises(ValueError, match="circular reference"):
+        Context().eval(code)
+    context = Context(mark_circular=True)
+    assert context.eval(code) == {"name": "a", "children": ["[Circular]"]}
+    assert context.eval("const leaf = {n: 1}; [leaf, leaf]") == [{"n": 1}, {"n": 1}]
+    assert Context(cache_results=True, mark_circular=True).eval(code)["children"] == ["[Circular]"]
+
+
+def test_context_module_dir(tmp_path) -> None:
+    (tmp_path / "b_second.js").write_text("globalThis.order = (globalThis.order ?? []).concat('b');")
+    (tmp_path / "a_first.ts").write_text("const n: number = 1; globalThis.order = [`a${n}`]; export const name = 'a';")
+    (tmp_path / "notes.txt").write_text("not a module")
+    context = Context(module_dir=str(tmp_path))
+    assert context.eval("order") == ["a1", "b"]
+    assert [Path(m.filename).name for m in context.modules] == ["a_first.ts", "b_second.js"]
+    assert Context().modules == []
+    (tmp_path / "c_broken.js").write_text("throw new Error('plugin failed')")
+    with pytest.raises(RuntimeError, match="plugin failed"):
+        Context(module_dir=str(tmp_path))
+
+
+def test_context_warmup() -> None:
+    context = Context()
+    context.eval("var calls = 0; function square(n) { calls++; return n * n; }")
+    assert context.warmup("square", 50, 3) is None
+    assert context.eval("calls") == 50
+    with pytest.raises(RuntimeError):
+        context.warmup("missing", 1)
+
+
+def test_context_put_buffer() -> None:
+    context = Context()
+    data = bytes(range(256)) * 4096
+    context.put_buffer("asset", data)
+    assert context.eval("asset instanceof ArrayBuffer && asset.byteLength") == len(data)
+    assert context.eval("new Uint8Array(asset)[257]") == 1
+    assert context.get_value("asset") == data
+
+
+def test_context_shared_buffer() -> None:
+    context = Context()
+    context.create_shared_buffer("state", 8)
+    assert context.read_shared_buffer("state") == bytes(8)
+    context.eval("function tick() { const view = new Uint8Array(state); view[0] += 1; view[7] = 255; }")
+    for _ in range(3):
+        context.call("tick")
+    assert context.read_shared_buffer("state") == bytes([3, 0, 0, 0, 0, 0, 0, 255])
+    context.recycle()
+    assert context.eval("new Uint8Array(state)[0]") == 3
+    with pytest.raises(KeyError):
+        context.read_shared_buffer("missing")
+
+
+def test_context_default_locale() -> None:
+    code = "new Intl.NumberFormat().format(1234567.5)"
+    assert Context(default_locale="de-DE").eval(code) == "1.234.567,5"
+    assert Context(default_locale="en-US").eval(code) == "1,234,567.5"
+    assert Context(default_locale="de-DE").eval("Intl.DateTimeFormat().resolvedOptions().locale") == "de-DE"
+    with pytest.raises(ValueError):
+        Context(default_locale="not a locale!")
+
+
+def test_context_max_result_bytes() -> None:
+    context = Context(max_result_bytes=100)
+    assert context.eval("'x'.repeat(10)") == "x" * 10
+    with pytest.raises(ValueError):
+        context.eval("'x'.repeat(1000)")
+    with pytest.raises(ValueError):
+        context.eval("Array.from({length: 100}, (_, i) => i)")
+    # Maps and bigints json would have made empty or refused are measured too
+    with pytest.raises(ValueError):
+        context.eval("new Map([['k', 'x'.repeat(200)]])")
+    with pytest.raises(ValueError):
+        context.eval("10n ** 200n")
+    assert Context(max_result_bytes=100, mark_circular=True).eval("const o = {}; o.o = o; o") == {"o": "[Circular]"}
+
+
+def test_context_lock_timeout() -> None:
+    context = Context(lock_timeout=0.5)
+    assert context.eval("1 + 1") == 2
+    assert context.eval("[1, 2].length") == 2
+    with pytest.raises(ValueError):
+        Context(lock_timeout=-1)
+
+
+def test_context_registered_function_exceptions() -> None:
+    class OutOfStock(Exception):
+        pass
+
+    def reserve(item):
+        raise OutOfStock(item)
+
+    context = Context()
+    context.register_function("reserve", reserve)
+    with pytest.raises(OutOfStock, match="widget") as info:
+        context.eval("reserve('widget')")
+    assert info.traceback[-1].name == "reserve"
+    assert context.eval("try { reserve('widget') } catch (e) { e.message }").endswith("widget")
+    assert "python exception" not in context.eval("try { reserve('widget') } catch (e) { e.stack }")
+    with pytest.raises(RuntimeError):
+        context.eval("throw new Error('unrelated')")
+    # Caught ones aren't re-raised for a later error that happens to share the message
+    with pytest.raises(RuntimeError) as info:
+        context.eval("try { reserve('gadget') } catch (e) {} throw new Error('gadget')")
+    assert not isinstance(info.value, OutOfStock)
+    with pytest.raises(RuntimeError) as info:
+        context.eval("throw new Error('gadget')")
+    assert not isinstance(info.value, OutOfStock)
+
+
+def test_context_import_map(tmp_path) -> None:
+    (tmp_path / "react.js").write_text("export const version = '18';")
+    (tmp_path / "lib").mkdir()
+    (tmp_path / "lib" / "math.js").write_text("export const add = (a, b) => a + b;")
+    entry = tmp_path / "main.js"
+    entry.write_text(
+        "import { version } from 'react'; import { add } from 'lib/math.js';"
+        "export const result = () => `${version}:${add(1, 2)}`;"
+    )
+    import_map = {"imports": {"react": str(tmp_path / "react.js"), "lib/": f"{tmp_path / 'lib'}/"}}
+    context = Context(import_map=import_map)
+    handle = context.load_module(JsModule.load(str(entry)))
+    assert context.call_module(handle, "result") == "18:3"
+    for bad in ({"react": 1}, {"react": "react.js"}, {"lib/": "/lib"}, {"imports": [], "scopes": {}}):
+        with pytest.raises(ValueError):
+            Context(import_map=bad)
+
+
+def test_context_reload_module(tmp_path) -> None:
+    (tmp_path / "config.js").write_text("export const mode = 'old';")
+    (tmp_path / "app.js").write_text("import { mode } from './config.js'; export const current = () => mode;")
+    context = Context()
+    config = context.load_module(JsModule.load(str(tmp_path / "config.js")))
+    app = context.load_module(JsModule.load(str(tmp_path / "app.js")))
+    assert context.call_module(app, "current") == "old"
+    context.reload_module(config, "export const mode = 'new';")
+    assert config.contents == "export const mode = 'new';"
+    assert context.call_module(app, "current") == "new"
+    with pytest.raises(ValueError):
+        Context().reload_module(config, "export const mode = 1;")
+
+
+def test_context_conditions(tmp_path) -> None:
+    package = tmp_path / "node_modules" / "dep"
+    package.mkdir(parents=True)
+    (package / "package.json").write_text(
+        '{"exports": {".": {"browser": "./browser.js", "node": "./node.js"}, "./util/*": "./lib/*.js"}}'
+    )
+    (package / "browser.js").write_text("export const target = 'browser';")
+    (package / "node.js").write_text("import { id } from './lib/id.js'; export const target = id('node');")
+    (package / "lib").mkdir()
+    (package / "lib" / "id.js").write_text("export const id = (x) => x;")
+    entry = tmp_path / "main.js"
+    entry.write_text("import { target } from 'dep'; import { id } from 'dep/util/id'; export const run = () => id(target);")
+    for conditions, expected in ((["import", "node"], "node"), (["browser"], "browser")):
+        context = Context(conditions=conditions)
+        handle = context.load_module(JsModule.load(str(entry)))
+        assert context.call_module(handle, "run") == expected
+    with pytest.raises(RuntimeError):
+        Context().load_module(JsModule.load(str(entry)))
+    other = tmp_path / "other" / "node_modules" / "x"
+    other.mkdir(parents=True)
+    (other / "secret.js").write_text("export const secret = 1;")
+    app = tmp_path / "app"
+    app.mkdir()
+    (app / "main.js").write_text("export { secret } from '../other/node_modules/x/secret.js';")
+    with pytest.raises(RuntimeError):
+        Context(conditions=["node"]).load_module(JsModule.load(str(app / "main.js")))
+
+
+def test_context_module_load_hook(tmp_path) -> None:
+    (tmp_path / "data.json").write_text("[1, 2]")
+    entry = tmp_path / "main.js"
+    entry.write_text("import data from './data.json' with { type: 'json' }; export const n = data.length;")
+    context = Context()
+    loaded = []
+    context.set_module_load_hook(loaded.append)
+    context.load_module(JsModule.load(str(entry)))
+    assert loaded == [entry.as_uri(), (tmp_path / "data.json").as_uri()]
+    with pytest.raises(ValueError):
+        context.set_module_load_hook(1)
+
+
+def test_context_register_async_function() -> None:
+    async def lookup(n):
+        await asyncio.sleep(0.01)
+        return n * 2
+
+    async def fail():
+        raise ValueError("nope")
+
+    context = Context()
+    context.register_async_function("lookup", lookup)
+    context.register_async_function("fail", fail)
+    context.eval(
+        "async function total() { const [a, b] = await Promise.all([lookup(1), lookup(2)]); return a + b; }"
+        "async function tryFail() { try { await fail(); } catch (e) { return String(e); } }"
+    )
+    assert context.run_until_complete(context.call_async("total")) == 6
+    assert context.run_until_complete(context.call_async("tryFail")).endswith("nope")
+    assert {"lookup", "fail"} <= set(context.registered_functions())
+
+
+def test_context_async_function_waits_idle() -> None:
+    async def slow():
+        await asyncio.sleep(0.2)
+        return "done"
+
+    context = Context()
+    context.register_async_function("slow", slow)
+    context.eval("var ticks = 0; const id = setInterval(() => ticks++, 10)")
+    started = time.process_time()
+    assert context.eval("slow()") == "done"
+    assert time.process_time() - started < 0.1
+    assert context.eval("clearInterval(id); ticks") >= 5
+    shared = Context(shared_pool=True)
+    shared.register_async_function("slow", slow)
+    started = time.process_time()
+    assert shared.eval("slow()") == "done"
+    assert time.process_time() - started < 0.1
+
+
+def test_context_keeps_the_gil_without_async_calls() -> None:
+    context = Context()
+    waiting = threading.Thread(target=context.eval, args=("new Promise((r) => setTimeout(r, 100))",))
+    waiting.start()
+    time.sleep(0.02)
+    # Waits on the GIL for the other eval instead of finding the context borrowed
+    assert context.eval("1") == 1
+    waiting.join()
+
+
+def test_context_env() -> None:
+    context = Context(env={"MODE": "test"})
+    assert context.eval("process.env.MODE") == "test"
+    assert context.eval("process.env.MODE = 'prod'; process.env.MODE") == "test"
+    if context.eval("typeof Deno.env.get") == "function":
+        assert context.eval("Deno.env.get('MODE')") == "test"
+    writable = Context(env={"MODE": "test"}, env_writable=True)
+    assert writable.eval("process.env.MODE = 'prod'; process.env.MODE") == "prod"
+    writable.recycle()
+    assert writable.eval("process.env.MODE") == "test"
+    with pytest.raises(ValueError):
+        Context(env={"PORT": 80})
+
+
+def test_context_max_concurrent_ops() -> None:
+    running = [0, 0]
+
+    async def work(n):
+        running[0] += 1
+        running[1] = max(running[1], running[0])
+        await asyncio.sleep(0.01)
+        running[0] -= 1
+        return n
+
+    context = Context(max_concurrent_ops=2)
+    context.register_async_function("work", work)
+    assert context.eval("Promise.all([1, 2, 3, 4, 5].map((n) => work(n)))") == [1, 2, 3, 4, 5]
+    assert running[1] == 2
+    context.eval(
+        "var fired = []; const ids = [1, 2, 3].map((n) => setTimeout(() => fired.push(n), 5));"
+        "clearTimeout(ids[2]); setTimeout(() => fired.push(4), 1)"
+    )
+    context.eval_await("new Promise((resolve) => setTimeout(resolve, 30))")
+    assert sorted(context.eval("fired")) == [1, 2, 4]
+    with pytest.raises(ValueError):
+        Context(max_concurrent_ops=0)
+
+
+def test_js_module_contents_hash(tmp_path) -> None:
+    path = tmp_path / "mod.js"
+    path.write_text("export const n = 1;")
+    module = JsModule.load(str(path))
+    digest = module.contents_hash()
+    assert len(digest) == 64 and digest == module.contents_hash()
+    assert JsModule("other.js", "export const n = 1;").contents_hash() == digest
+    path.write_text("export const n = 2;")
+    assert JsModule.load(str(path)).contents_hash() != digest
+
+
+def test_js_module_transpile() -> None:
+    code = JsModule.transpile("const n: number = 1; export default n as number;", "mod.ts")
+    assert "number" not in code and "const n = 1" in code
+    assert JsModule.transpile("export const a = 1;", "mod.js") == "export const a = 1;"
+    with pytest.raises(ValueError):
+        JsModule.transpile("const = ;", "broken.ts")
+
+
+def test_enable_lock_tracing() -> None:
+    context = Context()
+    events = []
+    pyrv8.enable_lock_tracing(lambda event, site: events.append(event))
+    try:
+        assert context.eval("1 + 1") == 2
+    finally:
+        pyrv8.enable_lock_tracing(None)
+    assert events[:3] == ["waiting", "acquired", "released"]
+    count = len(events)
+    context.eval("2")
+    assert len(events) == count
+    with pytest.raises(ValueError):
+        pyrv8.enable_lock_tracing(1)